@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use owo_colors::AnsiColors;
+use serde::Serialize;
+
+use crate::output::{self, ConsoleStyle, OutputOptions};
+use crate::uploader::FileMeta;
+
+/// File attribute that `--group-by` aggregates on
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupBy {
+    /// Parent directory
+    Folder,
+    /// Lowercase extension without the dot; empty for files without one
+    Extension,
+    Filetype,
+    Mime,
+}
+
+impl GroupBy {
+    fn key(self, file: &FileMeta) -> String {
+        match self {
+            GroupBy::Folder => file.folder.clone(),
+            GroupBy::Extension => Path::new(file.full_path())
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            GroupBy::Filetype => file.filetype.clone(),
+            GroupBy::Mime => file.mime.clone(),
+        }
+    }
+}
+
+/// Number and combined size of the files sharing one key
+#[derive(Serialize)]
+pub struct GroupTotals {
+    pub key: String,
+    pub count: usize,
+    pub total_size: u64,
+}
+
+/// Totals per key, largest first
+pub fn group_files(files: &[FileMeta], by: GroupBy) -> Vec<GroupTotals> {
+    let mut totals: HashMap<String, GroupTotals> = HashMap::new();
+    for file in files {
+        let key = by.key(file);
+        let group = totals.entry(key.clone()).or_insert_with(|| GroupTotals {
+            key,
+            count: 0,
+            total_size: 0,
+        });
+        group.count += 1;
+        group.total_size += file.size;
+    }
+
+    let mut groups: Vec<GroupTotals> = totals.into_values().collect();
+    groups.sort_by(|a, b| {
+        b.total_size
+            .cmp(&a.total_size)
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    groups
+}
+
+/// Renders the totals as human-readable console lines
+pub fn generate_console(groups: &[GroupTotals], style: ConsoleStyle) -> String {
+    let mut output = String::new();
+    for group in groups {
+        let key = if group.key.is_empty() {
+            "(none)"
+        } else {
+            &group.key
+        };
+        output.push_str(&format!(
+            "{}{}: {} files, {}\n",
+            style.icon("📦"),
+            key,
+            group.count,
+            style.paint(output::format_size(group.total_size), AnsiColors::Yellow)
+        ));
+    }
+    output
+}
+
+/// Renders the totals as TOML with one `[[groups]]` table per key
+pub fn generate_toml(groups: &[GroupTotals]) -> String {
+    #[derive(Serialize)]
+    struct Document<'a> {
+        groups: &'a [GroupTotals],
+    }
+    toml::to_string(&Document { groups }).expect("Failed to serialize to TOML")
+}
+
+/// Renders the totals as CSV with one row per key
+pub fn generate_csv(groups: &[GroupTotals], options: &OutputOptions) -> String {
+    let mut writer = output::csv_writer(options);
+    for group in groups {
+        writer.serialize(group).expect("Failed to serialize to CSV");
+    }
+    output::finish_csv(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{scan_folder, ScanOptions};
+
+    fn totals(groups: &[GroupTotals]) -> Vec<(&str, usize, u64)> {
+        groups
+            .iter()
+            .map(|group| (group.key.as_str(), group.count, group.total_size))
+            .collect()
+    }
+
+    #[test]
+    fn grouping_a_mixed_dir_by_filetype_matches_hand_totals() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, size, fill) in [
+            ("a.jpg", 1000, 1),
+            ("b.PNG", 3000, 2),
+            ("trip/c.jpg", 500, 3),
+            ("clip.mp4", 6000, 4),
+            ("song.mp3", 2000, 5),
+            ("notes.txt", 100, 6),
+        ] {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, vec![fill; size]).unwrap();
+        }
+        let files = scan_folder(
+            &[dir.path().display().to_string()],
+            &ScanOptions::default(),
+            None,
+        )
+        .into_files();
+
+        assert_eq!(
+            totals(&group_files(&files, GroupBy::Filetype)),
+            [
+                ("video", 1, 6000),
+                ("image", 3, 4500),
+                ("audio", 1, 2000),
+                ("other", 1, 100),
+            ]
+        );
+        assert_eq!(
+            totals(&group_files(&files, GroupBy::Extension)),
+            [
+                ("mp4", 1, 6000),
+                ("png", 1, 3000),
+                ("mp3", 1, 2000),
+                ("jpg", 2, 1500),
+                ("txt", 1, 100),
+            ]
+        );
+    }
+}
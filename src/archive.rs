@@ -0,0 +1,247 @@
+use std::fs;
+use std::io::{self, BufReader, Read};
+use std::time::{Duration, UNIX_EPOCH};
+
+use flate2::read::GzDecoder;
+use mime_guess::from_path;
+
+use crate::scanner::{self, FileFilter, HashStrategy};
+use crate::uploader::FileMeta;
+
+/// Separates the archive path from the entry path, e.g. `backup.zip!photos/a.jpg`
+pub const ENTRY_SEPARATOR: char = '!';
+
+#[derive(Clone, Copy)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+/// Container layout of a scanned file, when `--expand-archives` can read it
+fn archive_kind(file: &FileMeta) -> Option<ArchiveKind> {
+    match file.mime.as_str() {
+        "application/zip" => Some(ArchiveKind::Zip),
+        "application/x-tar" => Some(ArchiveKind::Tar),
+        // A gzip stream is only unpacked when its name says it holds a tarball
+        "application/gzip" | "application/x-gzip" => {
+            let name = file.full_path().to_ascii_lowercase();
+            (name.ends_with(".tar.gz") || name.ends_with(".tgz")).then_some(ArchiveKind::TarGz)
+        }
+        _ => None,
+    }
+}
+
+/// Whether `file` is a zip, tar, or gzipped tar archive
+pub fn is_expandable(file: &FileMeta) -> bool {
+    archive_kind(file).is_some()
+}
+
+/// Hashes the decompressed contents of every regular entry in `archive`,
+/// keeping those within the filter's size range; nested archives stay opaque
+pub fn expand_entries(
+    archive: &FileMeta,
+    hash_strategy: HashStrategy,
+    filter: &FileFilter,
+) -> io::Result<Vec<FileMeta>> {
+    let Some(kind) = archive_kind(archive) else {
+        return Ok(Vec::new());
+    };
+    let file = BufReader::new(fs::File::open(archive.os_path())?);
+    let expander = Expander {
+        archive,
+        hash_strategy,
+        filter,
+    };
+    match kind {
+        ArchiveKind::Zip => expander.zip(file),
+        ArchiveKind::Tar => expander.tar(file),
+        ArchiveKind::TarGz => expander.tar(GzDecoder::new(file)),
+    }
+}
+
+struct Expander<'a> {
+    archive: &'a FileMeta,
+    hash_strategy: HashStrategy,
+    filter: &'a FileFilter,
+}
+
+impl Expander<'_> {
+    fn zip(&self, reader: impl Read + io::Seek) -> io::Result<Vec<FileMeta>> {
+        let mut zip = zip::ZipArchive::new(reader).map_err(io::Error::other)?;
+        let mut entries = Vec::new();
+        for index in 0..zip.len() {
+            let entry = zip.by_index(index).map_err(io::Error::other)?;
+            if !entry.is_file() || !self.filter.size_in_range(entry.size()) {
+                continue;
+            }
+            // Zip timestamps are local time without a zone, so none is reported
+            let name = entry.name().to_string();
+            let size = entry.size();
+            entries.push(self.entry(&name, size, None, entry)?);
+        }
+        Ok(entries)
+    }
+
+    fn tar(&self, reader: impl Read) -> io::Result<Vec<FileMeta>> {
+        let mut tar = tar::Archive::new(reader);
+        let mut entries = Vec::new();
+        for entry in tar.entries()? {
+            let entry = entry?;
+            if !entry.header().entry_type().is_file() || !self.filter.size_in_range(entry.size()) {
+                continue;
+            }
+            let name = entry.path()?.display().to_string();
+            let size = entry.size();
+            let modified = entry.header().mtime().ok().map(|secs| {
+                chrono::DateTime::<chrono::Utc>::from(UNIX_EPOCH + Duration::from_secs(secs))
+                    .to_rfc3339()
+            });
+            entries.push(self.entry(&name, size, modified, entry)?);
+        }
+        Ok(entries)
+    }
+
+    /// Hashes one entry in full; entries are streamed, so they are never sampled
+    fn entry(
+        &self,
+        name: &str,
+        size: u64,
+        modified: Option<String>,
+        contents: impl Read,
+    ) -> io::Result<FileMeta> {
+        let algorithm = self.hash_strategy.algorithm;
+        let hash = self
+            .hash_strategy
+            .encoding
+            .encode(&algorithm.digest_reader(contents)?);
+        let mime = from_path(name).first_or_octet_stream().to_string();
+        Ok(FileMeta {
+            filename: format!("{}{}{}", self.archive.full_path(), ENTRY_SEPARATOR, name),
+            path: None,
+            path_bytes: None,
+            folder: self.archive.folder.clone(),
+            size,
+            filetype: scanner::determine_file_type_fast(&mime),
+            mime,
+            hash,
+            hash_algo: algorithm.name().to_string(),
+            duplicate_paths: Vec::new(),
+            modified,
+            created: None,
+            width: None,
+            height: None,
+            duration_secs: None,
+            codec: None,
+            camera_make: None,
+            camera_model: None,
+            taken_at: None,
+            latitude: None,
+            longitude: None,
+            chunks: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use crate::scanner::{scan_folder, ScanOptions};
+
+    fn write_zip(path: &std::path::Path, entries: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, contents) in entries {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn scanned(root: &std::path::Path, expand_archives: bool) -> Vec<(String, String)> {
+        let mut options = ScanOptions::default();
+        options.process.expand_archives = expand_archives;
+        let report = scan_folder(&[root.display().to_string()], &options, None);
+        assert!(report.errors.is_empty());
+        report
+            .files
+            .into_iter()
+            .map(|file| {
+                let name = file
+                    .filename
+                    .strip_prefix(&format!("{}/", root.display()))
+                    .unwrap()
+                    .to_string();
+                (name, file.hash)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zip_entries_are_listed_with_their_own_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        write_zip(
+            &dir.path().join("backup.zip"),
+            &[
+                ("photos/a.jpg", b"first entry"),
+                ("notes.txt", b"second entry"),
+            ],
+        );
+
+        let files = scanned(dir.path(), true);
+        let entries: Vec<(&str, &str)> = files
+            .iter()
+            .filter(|(name, _)| name.contains(ENTRY_SEPARATOR))
+            .map(|(name, hash)| (name.as_str(), hash.as_str()))
+            .collect();
+        let expected_a = blake3::hash(b"first entry").to_hex().to_string();
+        let expected_notes = blake3::hash(b"second entry").to_hex().to_string();
+        assert_eq!(
+            entries,
+            [
+                ("backup.zip!notes.txt", expected_notes.as_str()),
+                ("backup.zip!photos/a.jpg", expected_a.as_str()),
+            ]
+        );
+        assert_ne!(entries[0].1, entries[1].1);
+        // The archive itself is still emitted
+        assert!(files.iter().any(|(name, _)| name == "backup.zip"));
+    }
+
+    #[test]
+    fn archives_stay_opaque_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        write_zip(&dir.path().join("backup.zip"), &[("a.jpg", b"entry")]);
+        let names: Vec<String> = scanned(dir.path(), false)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["backup.zip"]);
+    }
+
+    #[test]
+    fn gzipped_tar_entries_are_expanded() {
+        let dir = tempfile::tempdir().unwrap();
+        let encoder = flate2::write::GzEncoder::new(
+            fs::File::create(dir.path().join("backup.tar.gz")).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_mtime(1_700_000_000);
+        header.set_cksum();
+        tar.append_data(&mut header, "clip.mp4", &b"video"[..])
+            .unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let names: Vec<String> = scanned(dir.path(), true)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["backup.tar.gz", "backup.tar.gz!clip.mp4"]);
+    }
+}
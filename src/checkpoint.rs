@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::warning;
+
+const CHECKPOINT_FLUSH_INTERVAL: usize = 1000; // Flush after every 1000 newly hashed files
+
+/// A previously computed hash, valid while the file's size and mtime are unchanged
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckpointEntry {
+    pub size: u64,
+    pub mtime_ns: u64,
+    pub hash: String,
+    pub hash_algo: String,
+}
+
+/// Resumable record of hashed files, keyed by full path
+pub struct Checkpoint {
+    /// Backing file; `None` keeps the entries in memory only
+    path: Option<PathBuf>,
+    entries: DashMap<String, CheckpointEntry>,
+    /// Most entries kept; recording past it evicts an arbitrary entry
+    capacity: Option<usize>,
+    pending: AtomicUsize,
+    hits: AtomicUsize,
+    write_lock: Mutex<()>,
+}
+
+impl Checkpoint {
+    /// Loads an existing checkpoint, or starts an empty one if the file is missing
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let entries = DashMap::new();
+        if path.exists() {
+            let content = fs::read_to_string(path)?;
+            let stored: BTreeMap<String, CheckpointEntry> = serde_json::from_str(&content)?;
+            for (file_path, entry) in stored {
+                entries.insert(file_path, entry);
+            }
+        }
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            entries,
+            capacity: None,
+            pending: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    /// Starts an empty checkpoint that is never written to disk, for caching
+    /// hashes across scans within one process; it holds at most `capacity` entries
+    pub fn in_memory(capacity: usize) -> Self {
+        Self {
+            path: None,
+            entries: DashMap::new(),
+            capacity: Some(capacity),
+            pending: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Number of files loaded from a previous run
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether a previous run left no entries to reuse
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of hashes reused instead of recomputed during this run
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the stored hash if the file is unchanged since it was recorded
+    pub fn lookup(
+        &self,
+        file_path: &str,
+        metadata: &fs::Metadata,
+        hash_algo: &str,
+    ) -> Option<String> {
+        let entry = self.entries.get(file_path)?;
+        if entry.size == metadata.len()
+            && entry.mtime_ns == mtime_ns(metadata)
+            && entry.hash_algo == hash_algo
+        {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            Some(entry.hash.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Records a freshly computed hash, flushing to disk periodically
+    pub fn record(&self, file_path: &str, metadata: &fs::Metadata, hash: &str, hash_algo: &str) {
+        if let Some(capacity) = self.capacity {
+            if self.entries.len() >= capacity && !self.entries.contains_key(file_path) {
+                // The key is cloned first: removing while iterating would deadlock the shard
+                let evicted = self.entries.iter().next().map(|entry| entry.key().clone());
+                if let Some(evicted) = evicted {
+                    self.entries.remove(&evicted);
+                }
+            }
+        }
+        self.entries.insert(
+            file_path.to_string(),
+            CheckpointEntry {
+                size: metadata.len(),
+                mtime_ns: mtime_ns(metadata),
+                hash: hash.to_string(),
+                hash_algo: hash_algo.to_string(),
+            },
+        );
+
+        let pending = self.pending.fetch_add(1, Ordering::Relaxed) + 1;
+        if pending >= CHECKPOINT_FLUSH_INTERVAL {
+            // Another thread already flushing is fine; the next interval catches up
+            if let Ok(_guard) = self.write_lock.try_lock() {
+                self.pending.store(0, Ordering::Relaxed);
+                if let Err(e) = self.write_to_disk() {
+                    warning!("⚠️  Failed to write checkpoint: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Writes all entries to disk
+    pub fn flush(&self) -> std::io::Result<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.pending.store(0, Ordering::Relaxed);
+        self.write_to_disk()
+    }
+
+    /// Writes via a temporary file so an interrupted flush never corrupts the checkpoint
+    fn write_to_disk(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let snapshot: BTreeMap<String, CheckpointEntry> = self
+            .entries
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        let json = serde_json::to_string(&snapshot)?;
+
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+/// Modification time in nanoseconds since the Unix epoch, or 0 if unavailable
+fn mtime_ns(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{self, ScanOptions};
+
+    #[test]
+    fn second_run_reuses_every_checkpointed_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("photos");
+        fs::create_dir(&root).unwrap();
+        for (name, fill) in [("a.jpg", 1u8), ("b.jpg", 2), ("c.jpg", 3)] {
+            fs::write(root.join(name), vec![fill; 4096]).unwrap();
+        }
+        let checkpoint_path = dir.path().join("scan.checkpoint");
+        let folders = [root.display().to_string()];
+        let options = ScanOptions::default();
+
+        let checkpoint = Checkpoint::load(&checkpoint_path).unwrap();
+        let first = scanner::scan_folder(&folders, &options, Some(&checkpoint));
+        assert_eq!(checkpoint.hits(), 0);
+
+        // Rewrite a file but keep its size and mtime: only a re-hash would notice
+        let changed = root.join("a.jpg");
+        let mtime = fs::metadata(&changed).unwrap().modified().unwrap();
+        fs::write(&changed, vec![9u8; 4096]).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&changed)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let checkpoint = Checkpoint::load(&checkpoint_path).unwrap();
+        assert_eq!(checkpoint.len(), 3);
+        let second = scanner::scan_folder(&folders, &options, Some(&checkpoint));
+        assert_eq!(checkpoint.hits(), 3);
+        let hashes = |report: &scanner::ScanReport| -> Vec<String> {
+            report.files.iter().map(|file| file.hash.clone()).collect()
+        };
+        assert_eq!(hashes(&second), hashes(&first));
+    }
+
+    #[test]
+    fn sampled_hashes_are_not_reused_for_a_full_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.bin");
+        let content: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &content).unwrap();
+        let checkpoint_path = dir.path().join("scan.checkpoint");
+        let folders = [dir.path().join("large.bin").display().to_string()];
+        let mut options = ScanOptions::default();
+        options.hash.large_threshold = 4096;
+        options.hash.sample_threshold = 8192;
+        options.hash.sample_size = 1024;
+
+        let checkpoint = Checkpoint::load(&checkpoint_path).unwrap();
+        let sampled = scanner::scan_folder(&folders, &options, Some(&checkpoint));
+        checkpoint.flush().unwrap();
+
+        options.hash.full_hash = true;
+        let checkpoint = Checkpoint::load(&checkpoint_path).unwrap();
+        let full = scanner::scan_folder(&folders, &options, Some(&checkpoint));
+        assert_eq!(checkpoint.hits(), 0);
+        assert_ne!(full.files[0].hash, sampled.files[0].hash);
+        assert_eq!(
+            full.files[0].hash,
+            crate::hash::HashAlgorithm::Blake3.hash_bytes(&content)
+        );
+    }
+
+    #[test]
+    fn in_memory_cache_evicts_past_its_capacity() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Checkpoint::in_memory(2);
+        for name in ["a", "b", "c"] {
+            let path = dir.path().join(name);
+            fs::write(&path, name).unwrap();
+            let metadata = fs::metadata(&path).unwrap();
+            cache.record(&path.display().to_string(), &metadata, name, "blake3");
+        }
+        assert_eq!(cache.len(), 2);
+
+        // Re-recording a cached file replaces it instead of evicting another
+        let path = dir.path().join("c");
+        cache.record(
+            &path.display().to_string(),
+            &fs::metadata(&path).unwrap(),
+            "c2",
+            "blake3",
+        );
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command};
+use serde::Deserialize;
+
+/// Config file picked up from the scan root when `--config` is not given
+pub const DEFAULT_CONFIG_FILE: &str = ".biebie.toml";
+
+/// Option defaults read from a TOML file
+///
+/// Keys are long flag names (`min-size` or `min_size`) plus `folders` for the
+/// scan paths, and values are written the way they would be on the command
+/// line: `min-size = "10MB"`, `exclude = ["cache/", "*.tmp"]`, `full-hash = true`.
+#[derive(Deserialize, Debug, Default)]
+pub struct Config {
+    #[serde(flatten)]
+    values: BTreeMap<String, toml::Value>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Returns `.biebie.toml` in the scan root if one exists
+    pub fn discover(root: &Path) -> Option<PathBuf> {
+        let path = root.join(DEFAULT_CONFIG_FILE);
+        path.is_file().then_some(path)
+    }
+
+    /// Converts the file's values into command-line arguments for every option
+    /// not already given on the command line or through the environment, so
+    /// they go through the same parsing and validation as typed flags
+    pub fn to_args(&self, command: &Command, matches: &ArgMatches) -> Result<Vec<String>, String> {
+        let mut args = Vec::new();
+        for (key, value) in &self.values {
+            let name = key.replace('_', "-");
+            let arg = command
+                .get_arguments()
+                .find(|arg| match arg.get_long() {
+                    Some(long) => long == name,
+                    None => arg.get_id() == key.as_str(),
+                })
+                .filter(|arg| arg.get_id() != "config")
+                .ok_or_else(|| format!("unknown option in config file: {}", key))?;
+
+            let id = arg.get_id().as_str();
+            if matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            ) {
+                continue;
+            }
+
+            let values = match value {
+                toml::Value::Array(items) => items.iter().collect(),
+                value => vec![value],
+            };
+            for value in values {
+                let value = match value {
+                    toml::Value::String(s) => s.clone(),
+                    // Counted flags such as `verbose = 2` repeat the flag instead
+                    toml::Value::Integer(n) if matches!(arg.get_action(), ArgAction::Count) => {
+                        for _ in 0..*n {
+                            args.push(format!("--{}", name));
+                        }
+                        continue;
+                    }
+                    toml::Value::Integer(n) => n.to_string(),
+                    toml::Value::Float(n) => n.to_string(),
+                    toml::Value::Boolean(enabled) => {
+                        // Flags take no value; `false` leaves them at their default
+                        if *enabled {
+                            args.push(format!("--{}", name));
+                        }
+                        continue;
+                    }
+                    _ => return Err(format!("unsupported value for {} in config file", key)),
+                };
+                match arg.get_long() {
+                    Some(long) => args.push(format!("--{}={}", long, value)),
+                    None => args.push(value),
+                }
+            }
+        }
+        Ok(args)
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::uploader::FileMeta;
+
+/// Differences between a previous scan and the current one, keyed by full path
+pub struct ScanDiff {
+    pub added: Vec<FileMeta>,
+    pub modified: Vec<FileMeta>,
+    pub removed: Vec<FileMeta>,
+}
+
+impl ScanDiff {
+    /// Files that are new or changed, sorted by filename
+    pub fn changed_files(&self) -> Vec<FileMeta> {
+        let mut changed: Vec<FileMeta> = self
+            .added
+            .iter()
+            .chain(self.modified.iter())
+            .cloned()
+            .collect();
+        changed.sort_by(|a, b| a.filename.cmp(&b.filename));
+        changed
+    }
+}
+
+/// Shapes of a previous `--output json` file
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PreviousScan {
+    Files(Vec<FileMeta>),
+    /// Written under `--manifest-hash`
+    Fingerprinted {
+        files: Vec<FileMeta>,
+    },
+}
+
+/// Loads a previous `--output json` or `--output ndjson` file, with or without
+/// the `--manifest-hash` fingerprint
+pub fn load_previous(path: &Path) -> Result<Vec<FileMeta>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    match serde_json::from_str(&content) {
+        Ok(PreviousScan::Files(files) | PreviousScan::Fingerprinted { files }) => Ok(files),
+        Err(e) if content.trim_start().starts_with('[') => Err(e.into()),
+        // One record per line; the fingerprint trailer is not a file
+        Err(_) => content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str::<serde_json::Value>)
+            .filter(|record| !matches!(record, Ok(value) if value.get("manifest_hash").is_some()))
+            .map(|record| Ok(serde_json::from_value(record?)?))
+            .collect(),
+    }
+}
+
+/// Compares two scans by full path, treating a changed `hash` as a modification
+pub fn diff_scans(previous: &[FileMeta], current: &[FileMeta]) -> ScanDiff {
+    // Keyed on the exact path so lossily displayed names cannot collide
+    let previous_by_path: HashMap<PathBuf, &FileMeta> =
+        previous.iter().map(|file| (file.os_path(), file)).collect();
+    let current_by_path: HashMap<PathBuf, &FileMeta> =
+        current.iter().map(|file| (file.os_path(), file)).collect();
+
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+    for file in current {
+        match previous_by_path.get(&file.os_path()) {
+            None => added.push(file.clone()),
+            Some(old) if old.hash != file.hash => modified.push(file.clone()),
+            Some(_) => {}
+        }
+    }
+
+    let removed = previous
+        .iter()
+        .filter(|file| !current_by_path.contains_key(&file.os_path()))
+        .cloned()
+        .collect();
+
+    ScanDiff {
+        added,
+        modified,
+        removed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(files: &[FileMeta]) -> Vec<&str> {
+        files.iter().map(|file| file.filename.as_str()).collect()
+    }
+
+    #[test]
+    fn added_removed_and_modified_paths_are_detected() {
+        let previous = [
+            FileMeta::for_test("/photos/same.jpg").with_hash("aaa"),
+            FileMeta::for_test("/photos/edited.jpg").with_hash("bbb"),
+            FileMeta::for_test("/photos/deleted.jpg").with_hash("ccc"),
+        ];
+        let current = [
+            FileMeta::for_test("/photos/same.jpg").with_hash("aaa"),
+            FileMeta::for_test("/photos/edited.jpg").with_hash("bbb2"),
+            FileMeta::for_test("/photos/new.jpg").with_hash("ddd"),
+        ];
+
+        let diff = diff_scans(&previous, &current);
+        assert_eq!(names(&diff.added), ["/photos/new.jpg"]);
+        assert_eq!(names(&diff.removed), ["/photos/deleted.jpg"]);
+        assert_eq!(names(&diff.modified), ["/photos/edited.jpg"]);
+        assert_eq!(
+            names(&diff.changed_files()),
+            ["/photos/edited.jpg", "/photos/new.jpg"]
+        );
+    }
+
+    #[test]
+    fn previous_scan_is_loaded_from_json_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("yesterday.json");
+        let previous = vec![FileMeta::for_test("/photos/a.jpg").with_hash("aaa")];
+        fs::write(&path, serde_json::to_string(&previous).unwrap()).unwrap();
+
+        let loaded = load_previous(&path).unwrap();
+        assert_eq!(names(&loaded), ["/photos/a.jpg"]);
+        assert_eq!(loaded[0].hash, "aaa");
+    }
+}
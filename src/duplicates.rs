@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use owo_colors::AnsiColors;
+use serde::Serialize;
+
+use crate::output::{self, ConsoleStyle, OutputOptions};
+use crate::scanner::HashStrategy;
+use crate::uploader::FileMeta;
+
+/// Files sharing an identical hash
+#[derive(Serialize)]
+pub struct DuplicateGroup {
+    pub hash: String,
+    pub paths: Vec<String>,
+    /// Space reclaimable by keeping one copy: size × (count - 1)
+    pub wasted_bytes: u64,
+    #[serde(skip)]
+    pub size: u64,
+}
+
+/// Groups files by hash, keeping only groups of two or more, largest waste first
+pub fn find_duplicate_groups(files: &[FileMeta]) -> Vec<DuplicateGroup> {
+    let mut by_hash: HashMap<&str, Vec<&FileMeta>> = HashMap::new();
+    for file in files {
+        by_hash.entry(file.hash.as_str()).or_default().push(file);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(hash, members)| {
+            let size = members[0].size;
+            let mut paths: Vec<String> =
+                members.iter().map(|f| f.full_path().to_string()).collect();
+            paths.sort();
+            DuplicateGroup {
+                hash: hash.to_string(),
+                wasted_bytes: size * (members.len() as u64 - 1),
+                paths,
+                size,
+            }
+        })
+        .collect();
+
+    groups.sort_by(|a, b| {
+        b.wasted_bytes
+            .cmp(&a.wasted_bytes)
+            .then_with(|| a.hash.cmp(&b.hash))
+    });
+    groups
+}
+
+/// Number of groups whose hash came from sampling rather than full content
+/// and was not confirmed by `--rehash-on-size-match`
+pub fn count_sampled_groups(groups: &[DuplicateGroup], strategy: &HashStrategy) -> usize {
+    if strategy.rehash_on_size_match {
+        return 0;
+    }
+    groups
+        .iter()
+        .filter(|group| strategy.samples(group.size))
+        .count()
+}
+
+/// Renders the report as human-readable console lines
+pub fn generate_console(groups: &[DuplicateGroup], style: ConsoleStyle) -> String {
+    let mut output = String::new();
+    for group in groups {
+        output.push_str(&format!(
+            "{}{} ({} copies, {} bytes wasted)\n",
+            style.icon("🔁"),
+            style.paint(&group.hash, AnsiColors::Magenta),
+            group.paths.len(),
+            style.paint(group.wasted_bytes, AnsiColors::Yellow)
+        ));
+        for path in &group.paths {
+            output.push_str(&format!("   {}\n", path));
+        }
+    }
+    output
+}
+
+/// Renders the report as TOML with one `[[groups]]` table per group
+pub fn generate_toml(groups: &[DuplicateGroup]) -> String {
+    #[derive(Serialize)]
+    struct Document<'a> {
+        groups: &'a [DuplicateGroup],
+    }
+    toml::to_string(&Document { groups }).expect("Failed to serialize to TOML")
+}
+
+/// Renders the report as CSV with one row per duplicate path
+pub fn generate_csv(groups: &[DuplicateGroup], options: &OutputOptions) -> String {
+    let mut writer = output::csv_writer(options);
+    if options.csv_header {
+        writer
+            .write_record(["hash", "wasted_bytes", "path"])
+            .expect("Failed to serialize to CSV");
+    }
+    for group in groups {
+        for path in &group.paths {
+            writer
+                .write_record([group.hash.as_str(), &group.wasted_bytes.to_string(), path])
+                .expect("Failed to serialize to CSV");
+        }
+    }
+    output::finish_csv(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_files_form_one_group_and_unique_files_none() {
+        let files = [
+            FileMeta::for_test("/photos/b.jpg")
+                .with_size(500)
+                .with_hash("same"),
+            FileMeta::for_test("/photos/unique.jpg")
+                .with_size(900)
+                .with_hash("other"),
+            FileMeta::for_test("/photos/a.jpg")
+                .with_size(500)
+                .with_hash("same"),
+        ];
+        let groups = find_duplicate_groups(&files);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(
+            serde_json::to_value(&groups[0]).unwrap(),
+            serde_json::json!({
+                "hash": "same",
+                "paths": ["/photos/a.jpg", "/photos/b.jpg"],
+                "wasted_bytes": 500,
+            })
+        );
+    }
+
+    #[test]
+    fn groups_are_sorted_by_wasted_space() {
+        let files = [
+            FileMeta::for_test("/a1").with_size(100).with_hash("small"),
+            FileMeta::for_test("/a2").with_size(100).with_hash("small"),
+            FileMeta::for_test("/a3").with_size(100).with_hash("small"),
+            FileMeta::for_test("/b1").with_size(1000).with_hash("large"),
+            FileMeta::for_test("/b2").with_size(1000).with_hash("large"),
+        ];
+        let wasted: Vec<u64> = find_duplicate_groups(&files)
+            .iter()
+            .map(|group| group.wasted_bytes)
+            .collect();
+        assert_eq!(wasted, [1000, 200]);
+    }
+
+    #[test]
+    fn sampled_groups_are_counted_unless_rehashed() {
+        let strategy = HashStrategy {
+            sample_threshold: 1000,
+            ..HashStrategy::default()
+        };
+        let files = [
+            FileMeta::for_test("/big1").with_size(5000).with_hash("big"),
+            FileMeta::for_test("/big2").with_size(5000).with_hash("big"),
+            FileMeta::for_test("/s1").with_size(10).with_hash("s"),
+            FileMeta::for_test("/s2").with_size(10).with_hash("s"),
+        ];
+        let groups = find_duplicate_groups(&files);
+        assert_eq!(count_sampled_groups(&groups, &strategy), 1);
+
+        let rehashed = HashStrategy {
+            rehash_on_size_match: true,
+            ..strategy
+        };
+        assert_eq!(count_sampled_groups(&groups, &rehashed), 0);
+    }
+}
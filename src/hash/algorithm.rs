@@ -0,0 +1,165 @@
+use std::io::Read;
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use xxhash_rust::xxh3::Xxh3;
+
+use super::HashEncoding;
+
+const STREAM_CHUNK_SIZE: usize = 1024 * 1024; // 1MB reads for streamed hashing
+
+/// Hash algorithm used for every hashing path (direct, mmap, and sampled)
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[default]
+    Blake3,
+    Sha256,
+    Md5,
+    /// 64-bit XXH3; much faster on small files but non-cryptographic, so fine
+    /// for dedup and change detection, not for tamper-proof integrity checks
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    /// Creates a fresh incremental hasher for this algorithm
+    pub fn hasher(self) -> Box<dyn Hasher> {
+        match self {
+            HashAlgorithm::Blake3 => Box::new(blake3::Hasher::new()),
+            HashAlgorithm::Sha256 => Box::new(Sha256::new()),
+            HashAlgorithm::Md5 => Box::new(Md5::new()),
+            HashAlgorithm::Xxh3 => Box::new(Xxh3::new()),
+        }
+    }
+
+    /// Hashes a complete in-memory buffer and returns the hex digest
+    pub fn hash_bytes(self, data: &[u8]) -> String {
+        HashEncoding::Hex.encode(&self.digest_bytes(data))
+    }
+
+    /// Hashes a complete in-memory buffer and returns the raw digest
+    pub fn digest_bytes(self, data: &[u8]) -> Vec<u8> {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    /// Hashes a reader in fixed-size chunks without holding it all in memory
+    pub fn digest_reader(self, mut reader: impl Read) -> std::io::Result<Vec<u8>> {
+        let mut hasher = self.hasher();
+        let mut buffer = vec![0; STREAM_CHUNK_SIZE];
+        loop {
+            let bytes_read = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            hasher.update(&buffer[..bytes_read]);
+        }
+        Ok(hasher.finalize())
+    }
+
+    /// Parses a `FileMeta.hash_algo` name back into an algorithm
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "blake3" => Some(HashAlgorithm::Blake3),
+            "sha256" => Some(HashAlgorithm::Sha256),
+            "md5" => Some(HashAlgorithm::Md5),
+            "xxh3" => Some(HashAlgorithm::Xxh3),
+            _ => None,
+        }
+    }
+
+    /// Name as written to `FileMeta.hash_algo`
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+}
+
+/// Minimal incremental hashing interface shared by all algorithms
+pub trait Hasher: Send {
+    fn update(&mut self, data: &[u8]);
+    /// Raw digest bytes, most significant first
+    fn finalize(self: Box<Self>) -> Vec<u8>;
+}
+
+impl Hasher for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        blake3::Hasher::finalize(&self).as_bytes().to_vec()
+    }
+}
+
+impl Hasher for Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        Digest::finalize(*self).to_vec()
+    }
+}
+
+impl Hasher for Md5 {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        Digest::finalize(*self).to_vec()
+    }
+}
+
+impl Hasher for Xxh3 {
+    fn update(&mut self, data: &[u8]) {
+        Xxh3::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        self.digest().to_be_bytes().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha256_matches_the_published_digest() {
+        assert_eq!(
+            HashAlgorithm::Sha256.hash_bytes(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn streamed_and_buffered_digests_agree() {
+        let data = vec![7u8; STREAM_CHUNK_SIZE * 2 + 5];
+        for algorithm in [
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Md5,
+            HashAlgorithm::Xxh3,
+        ] {
+            assert_eq!(
+                algorithm.digest_reader(&data[..]).unwrap(),
+                algorithm.digest_bytes(&data)
+            );
+        }
+    }
+
+    #[test]
+    fn xxh3_matches_the_reference_digest() {
+        // XXH3_64bits of empty input, from the xxHash reference implementation
+        assert_eq!(HashAlgorithm::Xxh3.hash_bytes(b""), "2d06800538d394c2");
+        assert_eq!(HashAlgorithm::Xxh3.digest_bytes(b"").len(), 8);
+    }
+}
@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::Path;
+
+use fastcdc::v2020::FastCDC;
+use memmap2::Mmap;
+
+/// Smallest chunk FastCDC cuts, except for the end of the file
+pub const CHUNK_MIN_SIZE: u32 = 16 * 1024;
+/// Chunk size FastCDC aims for on average
+pub const CHUNK_AVG_SIZE: u32 = 64 * 1024;
+/// Largest chunk FastCDC cuts
+pub const CHUNK_MAX_SIZE: u32 = 256 * 1024;
+
+/// BLAKE3 hashes of the file's content-defined chunks, in file order
+///
+/// Chunk boundaries depend on the content rather than on offsets, so appending
+/// to or editing a file only changes the chunks around the edit. Each hash is
+/// truncated to 16 hex characters, which is plenty for comparing files.
+pub fn chunk_hashes(path: &Path) -> std::io::Result<Vec<String>> {
+    let file = fs::File::open(path)?;
+    if file.metadata()?.len() == 0 {
+        return Ok(Vec::new());
+    }
+    // Some filesystems and special files cannot be mapped; read those instead
+    let content = match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => ChunkSource::Mapped(mmap),
+        Err(_) => ChunkSource::Read(fs::read(path)?),
+    };
+    Ok(chunk_bytes(content.as_ref()))
+}
+
+/// Chunk hashes of an in-memory buffer, as [`chunk_hashes`] computes them
+pub fn chunk_bytes(content: &[u8]) -> Vec<String> {
+    FastCDC::new(content, CHUNK_MIN_SIZE, CHUNK_AVG_SIZE, CHUNK_MAX_SIZE)
+        .map(|chunk| {
+            let data = &content[chunk.offset..chunk.offset + chunk.length];
+            blake3::hash(data).to_hex()[..16].to_string()
+        })
+        .collect()
+}
+
+enum ChunkSource {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl AsRef<[u8]> for ChunkSource {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            ChunkSource::Mapped(mmap) => mmap,
+            ChunkSource::Read(content) => content,
+        }
+    }
+}
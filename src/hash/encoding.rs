@@ -0,0 +1,78 @@
+use base64::Engine;
+
+/// Text encoding of the digests written to `FileMeta.hash`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashEncoding {
+    /// Lowercase hexadecimal
+    #[default]
+    Hex,
+    /// Standard base64 alphabet with padding (RFC 4648), 25% shorter than hex
+    Base64,
+    /// Uppercase base32 alphabet with padding (RFC 4648), case-insensitive safe
+    Base32,
+}
+
+impl HashEncoding {
+    /// Encodes a raw digest
+    pub fn encode(self, digest: &[u8]) -> String {
+        match self {
+            HashEncoding::Hex => digest.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            HashEncoding::Base64 => base64::engine::general_purpose::STANDARD.encode(digest),
+            HashEncoding::Base32 => {
+                base32::encode(base32::Alphabet::Rfc4648 { padding: true }, digest)
+            }
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashEncoding::Hex => "hex",
+            HashEncoding::Base64 => "base64",
+            HashEncoding::Base32 => "base32",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::HashAlgorithm;
+
+    /// Bytes of a lowercase hex string
+    fn hex_decode(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn encodings_of_a_known_digest_agree_with_its_hex() {
+        let digest = HashAlgorithm::Sha256.digest_bytes(b"abc");
+        let hex = HashEncoding::Hex.encode(&digest);
+        assert_eq!(
+            hex,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(hex_decode(&hex), digest);
+
+        let base64 = HashEncoding::Base64.encode(&digest);
+        assert_eq!(base64, "ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0=");
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD
+                .decode(&base64)
+                .unwrap(),
+            hex_decode(&hex)
+        );
+
+        let base32 = HashEncoding::Base32.encode(&digest);
+        assert_eq!(
+            base32,
+            "XJ4BNP4PAHH6UQKBIDPF3LRCEOYAGYNDSYLXVHFUCD7WD4QACWWQ===="
+        );
+        assert_eq!(
+            base32::decode(base32::Alphabet::Rfc4648 { padding: true }, &base32).unwrap(),
+            hex_decode(&hex)
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use crate::uploader::FileMeta;
+
+/// BLAKE3 fingerprint of a result set, for detecting whether anything changed
+///
+/// Covers the sorted `(hash, size)` pairs only, so it is independent of scan
+/// order, paths, timestamps, and platform; renaming a file keeps it unchanged.
+pub fn manifest_hash(files: &[FileMeta]) -> String {
+    let mut pairs: Vec<(&str, u64)> = files
+        .iter()
+        .map(|file| (file.hash.as_str(), file.size))
+        .collect();
+    pairs.sort_unstable();
+
+    let mut hasher = blake3::Hasher::new();
+    for (hash, size) in pairs {
+        hasher.update(format!("{}:{}\n", hash, size).as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{scan_folder, ScanOptions};
+
+    fn scanned_manifest_hash(root: &std::path::Path) -> String {
+        let report = scan_folder(&[root.display().to_string()], &ScanOptions::default(), None);
+        manifest_hash(&report.files)
+    }
+
+    #[test]
+    fn manifest_hash_ignores_order_and_tracks_content() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, fill) in [("a.jpg", 1), ("b.jpg", 2), ("nested/c.jpg", 3)] {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, [fill; 2048]).unwrap();
+        }
+        let report = scan_folder(
+            &[dir.path().display().to_string()],
+            &ScanOptions::default(),
+            None,
+        );
+        let original = manifest_hash(&report.files);
+
+        let mut reversed = report.files.clone();
+        reversed.reverse();
+        assert_eq!(manifest_hash(&reversed), original);
+        assert_eq!(scanned_manifest_hash(dir.path()), original);
+
+        std::fs::write(dir.path().join("b.jpg"), [9; 2048]).unwrap();
+        assert_ne!(scanned_manifest_hash(dir.path()), original);
+    }
+}
@@ -1,3 +1,11 @@
+pub mod algorithm;
+pub mod chunks;
+pub mod encoding;
+pub mod manifest_hash;
 pub mod sample_hash;
 
-pub use sample_hash::compute_sample_hash;
+pub use algorithm::HashAlgorithm;
+pub use chunks::chunk_hashes;
+pub use encoding::HashEncoding;
+pub use manifest_hash::manifest_hash;
+pub use sample_hash::{auto_sample_points, compute_sample_hash, DEFAULT_SAMPLE_SIZE};
@@ -1,212 +1,152 @@
 use std::fs::File;
 
+use super::{HashAlgorithm, HashEncoding};
+
+/// Default size of each sampled region (64KB)
+pub const DEFAULT_SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Fewest samples taken when the count is picked automatically (start, middle, end)
+const MIN_AUTO_SAMPLE_POINTS: usize = 3;
+/// Most samples taken when the count is picked automatically
+const MAX_AUTO_SAMPLE_POINTS: usize = 16;
+/// One extra automatic sample per this many bytes (1GB)
+const AUTO_SAMPLE_SPACING: u64 = 1024 * 1024 * 1024;
+
+/// Sample count used when `--sample-points` is not given: three samples plus one
+/// per gigabyte, capped so very large files stay fast
+pub fn auto_sample_points(file_size: u64) -> usize {
+    let extra = (file_size / AUTO_SAMPLE_SPACING) as usize;
+    (MIN_AUTO_SAMPLE_POINTS + extra).min(MAX_AUTO_SAMPLE_POINTS)
+}
+
 /// Compute a sample-based hash of a file for duplicate detection
 ///
-/// This function reads samples from the beginning, middle, and end of a file
-/// to generate a hash that can identify duplicate files efficiently without
-/// reading the entire file content.
+/// This function reads `sample_points` evenly spaced samples, from the beginning
+/// to the end of the file, to generate a hash that can identify duplicate files
+/// efficiently without reading the entire file content. Each sample's offset is
+/// mixed into the hash so identical regions at different positions differ.
 ///
 /// # Arguments
 /// * `path` - Path to the file to hash
 /// * `file_size` - Size of the file in bytes
+/// * `algorithm` - Hash algorithm used to digest the samples
+/// * `sample_size` - Size of each sampled region in bytes
+/// * `sample_points` - Number of sampled regions (at least 1)
+/// * `encoding` - Text encoding of the returned digest
 ///
 /// # Returns
-/// * `std::io::Result<String>` - The computed hash as a hex string
-pub fn compute_sample_hash(path: &std::path::Path, file_size: u64) -> std::io::Result<String> {
+/// * `std::io::Result<String>` - The computed hash in `encoding`
+pub fn compute_sample_hash(
+    path: &std::path::Path,
+    file_size: u64,
+    algorithm: HashAlgorithm,
+    sample_size: usize,
+    sample_points: usize,
+    encoding: HashEncoding,
+) -> std::io::Result<String> {
     let file = File::open(path)?;
-    let mut hasher = blake3::Hasher::new();
+    let mut hasher = algorithm.hasher();
 
     // Sample configuration
-    let sample_size = 64 * 1024; // 64KB samples
     let mut buffer = vec![0; sample_size];
+    let offsets = sample_offsets(file_size, sample_size as u64, sample_points);
 
-    // Calculate sample positions
-    let beginning_offset = 0;
-    let middle_offset = file_size / 2;
-    let end_offset = file_size.saturating_sub(sample_size as u64);
-
-    // Platform-specific implementation with kernel optimization hints
+    // ให้ kernel รู้ว่าเราจะอ่านแบบ random access และ preload ช่วงที่จะอ่าน
     #[cfg(target_os = "linux")]
-    {
-        use std::os::unix::fs::FileExt;
-        use std::os::unix::io::AsRawFd;
-        let fd = file.as_raw_fd();
-
-        // ให้ kernel รู้ว่าเราจะอ่านแบบ random access
-        unsafe {
-            libc::posix_fadvise(fd, 0, file_size as libc::off_t, libc::POSIX_FADV_RANDOM);
-        }
-
-        // Hint kernel ว่าเราจะอ่านช่วงนี้เร็วๆ นี้ (preload to cache)
-        unsafe {
-            // Beginning sample
-            libc::posix_fadvise(
-                fd,
-                beginning_offset as libc::off_t,
-                sample_size as libc::off_t,
-                libc::POSIX_FADV_WILLNEED,
-            );
-
-            // Middle sample
-            if file_size > sample_size as u64 * 2 {
-                libc::posix_fadvise(
-                    fd,
-                    middle_offset as libc::off_t,
-                    sample_size as libc::off_t,
-                    libc::POSIX_FADV_WILLNEED,
-                );
-            }
-
-            // End sample
-            if file_size > sample_size as u64 {
-                libc::posix_fadvise(
-                    fd,
-                    end_offset as libc::off_t,
-                    sample_size as libc::off_t,
-                    libc::POSIX_FADV_WILLNEED,
-                );
-            }
-        }
-
-        // อ่านข้อมูลหลังจาก hint kernel แล้ว
-
-        // Beginning - อ่านจากตำแหน่ง 0
-        let bytes_read = file.read_at(&mut buffer, beginning_offset)?;
-        hasher.update(&buffer[..bytes_read]);
-
-        // Middle - อ่านจากตำแหน่งกลางไฟล์
-        if file_size > sample_size as u64 * 2 {
-            let bytes_read = file.read_at(&mut buffer, middle_offset)?;
-            hasher.update(&buffer[..bytes_read]);
-        }
-
-        // End - อ่านจากท้ายไฟล์
-        if file_size > sample_size as u64 {
-            let bytes_read = file.read_at(&mut buffer, end_offset)?;
-            hasher.update(&buffer[..bytes_read]);
-        }
-
-        // บอก kernel ว่าเราใช้ข้อมูลเสร็จแล้ว (สามารถ evict จาก cache ได้)
-        unsafe {
-            libc::posix_fadvise(
-                fd,
-                beginning_offset as libc::off_t,
-                sample_size as libc::off_t,
-                libc::POSIX_FADV_DONTNEED,
-            );
-            if file_size > sample_size as u64 * 2 {
-                libc::posix_fadvise(
-                    fd,
-                    middle_offset as libc::off_t,
-                    sample_size as libc::off_t,
-                    libc::POSIX_FADV_DONTNEED,
-                );
-            }
-            if file_size > sample_size as u64 {
-                libc::posix_fadvise(
-                    fd,
-                    end_offset as libc::off_t,
-                    sample_size as libc::off_t,
-                    libc::POSIX_FADV_DONTNEED,
-                );
-            }
-        }
+    advise(&file, 0, file_size, libc::POSIX_FADV_RANDOM);
+    #[cfg(target_os = "linux")]
+    for &offset in &offsets {
+        advise(&file, offset, sample_size as u64, libc::POSIX_FADV_WILLNEED);
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        use std::os::unix::fs::FileExt;
-
-        // macOS doesn't have posix_fadvise, so we just read without hints
-        // Beginning - อ่านจากตำแหน่ง 0
-        let bytes_read = file.read_at(&mut buffer, beginning_offset)?;
+    for &offset in &offsets {
+        let bytes_read = read_at(&file, &mut buffer, offset)?;
+        hasher.update(&offset.to_le_bytes());
         hasher.update(&buffer[..bytes_read]);
+    }
 
-        // Middle - อ่านจากตำแหน่งกลางไฟล์
-        if file_size > sample_size as u64 * 2 {
-            let bytes_read = file.read_at(&mut buffer, middle_offset)?;
-            hasher.update(&buffer[..bytes_read]);
-        }
-
-        // End - อ่านจากท้ายไฟล์
-        if file_size > sample_size as u64 {
-            let bytes_read = file.read_at(&mut buffer, end_offset)?;
-            hasher.update(&buffer[..bytes_read]);
-        }
+    // บอก kernel ว่าเราใช้ข้อมูลเสร็จแล้ว (สามารถ evict จาก cache ได้)
+    #[cfg(target_os = "linux")]
+    for &offset in &offsets {
+        advise(&file, offset, sample_size as u64, libc::POSIX_FADV_DONTNEED);
     }
 
-    #[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
-    {
-        use std::os::unix::fs::FileExt;
+    // Add file size to hash to distinguish files of different sizes
+    hasher.update(&file_size.to_le_bytes());
 
-        // Other Unix systems - use basic read_at without hints
-        // Beginning - อ่านจากตำแหน่ง 0
-        let bytes_read = file.read_at(&mut buffer, beginning_offset)?;
-        hasher.update(&buffer[..bytes_read]);
+    Ok(encoding.encode(&hasher.finalize()))
+}
 
-        // Middle - อ่านจากตำแหน่งกลางไฟล์
-        if file_size > sample_size as u64 * 2 {
-            let bytes_read = file.read_at(&mut buffer, middle_offset)?;
-            hasher.update(&buffer[..bytes_read]);
-        }
-
-        // End - อ่านจากท้ายไฟล์
-        if file_size > sample_size as u64 {
-            let bytes_read = file.read_at(&mut buffer, end_offset)?;
-            hasher.update(&buffer[..bytes_read]);
-        }
+/// Evenly spaced sample offsets; the first starts at 0 and the last ends at EOF
+fn sample_offsets(file_size: u64, sample_size: u64, sample_points: usize) -> Vec<u64> {
+    let last_offset = file_size.saturating_sub(sample_size);
+    if sample_points <= 1 || last_offset == 0 {
+        return vec![0];
     }
 
-    #[cfg(windows)]
-    {
-        // Windows implementation (ไม่มี posix_fadvise)
-        use std::os::windows::fs::FileExt;
-
-        // Beginning
-        let bytes_read = file.seek_read(&mut buffer, beginning_offset)?;
-        hasher.update(&buffer[..bytes_read]);
+    let intervals = sample_points as u64 - 1;
+    let mut offsets: Vec<u64> = (0..sample_points as u64)
+        .map(|i| (last_offset as u128 * i as u128 / intervals as u128) as u64)
+        .collect();
+    offsets.dedup();
+    offsets
+}
 
-        // Middle
-        if file_size > sample_size as u64 * 2 {
-            let bytes_read = file.seek_read(&mut buffer, middle_offset)?;
-            hasher.update(&buffer[..bytes_read]);
-        }
-
-        // End
-        if file_size > sample_size as u64 {
-            let bytes_read = file.seek_read(&mut buffer, end_offset)?;
-            hasher.update(&buffer[..bytes_read]);
-        }
+/// Kernel read-ahead hint for one region
+#[cfg(target_os = "linux")]
+fn advise(file: &File, offset: u64, len: u64, advice: libc::c_int) {
+    use std::os::unix::io::AsRawFd;
+    unsafe {
+        libc::posix_fadvise(
+            file.as_raw_fd(),
+            offset as libc::off_t,
+            len as libc::off_t,
+            advice,
+        );
     }
+}
 
-    // Fallback for other platforms
-    #[cfg(not(any(unix, windows)))]
-    {
-        use std::io::{Read, Seek, SeekFrom};
-        let mut file = file;
+/// Positional read that leaves the file cursor untouched
+#[cfg(unix)]
+fn read_at(file: &File, buffer: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    use std::os::unix::fs::FileExt;
+    file.read_at(buffer, offset)
+}
 
-        // Beginning
-        file.seek(SeekFrom::Start(beginning_offset))?;
-        let bytes_read = file.read(&mut buffer)?;
-        hasher.update(&buffer[..bytes_read]);
+#[cfg(windows)]
+fn read_at(file: &File, buffer: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    use std::os::windows::fs::FileExt;
+    file.seek_read(buffer, offset)
+}
 
-        // Middle
-        if file_size > sample_size as u64 * 2 {
-            file.seek(SeekFrom::Start(middle_offset))?;
-            let bytes_read = file.read(&mut buffer)?;
-            hasher.update(&buffer[..bytes_read]);
-        }
-
-        // End
-        if file_size > sample_size as u64 {
-            file.seek(SeekFrom::Start(end_offset))?;
-            let bytes_read = file.read(&mut buffer)?;
-            hasher.update(&buffer[..bytes_read]);
-        }
-    }
+// Fallback for other platforms
+#[cfg(not(any(unix, windows)))]
+fn read_at(file: &File, buffer: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut file = file;
+    file.seek(SeekFrom::Start(offset))?;
+    file.read(buffer)
+}
 
-    // Add file size to hash to distinguish files of different sizes
-    hasher.update(&file_size.to_le_bytes());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_span_the_file_evenly() {
+        assert_eq!(sample_offsets(1000, 100, 4), [0, 300, 600, 900]);
+        // Files no larger than one sample are read once from the start
+        assert_eq!(sample_offsets(100, 100, 4), [0]);
+        assert_eq!(sample_offsets(50, 100, 4), [0]);
+        // More points than bytes to spread them over collapse to distinct offsets
+        assert_eq!(sample_offsets(102, 100, 5), [0, 1, 2]);
+    }
 
-    Ok(hasher.finalize().to_hex().to_string())
+    #[test]
+    fn automatic_sample_points_are_bounded() {
+        const GB: u64 = 1024 * 1024 * 1024;
+        assert_eq!(auto_sample_points(200 * 1024 * 1024), 3);
+        assert_eq!(auto_sample_points(5 * GB), 8);
+        assert_eq!(auto_sample_points(1000 * GB), 16);
+    }
 }
@@ -0,0 +1,37 @@
+//! Scans folders for media files, hashes them, and uploads their metadata.
+//!
+//! ```no_run
+//! use biebie::{scan_folder, ScanOptions};
+//!
+//! let options = ScanOptions::default();
+//! let report = scan_folder(&["/path/to/folder".to_string()], &options, None);
+//! println!("{} unique files", report.stats.unique_files);
+//! ```
+
+pub mod aggregate;
+pub mod archive;
+pub mod checkpoint;
+pub mod config;
+pub mod diff;
+pub mod duplicates;
+pub mod hash;
+pub mod manifest;
+pub mod media;
+pub mod output;
+pub mod progress;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod scanner;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod similarity;
+pub mod status;
+pub mod uploader;
+pub mod verify;
+pub mod watch;
+
+pub use scanner::{
+    filetype_breakdown, scan_files, scan_folder, FileFilter, FiletypeTotals, HashStrategy,
+    ProcessOptions, ScanOptions, ScanReport, ScanStats, WalkOptions,
+};
+pub use uploader::{upload_metadata, upload_metadata_async, FileMeta, UploadOptions};
@@ -1,94 +1,1536 @@
-mod hash;
-mod scanner;
-mod uploader;
-
-use clap::Parser;
+use biebie::output::OutputFormat;
+use biebie::warning;
+use biebie::{
+    aggregate, checkpoint, config, diff, duplicates, hash, manifest, media, output, progress,
+    scanner, similarity, status, uploader, verify, watch,
+};
+use clap::{CommandFactory, FromArgMatches, Parser};
 
 /// Scan media folder and upload metadata to API
 #[derive(Parser)]
 struct Args {
-    /// Path to scan
-    folder: String,
+    /// Paths to scan
+    folders: Vec<String>,
+    /// Scan the paths listed in this file, one per line, instead of walking folders
+    /// (`-` reads the list from stdin)
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["folders", "verify"])]
+    files_from: Option<String>,
+    /// TOML file with defaults for any option; flags given on the command line win
+    /// [default: .biebie.toml in the scan root, if present]
+    #[arg(long, value_name = "FILE")]
+    config: Option<String>,
     /// API endpoint to upload
     #[arg(long)]
     api: Option<String>,
-    /// Output format (json, csv, or console)
+    /// Output format (console, tree, json, ndjson, csv, sqlite, parquet, toml, or yaml)
     #[arg(long, value_enum, default_value = "console")]
     output: OutputFormat,
-    /// Save output to file
+    /// Save output to file; `{date}`, `{datetime}` and `{root}` are expanded
     #[arg(long)]
     output_file: Option<String>,
+    /// Compress the written `--output-file` (gzip or zstd), adding `.gz`/`.zst` if missing
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ALGO",
+        default_value = "none",
+        requires = "output_file"
+    )]
+    compress_output: output::OutputCompression,
+    /// Colorize console output: auto (terminal only, unless `NO_COLOR` is set), always, or never
+    #[arg(long, value_enum, value_name = "WHEN", default_value = "auto")]
+    color: output::ColorWhen,
+    /// Leave emoji out of console output and status messages (implied by `NO_COLOR`)
+    #[arg(long)]
+    no_emoji: bool,
+    /// Indent JSON output [default: when printing to a terminal]
+    #[arg(long, conflicts_with = "compact")]
+    pretty: bool,
+    /// Write JSON output on a single line [default: when piped or saved to a file]
+    #[arg(long)]
+    compact: bool,
+    /// Only emit these fields, in this order, e.g. `hash,size,filename`
+    #[arg(
+        long,
+        value_name = "LIST",
+        value_delimiter = ',',
+        value_parser = output::parse_field,
+        conflicts_with = "find_duplicates"
+    )]
+    output_fields: Vec<String>,
+    /// Overwrite `--output-file` if it already exists
+    #[arg(long)]
+    force: bool,
+    /// Append to `--output-file` instead of replacing it (ndjson and csv only);
+    /// the CSV header is only written to an empty file
+    #[arg(long, requires = "output_file", conflicts_with = "force")]
+    append: bool,
+    /// Write the full scan report (files, stats, errors) as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    report: Option<String>,
+    /// Print a BLAKE3 fingerprint of all (hash, size) pairs, stable across runs
+    /// and platforms, and include it in the `--report`; it only changes when
+    /// file contents do. JSON output becomes `{"manifest_hash", "files"}` and
+    /// NDJSON ends with a `{"manifest_hash"}` record
+    #[arg(long)]
+    manifest_hash: bool,
+    /// Glob pattern to exclude, relative to the scan root (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Gitignore-style file of patterns to exclude (`!` re-includes), applied
+    /// relative to the scan root alongside `--exclude`
+    #[arg(long, value_name = "FILE")]
+    exclude_from: Option<String>,
+    /// Only scan files with this extension, e.g. jpg or .mp4 (repeatable)
+    #[arg(long, value_name = "EXT")]
+    include: Vec<String>,
+    /// Skip files smaller than this size, e.g. 10KB or 5MB (0 disables)
+    #[arg(long, value_name = "BYTES", value_parser = parse_size, default_value = "1024")]
+    min_size: u64,
+    /// Skip files larger than this size, e.g. 2GB
+    #[arg(long, value_name = "BYTES", value_parser = parse_size)]
+    max_size: Option<u64>,
+    /// Progress reporting (bar, json lines on stderr, or none)
+    #[arg(long, value_enum, default_value = "bar")]
+    progress: progress::ProgressMode,
+    /// Suppress informational messages (they go to stderr either way)
+    #[arg(long, short)]
+    quiet: bool,
+    /// Log more detail to stderr: -v for info, -vv for debug, -vvv for trace
+    /// (`RUST_LOG` overrides this)
+    #[arg(long, short, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// List the files that would be scanned without hashing them
+    #[arg(long)]
+    dry_run: bool,
+    /// Hash algorithm used for content hashes (xxh3 is fastest but non-cryptographic)
+    #[arg(long, value_enum, default_value = "blake3")]
+    hash_algo: hash::HashAlgorithm,
+    /// Text encoding of content hashes; --verify and --since must use the
+    /// encoding the manifest was written with
+    #[arg(long, value_enum, default_value = "hex")]
+    hash_encoding: hash::HashEncoding,
+    /// Hash entire files instead of sampling files over 100MB.
+    /// Slower, but avoids collisions between large files that share sampled regions
+    #[arg(long)]
+    full_hash: bool,
+    /// When sampled files share a hash, hash both in full before treating them as
+    /// duplicates; files that turn out to differ keep their full hash
+    #[arg(long, conflicts_with = "full_hash")]
+    rehash_on_size_match: bool,
+    /// Collect metadata only, without reading files to hash them; `hash` is left
+    /// empty and every file is kept, since duplicates cannot be detected
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "hash_algo",
+            "hash_encoding",
+            "full_hash",
+            "rehash_on_size_match",
+            "find_duplicates",
+            "find_similar",
+            "chunk_hashes",
+            "duplicates",
+            "checkpoint",
+            "since",
+            "verify",
+            "manifest_hash",
+            "upload_manifest",
+            "skip_uploaded",
+        ]
+    )]
+    no_hash: bool,
+    /// Files above this size are hashed via memory mapping
+    #[arg(long, value_name = "BYTES", value_parser = parse_size, default_value = "10MB")]
+    large_threshold: u64,
+    /// Files above this size are hashed by sampling (must be >= --large-threshold)
+    #[arg(long, value_name = "BYTES", value_parser = parse_size, default_value = "100MB")]
+    sample_threshold: u64,
+    /// Size of each sampled region when sampling
+    #[arg(long, value_name = "BYTES", value_parser = parse_size, default_value = "64KB")]
+    sample_size: u64,
+    /// Number of evenly spaced regions sampled [default: 3, plus 1 per GB up to 16]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    sample_points: Option<u64>,
+    /// Exit with a non-zero code if any file could not be scanned or was rejected by the API
+    #[arg(long)]
+    strict: bool,
+    /// Hashing threads; 0 picks a count from the CPU cores
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    threads: usize,
+    /// Thread heuristic: `io` oversubscribes, `cpu` uses physical cores, `auto` samples files
+    #[arg(long, value_enum, default_value = "io")]
+    workload: scanner::Workload,
+    /// Follow symbolic links while walking (cycles are detected and skipped)
+    #[arg(long)]
+    follow_symlinks: bool,
+    /// Maximum directory depth to descend (1 = only files directly in the root)
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+    /// Skip files and directories ignored by `.gitignore` files in or above the
+    /// scanned folders, even outside a git repository
+    #[arg(long)]
+    respect_gitignore: bool,
+    /// Skip directories holding fewer than N matching files, counted per
+    /// directory: files in subdirectories do not count toward their parent
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    min_files: usize,
+    /// After the scan, list directories whose files were all filtered out and why
+    /// (hidden, excluded, not in --include, outside the size range, or fewer
+    /// than --min-files)
+    #[arg(long)]
+    report_skipped: bool,
+    /// Checkpoint file for resuming interrupted scans; unchanged files are not rehashed
+    #[arg(long, value_name = "FILE")]
+    checkpoint: Option<String>,
+    /// Previous JSON or NDJSON output; only new or changed files are emitted
+    #[arg(long, value_name = "FILE")]
+    since: Option<String>,
+    /// Rehash files recorded in a previous JSON or NDJSON output and report mismatches
+    /// instead of scanning; exits non-zero if any file changed
+    #[arg(long, value_name = "MANIFEST")]
+    verify: Option<String>,
+    /// How files with an already-seen hash are handled
+    #[arg(long, value_enum, default_value = "skip")]
+    duplicates: scanner::DuplicateMode,
+    /// Emit every file even when its contents match another; same as `--duplicates keep-all`
+    #[arg(long, conflicts_with = "duplicates")]
+    no_dedup: bool,
+    /// Report groups of files with identical hashes instead of listing files
+    #[arg(long)]
+    find_duplicates: bool,
+    /// Emit only the scan totals (the summary, or `ScanStats` in the structured
+    /// formats) instead of one record per file
+    #[arg(
+        long,
+        conflicts_with_all = ["find_duplicates", "output_fields", "api", "watch"]
+    )]
+    stats_only: bool,
+    /// Emit the file count and total size per folder, extension, filetype, or
+    /// mime instead of one record per file
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FIELD",
+        conflicts_with_all = ["find_duplicates", "find_similar", "stats_only", "output_fields"]
+    )]
+    group_by: Option<aggregate::GroupBy>,
+    /// Record content-defined chunk hashes in a `chunks` field, for spotting
+    /// files that share most of their content (reads every file in full)
+    #[arg(long)]
+    chunk_hashes: bool,
+    /// Report pairs of files sharing at least this fraction (0-1) of their chunks
+    /// instead of listing files; implies `--chunk-hashes`
+    #[arg(
+        long,
+        value_name = "RATIO",
+        value_parser = parse_ratio,
+        conflicts_with_all = ["find_duplicates", "output_fields", "stats_only"]
+    )]
+    find_similar: Option<f64>,
+    /// Number of times to retry a failed upload (connection errors and 5xx only)
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    upload_retries: u32,
+    /// Initial delay between upload retries in milliseconds, doubled each retry
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    upload_retry_delay: u64,
+    /// Split the upload into requests of at most N files each
+    #[arg(long, value_name = "N")]
+    batch_size: Option<usize>,
+    /// Bearer token for the API (prefer the environment variable to keep it out of shell history)
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "BIEBIE_API_TOKEN",
+        hide_env_values = true
+    )]
+    auth_token: Option<String>,
+    /// Extra request header as NAME:VALUE (repeatable)
+    #[arg(long = "header", value_name = "K:V", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+    /// Request body layout: `wrapped` object with totals, bare `array`, or `ndjson`
+    #[arg(long, value_enum, default_value = "wrapped")]
+    upload_format: uploader::UploadFormat,
+    /// Record delimiting for `--upload-format ndjson`: `newline`, or
+    /// `length-prefixed` with a 4-byte big-endian length before each record,
+    /// sent as a streaming body
+    #[arg(long, value_enum, default_value = "newline")]
+    framing: uploader::Framing,
+    /// Send an `X-Content-Hash: <algo>=<hex>` header digesting each request body
+    /// as sent (after `--compress`)
+    #[arg(long, value_enum, value_name = "ALGO")]
+    content_hash: Option<hash::HashAlgorithm>,
+    /// Upload the rendered `--output` to this S3 bucket instead of an API
+    #[cfg(feature = "s3")]
+    #[arg(
+        long,
+        value_name = "BUCKET",
+        requires = "s3_key",
+        conflicts_with_all = ["api", "find_duplicates", "find_similar", "stats_only", "group_by"]
+    )]
+    s3_bucket: Option<String>,
+    /// Object key for `--s3-bucket`; `{date}`, `{datetime}` and `{root}` are expanded
+    #[cfg(feature = "s3")]
+    #[arg(long, value_name = "KEY", requires = "s3_bucket")]
+    s3_key: Option<String>,
+    /// S3-compatible endpoint, e.g. http://localhost:9000 for MinIO (uses path-style URLs)
+    #[cfg(feature = "s3")]
+    #[arg(long, value_name = "URL")]
+    s3_endpoint: Option<String>,
+    /// S3 region
+    #[cfg(feature = "s3")]
+    #[arg(long, env = "AWS_REGION", default_value = "us-east-1")]
+    s3_region: String,
+    /// S3 access key ID
+    #[cfg(feature = "s3")]
+    #[arg(
+        long,
+        value_name = "ID",
+        env = "AWS_ACCESS_KEY_ID",
+        hide_env_values = true
+    )]
+    s3_access_key_id: Option<String>,
+    /// S3 secret access key (prefer the environment variable)
+    #[cfg(feature = "s3")]
+    #[arg(
+        long,
+        value_name = "SECRET",
+        env = "AWS_SECRET_ACCESS_KEY",
+        hide_env_values = true
+    )]
+    s3_secret_access_key: Option<String>,
+    /// Gzip-compress upload request bodies
+    #[arg(long)]
+    compress: bool,
+    /// Upload request timeout in seconds (0 = no timeout)
+    #[arg(long, value_name = "SECS", default_value_t = 30)]
+    upload_timeout: u64,
+    /// Connection timeout in seconds (0 = no timeout)
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    connect_timeout: u64,
+    /// Upload up to N batches concurrently using the async client
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    concurrent_uploads: usize,
+    /// Write each uploaded file's hash and HTTP status to this JSON file
+    #[arg(long, value_name = "FILE")]
+    upload_manifest: Option<String>,
+    /// Skip uploading files whose hash was uploaded according to this manifest
+    #[arg(long, value_name = "MANIFEST")]
+    skip_uploaded: Option<String>,
+    /// CSV field delimiter (a single byte, e.g. ';' or '\t')
+    #[arg(long, value_name = "CHAR", value_parser = parse_delimiter, default_value = ",")]
+    csv_delimiter: u8,
+    /// Omit the CSV header row
+    #[arg(long)]
+    no_header: bool,
+    /// Read width/height from image headers
+    #[arg(long)]
+    extract_dimensions: bool,
+    /// Read camera make, model, capture date, and GPS position from JPEG and TIFF
+    /// EXIF headers
+    #[arg(long)]
+    exif: bool,
+    /// Also hash each file inside zip, tar, and .tar.gz archives, listed as
+    /// `archive.zip!entry.jpg`; archives themselves are still emitted
+    #[arg(long, conflicts_with_all = ["no_hash", "verify", "watch"])]
+    expand_archives: bool,
+    /// Read video duration/codec via ffprobe (must be on PATH)
+    #[arg(long)]
+    extract_media_info: bool,
+    /// Detect MIME types from file contents, falling back to the extension
+    #[arg(long)]
+    sniff_content: bool,
+    /// Path layout: `full` keeps the full path in `filename`, `split` adds a `path` field
+    #[arg(long, value_enum, default_value = "full")]
+    path_style: output::PathStyle,
+    /// Emit paths relative to the scan root
+    #[arg(long)]
+    relative: bool,
+    /// Order results by this field [default: name]
+    #[arg(long, value_enum)]
+    sort: Option<output::SortField>,
+    /// Scan result order when `--sort` is not given: `name`, `discovery`
+    /// (depth-first walk order), or `none` (fastest, unspecified)
+    #[arg(long, value_enum, default_value = "name")]
+    order: scanner::ResultOrder,
+    /// Reverse the `--sort` order
+    #[arg(long)]
+    desc: bool,
+    /// Keep running after the scan: rescan files as they are created, changed, or
+    /// deleted under the folders, then re-emit the results and upload the changes
+    #[arg(
+        long,
+        conflicts_with_all = ["files_from", "verify", "since", "dry_run", "append"]
+    )]
+    watch: bool,
+    /// Quiet period after the last filesystem event before `--watch` rescans
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    watch_debounce: u64,
+    /// Serve `GET /scan?path=<folder>` on this address (e.g. 127.0.0.1:8080)
+    /// instead of scanning once; results are JSON scan reports. Only the given
+    /// folders and their subfolders can be scanned
+    #[cfg(feature = "server")]
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["files_from", "verify", "watch"])]
+    serve: Option<String>,
+    /// Emit at most N files, taken after sorting so the result is the top N.
+    /// With `--order none` and no `--sort`/`--desc` the scan instead stops after
+    /// N unique files, which are then whichever the worker threads finished first
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+    /// Stop the scan after hashing N files, duplicates included. Unlike `--limit`
+    /// this always halts early, so sorting and statistics only cover those N files
+    #[arg(long, value_name = "N", conflicts_with_all = ["verify", "since"])]
+    max_files: Option<usize>,
 }
 
-#[derive(clap::ValueEnum, Clone)]
-enum OutputFormat {
-    Console,
-    Json,
-    Csv,
+fn main() {
+    let mut args = parse_args();
+    init_logging(args.verbose);
+
+    // Expanded once so every writer sees the same timestamp
+    args.output_file = args.output_file.as_deref().map(|template| {
+        let path = output::expand_output_path(template, args.folders.first().map(String::as_str));
+        args.compress_output.apply_extension(&path)
+    });
+
+    if let Err(message) = validate(&args) {
+        exit_invalid(&message);
+    }
+    let filter = build_filter(&args).unwrap_or_else(|message| exit_invalid(&message));
+
+    #[cfg(feature = "server")]
+    if let Some(addr) = &args.serve {
+        let scan_options = scan_options_from_args(&args, filter);
+        if let Err(e) = biebie::server::serve(addr, &args.folders, scan_options) {
+            exit_invalid(&format!("Failed to serve on {}: {}", addr, e));
+        }
+        return;
+    }
+
+    let inputs = load_inputs(&args).unwrap_or_else(|message| exit_invalid(&message));
+
+    match &inputs.listed_files {
+        Some(paths) => status!("📁 Scanning {} listed files", paths.len()),
+        None => status!("📁 Scanning: {}", args.folders.join(", ")),
+    }
+
+    let mut scan_options = scan_options_from_args(&args, filter);
+
+    if let Some(previous) = &inputs.verify_baseline {
+        run_verify(previous, &args, &scan_options);
+        return;
+    }
+
+    if limit_stops_scan(&args) {
+        scan_options.process.limit = args.limit;
+    }
+
+    let scanner::ScanReport {
+        files,
+        stats,
+        errors: scan_errors,
+        skipped_dirs,
+    } = run_scan(&args, &scan_options, &inputs);
+    let watch_state = args
+        .watch
+        .then(|| watch::WatchState::new(files.clone(), &scan_options));
+
+    let mut files = select_files(files, &args, inputs.previous_scan.as_deref());
+    let output_options = output_options_from_args(&args, &stats);
+    write_results(&files, &stats, &args, &scan_options, &output_options);
+    upload_results(&mut files, &stats, &args, &inputs, &output_options);
+    report_problems(&scan_errors, &skipped_dirs, &args);
+
+    if let Some(mut state) = watch_state {
+        run_watch(&mut state, &args, &scan_options, &output_options);
+    }
 }
 
-fn main() {
-    let args = Args::parse();
+/// Prints an argument or input error and exits non-zero
+fn exit_invalid(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
 
-    println!("📁 Scanning: {}", args.folder);
+/// Combinations of flags that clap cannot express as conflicts, checked before
+/// anything is read from disk
+fn validate(args: &Args) -> Result<(), String> {
+    let output = args.output.label().to_lowercase();
 
-    let files = scanner::scan_folder(&args.folder);
+    if args.folders.is_empty() && args.files_from.is_none() {
+        return Err(if serve_requested(args) {
+            "No folders to serve: pass the paths clients may scan".to_string()
+        } else {
+            "No folders to scan: pass a path or set `folders` in the config file".to_string()
+        });
+    }
 
-    // Output files based on format
-    match args.output {
-        OutputFormat::Console => {
-            for file in &files {
-                println!(
-                    "📄 {} ({} bytes, {}, hash: {}, type: {})",
-                    file.filename, file.size, file.mime, file.hash, file.filetype
-                );
-            }
+    if args.output.requires_output_file() && args.output_file.is_none() {
+        return Err(format!("--output {} requires --output-file", output));
+    }
+
+    let unsupported = [
+        (
+            "--find-duplicates",
+            args.find_duplicates && args.output.requires_output_file(),
+        ),
+        (
+            "--stats-only",
+            args.stats_only
+                && (args.output.requires_output_file() || args.output == OutputFormat::Csv),
+        ),
+        (
+            "--group-by",
+            args.group_by.is_some() && args.output.requires_output_file(),
+        ),
+        (
+            "--find-similar",
+            args.find_similar.is_some() && args.output.requires_output_file(),
+        ),
+        (
+            "--output-fields",
+            !args.output_fields.is_empty() && !args.output.supports_fields(),
+        ),
+        (
+            "--compress-output",
+            args.compress_output != output::OutputCompression::None
+                && (args.output.requires_output_file() || args.output.is_console()),
+        ),
+    ];
+    if let Some((flag, _)) = unsupported.iter().find(|(_, unsupported)| *unsupported) {
+        return Err(format!("{} does not support --output {}", flag, output));
+    }
+
+    if args.append && !args.output.supports_append() {
+        return Err(format!(
+            "--append only supports --output ndjson or csv, not {}",
+            output
+        ));
+    }
+
+    // Checked before scanning so a forgotten --output-file fails fast
+    if let Some(output_file) = &args.output_file {
+        if args.output.replaces_output_file()
+            && !args.force
+            && !args.append
+            && std::path::Path::new(output_file).exists()
+        {
+            return Err(format!(
+                "Output file {} already exists; pass --force to overwrite it",
+                output_file
+            ));
         }
-        OutputFormat::Json => {
-            let json_output =
-                serde_json::to_string_pretty(&files).expect("Failed to serialize to JSON");
-            if let Some(output_file) = &args.output_file {
-                std::fs::write(output_file, &json_output).expect("Failed to write JSON file");
-                println!("💾 JSON output saved to: {}", output_file);
-            } else {
-                println!("{}", json_output);
+    }
+
+    if let Some(max_size) = args.max_size {
+        if args.min_size > max_size {
+            return Err(format!(
+                "--min-size ({} bytes) must not exceed --max-size ({} bytes)",
+                args.min_size, max_size
+            ));
+        }
+    }
+
+    if args.sample_threshold < args.large_threshold {
+        return Err(format!(
+            "--sample-threshold ({} bytes) must not be below --large-threshold ({} bytes)",
+            args.sample_threshold, args.large_threshold
+        ));
+    }
+
+    if args.framing != uploader::Framing::Newline
+        && args.upload_format != uploader::UploadFormat::Ndjson
+    {
+        return Err("--framing only applies to --upload-format ndjson".to_string());
+    }
+
+    if args.sample_size == 0 {
+        return Err("--sample-size must be greater than zero".to_string());
+    }
+
+    Ok(())
+}
+
+/// Compiles the include, exclude, and size flags into the discovery filter
+fn build_filter(args: &Args) -> Result<scanner::FileFilter, String> {
+    let excludes = scanner::build_exclude_set(&args.exclude)
+        .map_err(|e| format!("Invalid --exclude pattern: {}", e))?;
+    let filter = scanner::FileFilter::new(&args.include, excludes, args.min_size, args.max_size);
+    match &args.exclude_from {
+        Some(exclude_from) => scanner::build_ignore_file(std::path::Path::new(exclude_from))
+            .map(|ignore| filter.with_ignore(ignore))
+            .map_err(|e| format!("Invalid --exclude-from file: {}", e)),
+        None => Ok(filter),
+    }
+}
+
+/// Files read before scanning; loaded up front so a bad path fails fast
+struct Inputs {
+    checkpoint: Option<checkpoint::Checkpoint>,
+    /// Results of the `--since` scan
+    previous_scan: Option<Vec<uploader::FileMeta>>,
+    /// Results of the `--verify` scan
+    verify_baseline: Option<Vec<uploader::FileMeta>>,
+    uploaded_hashes: Option<std::collections::HashSet<String>>,
+    /// Paths from `--files-from`, scanned instead of walking the folders
+    listed_files: Option<Vec<std::path::PathBuf>>,
+}
+
+/// Loads the checkpoint, baselines, upload manifest, and file list that the flags name
+fn load_inputs(args: &Args) -> Result<Inputs, String> {
+    let checkpoint = match &args.checkpoint {
+        Some(path) => {
+            let checkpoint = checkpoint::Checkpoint::load(std::path::Path::new(path))
+                .map_err(|e| format!("Failed to load checkpoint {}: {}", path, e))?;
+            status!(
+                "♻️  Loaded {} entries from checkpoint: {}",
+                checkpoint.len(),
+                path
+            );
+            Some(checkpoint)
+        }
+        None => None,
+    };
+    let load_scan = |path: &Option<String>, flag: &str| {
+        path.as_ref()
+            .map(|path| {
+                diff::load_previous(std::path::Path::new(path))
+                    .map_err(|e| format!("Failed to load {} {}: {}", flag, path, e))
+            })
+            .transpose()
+    };
+    let uploaded_hashes = args
+        .skip_uploaded
+        .as_ref()
+        .map(|path| {
+            manifest::load_uploaded_hashes(std::path::Path::new(path))
+                .map_err(|e| format!("Failed to load --skip-uploaded manifest {}: {}", path, e))
+        })
+        .transpose()?;
+    let listed_files = args
+        .files_from
+        .as_ref()
+        .map(|source| {
+            read_file_list(source)
+                .map_err(|e| format!("Failed to read --files-from {}: {}", source, e))
+        })
+        .transpose()?;
+
+    Ok(Inputs {
+        checkpoint,
+        previous_scan: load_scan(&args.since, "--since file")?,
+        verify_baseline: load_scan(&args.verify, "--verify manifest")?,
+        uploaded_hashes,
+        listed_files,
+    })
+}
+
+/// Whether `--limit` may stop hashing once enough files were collected; stopping
+/// early picks an arbitrary subset, so it needs `--order none` and no ordering
+/// or baseline that depends on every file
+fn limit_stops_scan(args: &Args) -> bool {
+    args.order == scanner::ResultOrder::Unordered
+        && args.sort.is_none()
+        && !args.desc
+        && args.since.is_none()
+        && !args.watch
+}
+
+/// Scans the listed files or the folders, then adds the manifest hash and
+/// writes the `--report`
+fn run_scan(
+    args: &Args,
+    scan_options: &scanner::ScanOptions,
+    inputs: &Inputs,
+) -> scanner::ScanReport {
+    let checkpoint = inputs.checkpoint.as_ref();
+    let mut report = match &inputs.listed_files {
+        Some(paths) => scanner::scan_files(paths, scan_options, checkpoint),
+        None => scanner::scan_folder(&args.folders, scan_options, checkpoint),
+    };
+    // Computed over every scanned file, before --since and --limit trim the list
+    if args.manifest_hash {
+        report.stats.manifest_hash = Some(hash::manifest_hash(&report.files));
+    }
+    if let Some(report_file) = &args.report {
+        let json = output::generate_json(&report, args.pretty);
+        output::emit(&json, Some(report_file), "Report");
+    }
+    report
+}
+
+/// Narrows the scanned files to what is emitted: relative paths, only the
+/// changes since `--since`, then sorting and `--limit`
+fn select_files(
+    mut files: Vec<uploader::FileMeta>,
+    args: &Args,
+    previous_scan: Option<&[uploader::FileMeta]>,
+) -> Vec<uploader::FileMeta> {
+    if args.relative {
+        output::relativize_paths(&mut files, &args.folders);
+    }
+
+    if let Some(previous) = previous_scan {
+        let scan_diff = diff::diff_scans(previous, &files);
+        status!(
+            "🔄 Since previous scan: {} added, {} removed, {} modified",
+            scan_diff.added.len(),
+            scan_diff.removed.len(),
+            scan_diff.modified.len()
+        );
+        files = scan_diff.changed_files();
+    }
+
+    arrange_files(&mut files, args);
+    files
+}
+
+/// Output settings for the selected format and destination
+fn output_options_from_args(args: &Args, stats: &scanner::ScanStats) -> output::OutputOptions {
+    // JSON is indented for people reading a terminal and compact for pipes and files
+    let json_to_terminal =
+        args.output_file.is_none() && std::io::IsTerminal::is_terminal(&std::io::stdout());
+    output::OutputOptions {
+        format: args.output,
+        output_file: args.output_file.clone(),
+        csv_delimiter: args.csv_delimiter,
+        csv_header: !args.no_header
+            && !output::appending_to_existing(args.output_file.as_deref(), args.append),
+        append: args.append,
+        pretty: args.pretty || (!args.compact && json_to_terminal),
+        fields: args.output_fields.clone(),
+        compression: args.compress_output,
+        console: output::ConsoleStyle::new(args.color, args.no_emoji),
+        manifest_hash: stats.manifest_hash.clone(),
+        scan_started: stats.started_at,
+    }
+}
+
+/// Emits the files, or the report that replaces them, followed by the summary
+fn write_results(
+    files: &[uploader::FileMeta],
+    stats: &scanner::ScanStats,
+    args: &Args,
+    scan_options: &scanner::ScanOptions,
+    output_options: &output::OutputOptions,
+) {
+    if args.stats_only {
+        output::write_stats(stats, output_options);
+    } else if let Some(group_by) = args.group_by {
+        output_group_report(files, group_by, output_options);
+    } else if args.find_duplicates {
+        output_duplicate_report(files, &scan_options.hash, output_options);
+    } else if let Some(min_ratio) = args.find_similar {
+        output_similarity_report(files, min_ratio, output_options);
+    } else {
+        output::write_files(files, output_options);
+    }
+
+    // Part of the payload on the console; a status line alongside file output
+    // and the reports that replace it
+    if !args.dry_run {
+        let summary = output::generate_summary(stats);
+        let report_output =
+            args.find_duplicates || args.find_similar.is_some() || args.group_by.is_some();
+        if args.output.is_console() && !report_output {
+            print!("{}", status::decorate(&summary));
+        } else {
+            status!("{}", summary.trim_end());
+        }
+    }
+}
+
+/// Uploads the files to the API or S3 target, if any; skips dry runs
+#[cfg_attr(not(feature = "s3"), allow(unused_variables))]
+fn upload_results(
+    files: &mut Vec<uploader::FileMeta>,
+    stats: &scanner::ScanStats,
+    args: &Args,
+    inputs: &Inputs,
+    output_options: &output::OutputOptions,
+) {
+    if args.dry_run {
+        if args.api.is_some() || s3_requested(args) {
+            status!("💡 Dry run: skipping upload");
+        }
+    } else if let Some(api_url) = &args.api {
+        status!("📤 Uploading to API: {}", api_url);
+        // Keyed on hash, so moved or copied files count as uploaded too
+        if let Some(uploaded_hashes) = &inputs.uploaded_hashes {
+            let before = files.len();
+            files.retain(|file| !uploaded_hashes.contains(&file.hash));
+            status!(
+                "⏭️  Skipping {} files already in the upload manifest",
+                before - files.len()
+            );
+        }
+        if let Err(e) = upload_files(api_url, files, stats.started_at, args) {
+            exit_with_error(args.output, "upload", &format!("Failed to upload: {}", e));
+        }
+    } else if s3_requested(args) {
+        #[cfg(feature = "s3")]
+        upload_to_s3(files, args, output_options);
+    } else {
+        status!("💡 Use --api <URL> to upload metadata to an API endpoint");
+    }
+}
+
+/// Lists unreadable paths, failing with `--strict`, and the `--report-skipped` directories
+fn report_problems(
+    scan_errors: &[scanner::ScanError],
+    skipped_dirs: &[scanner::SkippedDir],
+    args: &Args,
+) {
+    if !scan_errors.is_empty() {
+        warning!("⚠️  {} paths could not be scanned:", scan_errors.len());
+        for (path, error) in scan_errors {
+            eprintln!("   {}: {}", path.display(), error);
+        }
+        if args.strict {
+            exit_with_error(
+                args.output,
+                "scan",
+                &format!("{} paths could not be scanned", scan_errors.len()),
+            );
+        }
+    }
+
+    if args.report_skipped {
+        scanner::print_skipped_dirs(skipped_dirs);
+    }
+}
+
+#[cfg(feature = "server")]
+fn serve_requested(args: &Args) -> bool {
+    args.serve.is_some()
+}
+
+#[cfg(not(feature = "server"))]
+fn serve_requested(_args: &Args) -> bool {
+    false
+}
+
+#[cfg(feature = "s3")]
+fn s3_requested(args: &Args) -> bool {
+    args.s3_bucket.is_some()
+}
+
+#[cfg(not(feature = "s3"))]
+fn s3_requested(_args: &Args) -> bool {
+    false
+}
+
+/// Stores the results, rendered as for `--output`, as one S3 object
+#[cfg(feature = "s3")]
+fn upload_to_s3(files: &[uploader::FileMeta], args: &Args, output_options: &output::OutputOptions) {
+    let (Some(bucket), Some(key)) = (&args.s3_bucket, &args.s3_key) else {
+        return;
+    };
+    let target = biebie::s3::S3Target {
+        bucket: bucket.clone(),
+        key: output::expand_output_path(key, args.folders.first().map(String::as_str)),
+        endpoint: args.s3_endpoint.clone(),
+        region: args.s3_region.clone(),
+        access_key_id: args.s3_access_key_id.clone(),
+        secret_access_key: args.s3_secret_access_key.clone(),
+    };
+    // SQLite and Parquet only exist as files, so upload what was written
+    let body = match output::render(files, output_options) {
+        Some(content) => content.into_bytes(),
+        None => {
+            let output_file = output_options.output_file.as_deref().unwrap_or_default();
+            std::fs::read(output_file).unwrap_or_else(|e| {
+                exit_with_error(
+                    args.output,
+                    "upload",
+                    &format!("Failed to read {}: {}", output_file, e),
+                )
+            })
+        }
+    };
+
+    status!("📤 Uploading to s3://{}/{}", target.bucket, target.key);
+    match biebie::s3::put_object(&target, body, args.output.content_type()) {
+        Ok(()) => status!("✅ Uploaded s3://{}/{}", target.bucket, target.key),
+        Err(e) => exit_with_error(
+            args.output,
+            "upload",
+            &format!("Failed to upload to S3: {}", e),
+        ),
+    }
+}
+
+/// Sorts, truncates, and lays out paths the way results are emitted
+fn arrange_files(files: &mut Vec<uploader::FileMeta>, args: &Args) {
+    // Without --sort/--desc, keep the order the scan produced unless it is by name
+    if args.sort.is_some() || args.desc || args.order == scanner::ResultOrder::Name {
+        output::sort_files(files, args.sort.unwrap_or_default(), args.desc);
+    }
+    if let Some(limit) = args.limit {
+        files.truncate(limit);
+    }
+    output::apply_path_style(files, args.path_style);
+}
+
+/// Uploads files with the `--upload-*` settings, concurrently when requested
+fn upload_files(
+    api_url: &str,
+    files: &[uploader::FileMeta],
+    scan_started: chrono::DateTime<chrono::Utc>,
+    args: &Args,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let upload_options = uploader::UploadOptions {
+        retries: args.upload_retries,
+        retry_delay: std::time::Duration::from_millis(args.upload_retry_delay),
+        batch_size: args.batch_size,
+        auth_token: args.auth_token.clone(),
+        headers: args.headers.clone(),
+        compress: args.compress,
+        timeout: seconds_or_none(args.upload_timeout),
+        connect_timeout: seconds_or_none(args.connect_timeout),
+        concurrent_uploads: args.concurrent_uploads,
+        strict: args.strict,
+        manifest: args.upload_manifest.clone().map(std::path::PathBuf::from),
+        format: args.upload_format,
+        framing: args.framing,
+        content_hash: args.content_hash,
+        scan_timestamp: Some(scan_started),
+    };
+    if args.concurrent_uploads > 1 {
+        tokio::runtime::Runtime::new()?.block_on(uploader::upload_metadata_async(
+            api_url,
+            files,
+            &upload_options,
+        ))
+    } else {
+        uploader::upload_metadata(api_url, files, &upload_options)
+    }
+}
+
+/// Re-emits the results, and uploads what changed, each time the watched folders settle
+fn run_watch(
+    state: &mut watch::WatchState,
+    args: &Args,
+    scan_options: &scanner::ScanOptions,
+    output_options: &output::OutputOptions,
+) {
+    // Our own writes inside a watched root would otherwise retrigger a rescan
+    let written = [
+        &args.output_file,
+        &args.report,
+        &args.upload_manifest,
+        &args.checkpoint,
+    ];
+    for path in written.into_iter().flatten() {
+        state.ignore(std::path::Path::new(path));
+    }
+
+    status!("👀 Watching {} for changes", args.folders.join(", "));
+    let debounce = std::time::Duration::from_millis(args.watch_debounce);
+    let result = watch::watch(&args.folders, state, debounce, |state, changes| {
+        status!(
+            "🔄 {} files updated, {} removed",
+            changes.updated.len(),
+            changes.removed.len()
+        );
+        let mut files = state.files();
+        let mut output_options = output_options.clone();
+        if args.manifest_hash {
+            output_options.manifest_hash = Some(hash::manifest_hash(&files));
+        }
+        let output_options = &output_options;
+        if args.relative {
+            output::relativize_paths(&mut files, &args.folders);
+        }
+        arrange_files(&mut files, args);
+        if let Some(group_by) = args.group_by {
+            output_group_report(&files, group_by, output_options);
+        } else if args.find_duplicates {
+            output_duplicate_report(&files, &scan_options.hash, output_options);
+        } else if let Some(min_ratio) = args.find_similar {
+            output_similarity_report(&files, min_ratio, output_options);
+        } else {
+            output::write_files(&files, output_options);
+        }
+
+        if let Some(api_url) = &args.api {
+            let mut updated = changes.updated.clone();
+            if args.relative {
+                output::relativize_paths(&mut updated, &args.folders);
+            }
+            output::apply_path_style(&mut updated, args.path_style);
+            // A failed upload is logged rather than ending the watch
+            if let Err(e) = upload_files(api_url, &updated, changes.started_at, args) {
+                log::error!("Failed to upload changes: {}", e);
             }
         }
-        OutputFormat::Csv => {
-            let csv_output = generate_csv(&files);
-            if let Some(output_file) = &args.output_file {
-                std::fs::write(output_file, &csv_output).expect("Failed to write CSV file");
-                println!("💾 CSV output saved to: {}", output_file);
-            } else {
-                println!("{}", csv_output);
+    });
+    if let Err(e) = result {
+        exit_with_error(
+            args.output,
+            "watch",
+            &format!("Failed to watch folders: {}", e),
+        );
+    }
+}
+
+/// Prints a fatal error and exits non-zero; with JSON output the error is also
+/// written to stdout as `{"error": ..., "context": ...}` so automation can parse it
+fn exit_with_error(format: OutputFormat, context: &str, message: &str) -> ! {
+    eprintln!("{}", message);
+    if matches!(format, OutputFormat::Json | OutputFormat::Ndjson) {
+        println!(
+            "{}",
+            serde_json::json!({ "error": message, "context": context })
+        );
+    }
+    std::process::exit(1);
+}
+
+/// Why the command line could not be turned into `Args`
+enum ArgsError {
+    Cli(clap::Error),
+    Config(std::path::PathBuf, String),
+}
+
+/// Parses the command line, filling options it leaves unset from the config file
+fn parse_args() -> Args {
+    match parse_args_from(std::env::args_os().collect()) {
+        Ok((args, config_path)) => {
+            status::set_quiet(args.quiet);
+            status::set_emoji(!args.no_emoji && !status::no_color_env());
+            if let Some(config_path) = config_path {
+                status!("⚙️  Loaded config: {}", config_path.display());
             }
+            args
         }
+        Err(ArgsError::Cli(e)) => e.exit(),
+        Err(ArgsError::Config(path, e)) => {
+            exit_invalid(&format!("Failed to load config {}: {}", path.display(), e))
+        }
+    }
+}
+
+/// Parses `argv` (program name first) and applies the `--config` file, or the
+/// one found in the scan root; also returns the config file that was applied
+fn parse_args_from(
+    argv: Vec<std::ffi::OsString>,
+) -> Result<(Args, Option<std::path::PathBuf>), ArgsError> {
+    let command = Args::command();
+    let matches = command
+        .clone()
+        .try_get_matches_from(&argv)
+        .map_err(ArgsError::Cli)?;
+    let args = Args::from_arg_matches(&matches).map_err(ArgsError::Cli)?;
+
+    let config_path = args
+        .config
+        .clone()
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            let root = args.folders.first().map(String::as_str).unwrap_or(".");
+            config::Config::discover(std::path::Path::new(root))
+        });
+    let Some(config_path) = config_path else {
+        return Ok((args, None));
+    };
+
+    let config_args = config::Config::load(&config_path)
+        .map_err(|e| e.to_string())
+        .and_then(|config| config.to_args(&command, &matches))
+        .map_err(|e| ArgsError::Config(config_path.clone(), e))?;
+
+    // Config values go first so anything typed on the command line is parsed after them
+    let mut argv = argv.into_iter();
+    let program = argv.next().unwrap_or_default();
+    let args = Args::try_parse_from(
+        std::iter::once(program)
+            .chain(config_args.into_iter().map(Into::into))
+            .chain(argv),
+    )
+    .map_err(ArgsError::Cli)?;
+    Ok((args, Some(config_path)))
+}
+
+/// Logs warnings and errors by default, more with each `-v`
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .init();
+}
+
+/// Builds the scan settings shared by every pipeline stage from the parsed flags
+fn scan_options_from_args(args: &Args, filter: scanner::FileFilter) -> scanner::ScanOptions {
+    scanner::ScanOptions {
+        filter,
+        hash: scanner::HashStrategy {
+            algorithm: args.hash_algo,
+            encoding: args.hash_encoding,
+            full_hash: args.full_hash,
+            large_threshold: args.large_threshold,
+            sample_threshold: args.sample_threshold,
+            sample_size: args.sample_size as usize,
+            sample_points: args.sample_points.map(|points| points as usize),
+            rehash_on_size_match: args.rehash_on_size_match,
+            chunk_hashes: args.chunk_hashes || args.find_similar.is_some(),
+            no_hash: args.no_hash,
+        },
+        walk: scanner::WalkOptions {
+            follow_symlinks: args.follow_symlinks,
+            max_depth: args.max_depth,
+            respect_gitignore: args.respect_gitignore,
+            min_files: args.min_files,
+            report_skipped: args.report_skipped,
+        },
+        process: scanner::ProcessOptions {
+            progress: args.progress,
+            dry_run: args.dry_run,
+            // Duplicate reports need every file, not just the first of each hash;
+            // keep-all also skips the seen-hash bookkeeping entirely
+            duplicates: if args.find_duplicates || args.no_dedup {
+                scanner::DuplicateMode::KeepAll
+            } else {
+                args.duplicates
+            },
+            extract: media::ExtractOptions {
+                dimensions: args.extract_dimensions,
+                media_info: args.extract_media_info,
+                sniff_content: args.sniff_content,
+                exif: args.exif,
+            },
+            limit: None,
+            max_files: args.max_files,
+            expand_archives: args.expand_archives,
+            threads: (args.threads > 0).then_some(args.threads),
+            workload: args.workload,
+            order: args.order,
+        },
+    }
+}
+
+/// Checks files from a previous scan against the disk and exits non-zero on mismatches
+fn run_verify(previous: &[uploader::FileMeta], args: &Args, scan_options: &scanner::ScanOptions) {
+    let (discovered, walk_errors) = scanner::discover_files(&args.folders, scan_options);
+    let root = std::path::Path::new(&args.folders[0]);
+    let report = verify::verify_files(previous, &discovered, root, scan_options.hash);
+    verify::print_report(&report);
+
+    for (path, error) in &walk_errors {
+        warning!("   ⚠️  {}: {}", path.display(), error);
+    }
+    if !report.mismatched.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Prints or saves the `--find-duplicates` report in the selected format
+fn output_duplicate_report(
+    files: &[uploader::FileMeta],
+    hash_strategy: &scanner::HashStrategy,
+    output_options: &output::OutputOptions,
+) {
+    let groups = duplicates::find_duplicate_groups(files);
+    let wasted: u64 = groups.iter().map(|group| group.wasted_bytes).sum();
+    status!(
+        "🔁 Found {} duplicate groups wasting {} bytes",
+        groups.len(),
+        wasted
+    );
+
+    let sampled = duplicates::count_sampled_groups(&groups, hash_strategy);
+    if sampled > 0 {
+        warning!(
+            "⚠️  {} duplicate groups were detected using sampled hashes and may be false positives; rerun with --rehash-on-size-match or --full-hash to confirm",
+            sampled
+        );
     }
 
-    // Upload to API if endpoint provided
-    if let Some(api_url) = args.api {
-        println!("📤 Uploading to API: {}", api_url);
-        if let Err(e) = uploader::upload_metadata(&api_url, &files) {
-            eprintln!("Failed to upload: {}", e);
-            std::process::exit(1);
+    let report = match output_options.format {
+        OutputFormat::Console | OutputFormat::Tree => {
+            duplicates::generate_console(&groups, output_options.console)
+        }
+        OutputFormat::Json => output::generate_json(&groups, output_options.pretty),
+        OutputFormat::Ndjson => output::generate_ndjson(&groups),
+        OutputFormat::Csv => duplicates::generate_csv(&groups, output_options),
+        OutputFormat::Toml => duplicates::generate_toml(&groups),
+        OutputFormat::Yaml => output::generate_yaml(&groups),
+        OutputFormat::Sqlite | OutputFormat::Parquet => {
+            unreachable!("rejected during argument validation")
+        }
+    };
+
+    output::emit_output(&report, output_options);
+}
+
+/// Prints or saves the `--group-by` totals in the selected format
+fn output_group_report(
+    files: &[uploader::FileMeta],
+    group_by: aggregate::GroupBy,
+    output_options: &output::OutputOptions,
+) {
+    let groups = aggregate::group_files(files, group_by);
+    let report = match output_options.format {
+        OutputFormat::Console | OutputFormat::Tree => {
+            aggregate::generate_console(&groups, output_options.console)
+        }
+        OutputFormat::Json => output::generate_json(&groups, output_options.pretty),
+        OutputFormat::Ndjson => output::generate_ndjson(&groups),
+        OutputFormat::Csv => aggregate::generate_csv(&groups, output_options),
+        OutputFormat::Toml => aggregate::generate_toml(&groups),
+        OutputFormat::Yaml => output::generate_yaml(&groups),
+        OutputFormat::Sqlite | OutputFormat::Parquet => {
+            unreachable!("rejected during argument validation")
+        }
+    };
+
+    output::emit_output(&report, output_options);
+}
+
+/// Prints or saves the `--find-similar` report in the selected format
+fn output_similarity_report(
+    files: &[uploader::FileMeta],
+    min_ratio: f64,
+    output_options: &output::OutputOptions,
+) {
+    let pairs = similarity::find_similar_pairs(files, min_ratio);
+    status!(
+        "🧩 Found {} similar file pairs sharing at least {:.0}% of their chunks",
+        pairs.len(),
+        min_ratio * 100.0
+    );
+
+    let report = match output_options.format {
+        OutputFormat::Console | OutputFormat::Tree => {
+            similarity::generate_console(&pairs, output_options.console)
+        }
+        OutputFormat::Json => output::generate_json(&pairs, output_options.pretty),
+        OutputFormat::Ndjson => output::generate_ndjson(&pairs),
+        OutputFormat::Csv => similarity::generate_csv(&pairs, output_options),
+        OutputFormat::Toml => similarity::generate_toml(&pairs),
+        OutputFormat::Yaml => output::generate_yaml(&pairs),
+        OutputFormat::Sqlite | OutputFormat::Parquet => {
+            unreachable!("rejected during argument validation")
         }
+    };
+
+    output::emit_output(&report, output_options);
+}
+
+/// Reads newline-separated paths from a file, or from stdin when `source` is `-`
+fn read_file_list(source: &str) -> std::io::Result<Vec<std::path::PathBuf>> {
+    use std::io::BufRead;
+
+    let reader: Box<dyn BufRead> = if source == "-" {
+        Box::new(std::io::stdin().lock())
     } else {
-        println!("💡 Use --api <URL> to upload metadata to an API endpoint");
+        Box::new(std::io::BufReader::new(std::fs::File::open(source)?))
+    };
+    let mut paths = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if !line.is_empty() {
+            paths.push(std::path::PathBuf::from(line));
+        }
+    }
+    Ok(paths)
+}
+
+/// Parses a CSV delimiter, accepting `\t` as an escape for tab
+fn parse_delimiter(input: &str) -> Result<u8, String> {
+    let delimiter = if input == "\\t" { "\t" } else { input };
+    match delimiter.as_bytes() {
+        [byte] => Ok(*byte),
+        _ => Err(format!("delimiter must be a single byte, got {:?}", input)),
     }
 }
 
-fn generate_csv(files: &[uploader::FileMeta]) -> String {
-    let mut csv = String::from("filename,folder,size,mime,hash,filetype\n");
-    for file in files {
-        csv.push_str(&format!(
-            "\"{}\",\"{}\",{},\"{}\",\"{}\",\"{}\"\n",
-            file.filename.replace('"', "\"\""),
-            file.folder.replace('"', "\"\""),
-            file.size,
-            file.mime,
-            file.hash,
-            file.filetype
+/// Parses a fraction between 0 and 1, e.g. `0.8`
+fn parse_ratio(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(ratio),
+        _ => Err(format!("ratio must be between 0 and 1, got {}", input)),
+    }
+}
+
+/// Converts a seconds option where 0 means "no limit"
+fn seconds_or_none(secs: u64) -> Option<std::time::Duration> {
+    (secs > 0).then(|| std::time::Duration::from_secs(secs))
+}
+
+/// Parses and validates a `NAME:VALUE` request header
+fn parse_header(input: &str) -> Result<(String, String), String> {
+    let (name, value) = input
+        .split_once(':')
+        .ok_or_else(|| format!("header must be in NAME:VALUE form: {}", input))?;
+    let name = name.trim();
+    let value = value.trim();
+
+    reqwest::header::HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| format!("invalid header name: {}", name))?;
+    reqwest::header::HeaderValue::from_str(value)
+        .map_err(|_| format!("invalid header value for {}", name))?;
+
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Parses a human-readable size such as `1024`, `10KB` or `5MB` into bytes
+fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split_at = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split_at);
+
+    let value: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid size: {}", input))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        "T" | "TB" => 1024 * 1024 * 1024 * 1024,
+        other => return Err(format!("unknown size unit: {}", other)),
+    };
+
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size too large: {}", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_accepts_bare_numbers_and_units() {
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("512B"), Ok(512));
+        assert_eq!(parse_size("10KB"), Ok(10 * 1024));
+        assert_eq!(parse_size("10k"), Ok(10 * 1024));
+        assert_eq!(parse_size("5MB"), Ok(5 * 1024 * 1024));
+        assert_eq!(parse_size("5 mb"), Ok(5 * 1024 * 1024));
+        assert_eq!(parse_size("2GB"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1T"), Ok(1024u64.pow(4)));
+    }
+
+    #[test]
+    fn parse_size_rejects_invalid_input() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("-5").is_err());
+        assert!(parse_size("1.5MB").is_err());
+        assert!(parse_size("10XB").is_err());
+        assert!(parse_size("99999999999TB").is_err());
+    }
+
+    /// Parses `argv` as typed after the program name, applying any config file
+    fn parse(argv: &[&str]) -> (Args, Option<std::path::PathBuf>) {
+        let argv = std::iter::once("biebie-cli")
+            .chain(argv.iter().copied())
+            .map(Into::into)
+            .collect();
+        match parse_args_from(argv) {
+            Ok(parsed) => parsed,
+            Err(ArgsError::Cli(e)) => panic!("{}", e),
+            Err(ArgsError::Config(path, e)) => panic!("{}: {}", path.display(), e),
+        }
+    }
+
+    #[test]
+    fn config_fills_unset_flags_and_command_line_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("scan.toml");
+        std::fs::write(
+            &config,
+            "min-size = \"10KB\"\nexclude = [\"cache/\", \"*.tmp\"]\nfull_hash = true\n",
+        )
+        .unwrap();
+        let config = config.display().to_string();
+        let root = dir.path().display().to_string();
+
+        let (args, applied) = parse(&["--config", &config, &root]);
+        assert_eq!(applied.as_deref(), Some(std::path::Path::new(&config)));
+        assert_eq!(args.min_size, 10 * 1024);
+        assert_eq!(args.exclude, ["cache/", "*.tmp"]);
+        assert!(args.full_hash);
+
+        let (args, _) = parse(&[
+            "--config",
+            &config,
+            "--min-size",
+            "5",
+            "--exclude",
+            "x",
+            &root,
+        ]);
+        assert_eq!(args.min_size, 5);
+        assert_eq!(args.exclude, ["x"]);
+        assert!(args.full_hash);
+    }
+
+    #[test]
+    fn config_is_discovered_in_the_scan_root() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(config::DEFAULT_CONFIG_FILE),
+            "max-depth = 2\n",
+        )
+        .unwrap();
+        let root = dir.path().display().to_string();
+
+        let (args, applied) = parse(&[&root]);
+        assert_eq!(applied, Some(dir.path().join(config::DEFAULT_CONFIG_FILE)));
+        assert_eq!(args.max_depth, Some(2));
+
+        let (args, _) = parse(&["--max-depth", "5", &root]);
+        assert_eq!(args.max_depth, Some(5));
+    }
+
+    #[test]
+    fn unknown_config_keys_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("bad.toml");
+        std::fs::write(&config, "no-such-flag = true\n").unwrap();
+        let argv = ["biebie-cli", "--config", config.to_str().unwrap(), "."]
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        assert!(matches!(
+            parse_args_from(argv),
+            Err(ArgsError::Config(_, e)) if e.contains("no-such-flag")
         ));
     }
-    csv
+
+    #[test]
+    fn min_size_above_max_size_is_rejected() {
+        let (args, _) = parse(&["--min-size", "10KB", "--max-size", "5KB", "."]);
+        let error = validate(&args).unwrap_err();
+        assert!(error.contains("must not exceed --max-size"), "{}", error);
+
+        let (args, _) = parse(&["--min-size", "5KB", "--max-size", "5KB", "."]);
+        assert!(validate(&args).is_ok());
+    }
+
+    #[test]
+    fn sample_threshold_below_large_threshold_is_rejected() {
+        let (args, _) = parse(&["--large-threshold", "2MB", "--sample-threshold", "1MB", "."]);
+        let error = validate(&args).unwrap_err();
+        assert!(error.contains("--sample-threshold"), "{}", error);
+
+        let (args, _) = parse(&["--large-threshold", "1MB", "--sample-threshold", "1MB", "."]);
+        assert!(validate(&args).is_ok());
+    }
+
+    #[test]
+    fn parse_header_requires_a_colon_and_valid_parts() {
+        assert_eq!(
+            parse_header("X-Tenant: acme"),
+            Ok(("X-Tenant".to_string(), "acme".to_string()))
+        );
+        let error = parse_header("X-Tenant acme").unwrap_err();
+        assert!(error.contains("NAME:VALUE"), "{}", error);
+        assert!(parse_header("Bad Name:x").is_err());
+    }
+
+    #[test]
+    fn zero_timeout_means_no_limit() {
+        assert_eq!(seconds_or_none(0), None);
+        assert_eq!(
+            seconds_or_none(45),
+            Some(std::time::Duration::from_secs(45))
+        );
+    }
+
+    #[test]
+    fn delimiter_must_be_a_single_byte() {
+        assert_eq!(parse_delimiter(";"), Ok(b';'));
+        assert_eq!(parse_delimiter("\\t"), Ok(b'\t'));
+        assert!(parse_delimiter(",,").is_err());
+        assert!(parse_delimiter("é").is_err());
+        assert!(parse_delimiter("").is_err());
+    }
+
+    #[test]
+    fn limit_keeps_the_first_n_files_by_name() {
+        let (args, _) = parse(&["--limit", "3", "."]);
+        assert!(!limit_stops_scan(&args));
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..10u8 {
+            std::fs::write(dir.path().join(format!("{}.jpg", i)), [i]).unwrap();
+        }
+        let root = dir.path().display().to_string();
+        let mut files = scanner::scan_folder(&[root], &scanner::ScanOptions::default(), None).files;
+        files.reverse();
+        arrange_files(&mut files, &args);
+        let names: Vec<String> = files
+            .iter()
+            .map(|file| {
+                file.os_path()
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        assert_eq!(names, ["0.jpg", "1.jpg", "2.jpg"]);
+    }
+
+    #[test]
+    fn limit_stops_the_scan_only_for_unordered_results() {
+        let (args, _) = parse(&["--limit", "3", "--order", "none", "."]);
+        assert!(limit_stops_scan(&args));
+        let (args, _) = parse(&["--limit", "3", "--order", "discovery", "."]);
+        assert!(!limit_stops_scan(&args));
+        let (args, _) = parse(&["--limit", "3", "--order", "none", "--sort", "size", "."]);
+        assert!(!limit_stops_scan(&args));
+    }
+
+    #[test]
+    fn threads_overrides_the_heuristic_unless_zero() {
+        let (args, _) = parse(&["--threads", "3", "."]);
+        let filter = build_filter(&args).unwrap();
+        assert_eq!(
+            scan_options_from_args(&args, filter).process.threads,
+            Some(3)
+        );
+
+        let (args, _) = parse(&["--threads", "0", "."]);
+        let filter = build_filter(&args).unwrap();
+        assert_eq!(scan_options_from_args(&args, filter).process.threads, None);
+    }
+
+    #[test]
+    fn no_hash_conflicts_with_hash_keyed_options() {
+        for flag in ["--skip-uploaded", "--checkpoint", "--verify", "--since"] {
+            let argv = ["biebie-cli", "--no-hash", flag, "state.json", "."]
+                .into_iter()
+                .map(Into::into)
+                .collect();
+            assert!(
+                matches!(parse_args_from(argv), Err(ArgsError::Cli(_))),
+                "--no-hash accepted with {}",
+                flag
+            );
+        }
+        // Unhashed SQLite rows store a NULL hash and replace the row for the same path
+        let (args, _) = parse(&[
+            "--no-hash",
+            "--output",
+            "sqlite",
+            "--output-file",
+            "x.db",
+            ".",
+        ]);
+        assert!(args.no_hash);
+    }
 }
@@ -0,0 +1,44 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Upload outcome of one file, written by `--upload-manifest`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ManifestEntry {
+    pub hash: String,
+    pub filename: String,
+    pub batch_index: usize,
+    /// Final HTTP status of the file's batch; `None` when no response was received
+    pub status: Option<u16>,
+}
+
+impl ManifestEntry {
+    /// Whether the file's batch was accepted with a 2xx status
+    pub fn uploaded(&self) -> bool {
+        self.status
+            .is_some_and(|status| (200..300).contains(&status))
+    }
+}
+
+/// Hashes of every successfully uploaded file in a previous manifest
+pub fn load_uploaded_hashes(path: &Path) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    let entries: Vec<ManifestEntry> = serde_json::from_str(&content)?;
+    Ok(entries
+        .into_iter()
+        .filter(ManifestEntry::uploaded)
+        .map(|entry| entry.hash)
+        .collect())
+}
+
+/// Writes the manifest as a JSON array, replacing any previous file
+pub fn write_manifest(
+    path: &Path,
+    entries: &[ManifestEntry],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = serde_json::to_string_pretty(entries)?;
+    fs::write(path, content)?;
+    Ok(())
+}
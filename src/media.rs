@@ -0,0 +1,418 @@
+use std::path::Path;
+use std::process::Command;
+
+use serde::Deserialize;
+
+/// Optional per-file metadata that costs extra I/O to extract
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExtractOptions {
+    /// Read width and height from image headers
+    pub dimensions: bool,
+    /// Probe video duration and codec with `ffprobe`
+    pub media_info: bool,
+    /// Detect MIME types from magic bytes instead of the extension alone
+    pub sniff_content: bool,
+    /// Read camera and capture date from the EXIF header of JPEG and TIFF images
+    pub exif: bool,
+}
+
+/// Duration and codec of a video file as reported by `ffprobe`
+#[derive(Debug, Default)]
+pub struct MediaInfo {
+    pub duration_secs: Option<f64>,
+    pub codec: Option<String>,
+}
+
+/// Camera and capture date from a photo's EXIF header
+#[derive(Debug, Default)]
+pub struct ExifInfo {
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    /// `DateTimeOriginal` (or the digitized or file time when that is missing)
+    /// as ISO 8601, with the UTC offset when the file records one
+    pub taken_at: Option<String>,
+    /// Decimal degrees, negative south of the equator
+    pub latitude: Option<f64>,
+    /// Decimal degrees, negative west of Greenwich
+    pub longitude: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    codec_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+}
+
+/// Reads image dimensions from the file header without decoding pixel data
+pub fn image_dimensions(path: &Path) -> Option<(u32, u32)> {
+    image::ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+/// Whether the MIME type is one [`read_exif`] is run for
+pub fn has_exif(mime: &str) -> bool {
+    matches!(mime, "image/jpeg" | "image/tiff")
+}
+
+/// Reads the EXIF header; `None` when the file has none or it cannot be parsed
+pub fn read_exif(path: &Path) -> Option<ExifInfo> {
+    let file = std::fs::File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut std::io::BufReader::new(file))
+        .ok()?;
+    let ascii = |tag: exif::Tag| -> Option<String> {
+        let field = exif.get_field(tag, exif::In::PRIMARY)?;
+        match &field.value {
+            exif::Value::Ascii(values) => values
+                .first()
+                .map(|bytes| {
+                    String::from_utf8_lossy(bytes)
+                        .trim_end_matches(['\0', ' '])
+                        .to_string()
+                })
+                .filter(|value| !value.is_empty()),
+            _ => None,
+        }
+    };
+
+    // Editors may rewrite `DateTime`, so it is only used when the capture times are missing
+    let taken_at = [
+        (exif::Tag::DateTimeOriginal, exif::Tag::OffsetTimeOriginal),
+        (exif::Tag::DateTimeDigitized, exif::Tag::OffsetTimeDigitized),
+        (exif::Tag::DateTime, exif::Tag::OffsetTime),
+    ]
+    .into_iter()
+    .find_map(|(time_tag, offset_tag)| {
+        let mut taken = exif::DateTime::from_ascii(ascii(time_tag)?.as_bytes()).ok()?;
+        if let Some(offset) = ascii(offset_tag) {
+            // A malformed offset leaves the local time without one
+            let _ = taken.parse_offset(offset.as_bytes());
+        }
+        format_exif_datetime(&taken)
+    });
+    let coordinate = |tag: exif::Tag, ref_tag: exif::Tag, negative: &str| -> Option<f64> {
+        let field = exif.get_field(tag, exif::In::PRIMARY)?;
+        let exif::Value::Rational(parts) = &field.value else {
+            return None;
+        };
+        let degrees = dms_to_decimal(parts)?;
+        let hemisphere = ascii(ref_tag)?;
+        Some(if hemisphere.eq_ignore_ascii_case(negative) {
+            -degrees
+        } else {
+            degrees
+        })
+    };
+    Some(ExifInfo {
+        camera_make: ascii(exif::Tag::Make),
+        camera_model: ascii(exif::Tag::Model),
+        taken_at,
+        latitude: coordinate(exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef, "S"),
+        longitude: coordinate(exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef, "W"),
+    })
+}
+
+/// Converts GPS degrees, minutes, and seconds rationals to decimal degrees;
+/// some writers leave out the seconds or store fractional minutes instead
+fn dms_to_decimal(parts: &[exif::Rational]) -> Option<f64> {
+    let value = |index: usize| -> Option<f64> {
+        match parts.get(index) {
+            Some(part) if part.denom != 0 => Some(part.to_f64()),
+            Some(_) => None,
+            None => Some(0.0),
+        }
+    };
+    if parts.is_empty() {
+        return None;
+    }
+    let decimal = value(0)? + value(1)? / 60.0 + value(2)? / 3600.0;
+    decimal.is_finite().then_some(decimal)
+}
+
+/// EXIF stores local time as `YYYY:MM:DD HH:MM:SS` with an optional separate offset
+fn format_exif_datetime(taken: &exif::DateTime) -> Option<String> {
+    let local =
+        chrono::NaiveDate::from_ymd_opt(taken.year.into(), taken.month.into(), taken.day.into())?
+            .and_hms_opt(taken.hour.into(), taken.minute.into(), taken.second.into())?;
+    Some(match taken.offset {
+        Some(minutes) => {
+            let offset = chrono::FixedOffset::east_opt(i32::from(minutes) * 60)?;
+            local.and_local_timezone(offset).single()?.to_rfc3339()
+        }
+        None => local.format("%Y-%m-%dT%H:%M:%S").to_string(),
+    })
+}
+
+/// Detects the MIME type from file contents, reusing already-read bytes when given
+pub fn sniff_mime(path: &Path, head: Option<&[u8]>) -> Option<&'static str> {
+    let kind = match head {
+        Some(bytes) => infer::get(bytes),
+        None => infer::get_from_path(path).ok().flatten(),
+    };
+    kind.map(|kind| kind.mime_type())
+}
+
+/// Runs `ffprobe` on the file; `None` when it is missing or cannot parse the file
+pub fn probe_media(path: &Path) -> Option<MediaInfo> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "format=duration:stream=codec_name",
+            "-of",
+            "json",
+        ])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let probe: ProbeOutput = serde_json::from_slice(&output.stdout).ok()?;
+    Some(MediaInfo {
+        duration_secs: probe
+            .format
+            .and_then(|format| format.duration)
+            .and_then(|duration| duration.parse().ok()),
+        codec: probe
+            .streams
+            .into_iter()
+            .find_map(|stream| stream.codec_name),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_dimensions_come_from_the_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("small.png");
+        image::RgbImage::new(7, 3).save(&path).unwrap();
+        assert_eq!(image_dimensions(&path), Some((7, 3)));
+
+        let not_an_image = dir.path().join("notes.png");
+        std::fs::write(&not_an_image, b"plain text").unwrap();
+        assert_eq!(image_dimensions(&not_an_image), None);
+    }
+
+    /// Whether the ffmpeg tools are installed; media tests are skipped without them
+    fn ffmpeg_available() -> bool {
+        ["ffmpeg", "ffprobe"].iter().all(|tool| {
+            Command::new(tool)
+                .arg("-version")
+                .output()
+                .is_ok_and(|output| output.status.success())
+        })
+    }
+
+    #[test]
+    fn sample_mp4_reports_duration_and_codec() {
+        if !ffmpeg_available() {
+            eprintln!("skipping: ffmpeg/ffprobe not installed");
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.mp4");
+        let status = Command::new("ffmpeg")
+            .args([
+                "-v",
+                "error",
+                "-f",
+                "lavfi",
+                "-i",
+                "testsrc=duration=1:size=64x64",
+            ])
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let info = probe_media(&path).unwrap();
+        let duration = info.duration_secs.unwrap();
+        assert!(duration > 0.5 && duration < 2.0, "duration {}", duration);
+        assert!(info.codec.is_some());
+    }
+
+    #[test]
+    fn unprobeable_files_yield_no_media_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.mp4");
+        std::fs::write(&path, b"not a video").unwrap();
+        // `None` whether ffprobe is missing or rejects the file
+        assert!(probe_media(&path).is_none());
+    }
+
+    fn ascii_field(tag: exif::Tag, value: &str) -> exif::Field {
+        exif::Field {
+            tag,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Ascii(vec![value.as_bytes().to_vec()]),
+        }
+    }
+
+    /// A minimal JPEG whose APP1 segment holds `fields` as EXIF
+    fn jpeg_with_exif(fields: &[exif::Field]) -> Vec<u8> {
+        let mut writer = exif::experimental::Writer::new();
+        for field in fields {
+            writer.push_field(field);
+        }
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let tiff = tiff.into_inner();
+
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend_from_slice(&u16::try_from(2 + 6 + tiff.len()).unwrap().to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(&[0xFF, 0xD9]);
+        jpeg
+    }
+
+    #[test]
+    fn exif_capture_date_and_camera_are_parsed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.jpg");
+        std::fs::write(
+            &path,
+            jpeg_with_exif(&[
+                ascii_field(exif::Tag::Make, "Canon"),
+                ascii_field(exif::Tag::Model, "EOS R6"),
+                ascii_field(exif::Tag::DateTime, "2024:01:01 00:00:00"),
+                ascii_field(exif::Tag::DateTimeOriginal, "2023:07:14 09:30:05"),
+                ascii_field(exif::Tag::OffsetTimeOriginal, "+02:00"),
+            ]),
+        )
+        .unwrap();
+
+        let info = read_exif(&path).unwrap();
+        assert_eq!(info.camera_make.as_deref(), Some("Canon"));
+        assert_eq!(info.camera_model.as_deref(), Some("EOS R6"));
+        // The original capture time wins over the last-edited `DateTime`
+        assert_eq!(info.taken_at.as_deref(), Some("2023-07-14T09:30:05+02:00"));
+        assert_eq!((info.latitude, info.longitude), (None, None));
+    }
+
+    #[test]
+    fn exif_date_without_offset_stays_local() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.jpg");
+        std::fs::write(
+            &path,
+            jpeg_with_exif(&[ascii_field(
+                exif::Tag::DateTimeOriginal,
+                "2023:07:14 09:30:05",
+            )]),
+        )
+        .unwrap();
+        let info = read_exif(&path).unwrap();
+        assert_eq!(info.taken_at.as_deref(), Some("2023-07-14T09:30:05"));
+        assert_eq!(info.camera_make, None);
+    }
+
+    #[test]
+    fn images_without_exif_yield_none() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plain.png");
+        image::RgbImage::new(2, 2).save(&path).unwrap();
+        assert!(read_exif(&path).is_none());
+    }
+
+    fn rational(values: &[(u32, u32)]) -> Vec<exif::Rational> {
+        values
+            .iter()
+            .map(|&(num, denom)| exif::Rational { num, denom })
+            .collect()
+    }
+
+    fn gps_field(tag: exif::Tag, dms: &[(u32, u32)]) -> exif::Field {
+        exif::Field {
+            tag,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Rational(rational(dms)),
+        }
+    }
+
+    #[test]
+    fn dms_converts_to_decimal_degrees() {
+        let decimal = |dms: &[(u32, u32)]| dms_to_decimal(&rational(dms));
+        let close = |value: Option<f64>, expected: f64| (value.unwrap() - expected).abs() < 1e-6;
+        assert!(close(decimal(&[(33, 1), (51, 1), (3564, 100)]), 33.8599));
+        // Fractional minutes and a missing seconds part
+        assert!(close(decimal(&[(151, 1), (1245, 100)]), 151.2075));
+        assert_eq!(decimal(&[]), None);
+        assert_eq!(decimal(&[(33, 1), (51, 0), (0, 1)]), None);
+    }
+
+    #[test]
+    fn southern_and_western_coordinates_are_negative() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sydney.jpg");
+        // Sydney Opera House: 33°51'21.6" S, 151°12'54.0" E
+        std::fs::write(
+            &path,
+            jpeg_with_exif(&[
+                ascii_field(exif::Tag::GPSLatitudeRef, "S"),
+                gps_field(exif::Tag::GPSLatitude, &[(33, 1), (51, 1), (216, 10)]),
+                ascii_field(exif::Tag::GPSLongitudeRef, "E"),
+                gps_field(exif::Tag::GPSLongitude, &[(151, 1), (12, 1), (540, 10)]),
+            ]),
+        )
+        .unwrap();
+        let info = read_exif(&path).unwrap();
+        assert!(
+            (info.latitude.unwrap() - -33.856).abs() < 1e-4,
+            "{:?}",
+            info.latitude
+        );
+        assert!(
+            (info.longitude.unwrap() - 151.215).abs() < 1e-4,
+            "{:?}",
+            info.longitude
+        );
+
+        let path = dir.path().join("new-york.jpg");
+        // Statue of Liberty: 40°41'21.4" N, 74°2'40.2" W
+        std::fs::write(
+            &path,
+            jpeg_with_exif(&[
+                ascii_field(exif::Tag::GPSLatitudeRef, "N"),
+                gps_field(exif::Tag::GPSLatitude, &[(40, 1), (41, 1), (214, 10)]),
+                ascii_field(exif::Tag::GPSLongitudeRef, "W"),
+                gps_field(exif::Tag::GPSLongitude, &[(74, 1), (2, 1), (402, 10)]),
+            ]),
+        )
+        .unwrap();
+        let info = read_exif(&path).unwrap();
+        assert!(
+            (info.latitude.unwrap() - 40.68928).abs() < 1e-4,
+            "{:?}",
+            info.latitude
+        );
+        assert!(
+            (info.longitude.unwrap() - -74.04450).abs() < 1e-4,
+            "{:?}",
+            info.longitude
+        );
+    }
+}
@@ -0,0 +1,1675 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_array::builder::{ListBuilder, StringBuilder};
+use arrow_array::{
+    ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+    UInt32Array,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use flate2::write::GzEncoder;
+use owo_colors::{AnsiColors, OwoColorize};
+use parquet::arrow::ArrowWriter;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use crate::scanner::ScanStats;
+use crate::status;
+use crate::uploader::FileMeta;
+
+/// Format of the emitted scan results
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Console,
+    Json,
+    /// One compact JSON object per line
+    Ndjson,
+    Csv,
+    /// SQLite database written to `--output-file`, upserted by hash; copies of
+    /// one file share a row, and unhashed files replace their previous row
+    Sqlite,
+    /// Columnar Parquet file written to `--output-file`
+    Parquet,
+    /// `[scan]` totals table followed by one `[[files]]` table per file
+    Toml,
+    /// YAML sequence with the same fields as JSON
+    Yaml,
+    /// Console lines grouped under a header per directory with its subtotals
+    Tree,
+}
+
+impl OutputFormat {
+    /// Human-readable name used in status messages
+    pub fn label(self) -> &'static str {
+        match self {
+            OutputFormat::Console => "Console",
+            OutputFormat::Json => "JSON",
+            OutputFormat::Ndjson => "NDJSON",
+            OutputFormat::Csv => "CSV",
+            OutputFormat::Sqlite => "SQLite",
+            OutputFormat::Parquet => "Parquet",
+            OutputFormat::Toml => "TOML",
+            OutputFormat::Yaml => "YAML",
+            OutputFormat::Tree => "Tree",
+        }
+    }
+
+    /// MIME type of the rendered output, for uploads that store it as an object
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Console | OutputFormat::Tree => "text/plain; charset=utf-8",
+            OutputFormat::Json => "application/json",
+            OutputFormat::Ndjson => "application/x-ndjson",
+            OutputFormat::Csv => "text/csv",
+            OutputFormat::Sqlite => "application/vnd.sqlite3",
+            OutputFormat::Parquet => "application/vnd.apache.parquet",
+            OutputFormat::Toml => "application/toml",
+            OutputFormat::Yaml => "application/yaml",
+        }
+    }
+
+    /// Human-readable formats that are always printed, never saved
+    pub fn is_console(self) -> bool {
+        matches!(self, OutputFormat::Console | OutputFormat::Tree)
+    }
+
+    /// Formats that cannot be printed to the console
+    pub fn requires_output_file(self) -> bool {
+        matches!(self, OutputFormat::Sqlite | OutputFormat::Parquet)
+    }
+
+    /// Formats that can be narrowed by `--output-fields`; the tree has its own layout
+    pub fn supports_fields(self) -> bool {
+        !self.requires_output_file() && self != OutputFormat::Tree
+    }
+
+    /// Formats whose output file can be extended by `--append`
+    pub fn supports_append(self) -> bool {
+        matches!(self, OutputFormat::Ndjson | OutputFormat::Csv)
+    }
+
+    /// Formats that replace an existing `--output-file`; SQLite upserts into it
+    /// and console output never writes it
+    pub fn replaces_output_file(self) -> bool {
+        !self.is_console() && self != OutputFormat::Sqlite
+    }
+}
+
+/// Compression applied to text written to `--output-file`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputCompression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl OutputCompression {
+    /// File extension the compressed output should carry
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            OutputCompression::None => None,
+            OutputCompression::Gzip => Some("gz"),
+            OutputCompression::Zstd => Some("zst"),
+        }
+    }
+
+    /// Adds the compressed extension to `path` unless it already ends with it,
+    /// e.g. `scan.json` becomes `scan.json.gz`
+    pub fn apply_extension(self, path: &str) -> String {
+        match self.extension() {
+            Some(extension) if !path.ends_with(&format!(".{}", extension)) => {
+                format!("{}.{}", path, extension)
+            }
+            _ => path.to_string(),
+        }
+    }
+}
+
+/// When `--color` colorizes console output
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorWhen {
+    /// Only when stdout is a terminal and `NO_COLOR` is unset
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Decoration of console output: ANSI colors and emoji markers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConsoleStyle {
+    pub color: bool,
+    pub emoji: bool,
+}
+
+impl Default for ConsoleStyle {
+    fn default() -> Self {
+        Self {
+            color: false,
+            emoji: true,
+        }
+    }
+}
+
+impl ConsoleStyle {
+    /// Resolves `--color` and `--no-emoji`; a non-empty `NO_COLOR` turns off
+    /// emoji and `auto` color, but not `--color always`
+    pub fn new(color: ColorWhen, no_emoji: bool) -> Self {
+        let no_color = status::no_color_env();
+        let color = match color {
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+            ColorWhen::Auto => !no_color && std::io::IsTerminal::is_terminal(&std::io::stdout()),
+        };
+        Self {
+            color,
+            emoji: !no_emoji && !no_color,
+        }
+    }
+
+    /// `icon` followed by a space, or nothing without emoji
+    pub fn icon(&self, icon: &str) -> String {
+        if self.emoji {
+            format!("{} ", icon)
+        } else {
+            String::new()
+        }
+    }
+
+    /// `text` in `color`, or unchanged without color
+    pub fn paint(&self, text: impl std::fmt::Display, color: AnsiColors) -> String {
+        if self.color {
+            text.color(color).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Colors a `FileMeta` field value by what it holds: type, size, or hash
+    fn paint_field(&self, field: &str, text: String) -> String {
+        match field {
+            "filetype" => self.paint(text, AnsiColors::Cyan),
+            "size" => self.paint(text, AnsiColors::Yellow),
+            "hash" => self.paint(text, AnsiColors::Magenta),
+            _ => text,
+        }
+    }
+}
+
+/// Output file writer that compresses according to `--compress-output`
+enum OutputWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl OutputWriter {
+    /// Creates or truncates `path`, or appends a new compressed member/frame to it
+    fn open(path: &str, compression: OutputCompression, append: bool) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?;
+        let file = BufWriter::new(file);
+        Ok(match compression {
+            OutputCompression::None => OutputWriter::Plain(file),
+            OutputCompression::Gzip => {
+                OutputWriter::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            OutputCompression::Zstd => OutputWriter::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    /// Writes the compression trailer and flushes; dropping without this loses data
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            OutputWriter::Plain(mut file) => file.flush(),
+            OutputWriter::Gzip(encoder) => encoder.finish()?.flush(),
+            OutputWriter::Zstd(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputWriter::Plain(file) => file.write(buf),
+            OutputWriter::Gzip(encoder) => encoder.write(buf),
+            OutputWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputWriter::Plain(file) => file.flush(),
+            OutputWriter::Gzip(encoder) => encoder.flush(),
+            OutputWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Field used to order the emitted files
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortField {
+    #[default]
+    Name,
+    Size,
+    /// Modification time; files without one sort first
+    Mtime,
+    Hash,
+}
+
+/// Sorts files by `field`; ties keep their existing (name) order
+pub fn sort_files(files: &mut [FileMeta], field: SortField, descending: bool) {
+    files.sort_by(|a, b| {
+        let ordering = match field {
+            SortField::Name => a.filename.cmp(&b.filename),
+            SortField::Size => a.size.cmp(&b.size),
+            // RFC 3339 UTC timestamps order correctly as strings
+            SortField::Mtime => a.modified.cmp(&b.modified),
+            SortField::Hash => a.hash.cmp(&b.hash),
+        };
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+/// How file paths are laid out in `FileMeta`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathStyle {
+    /// `filename` holds the full path
+    #[default]
+    Full,
+    /// `filename` holds the base name and `path` the full path
+    Split,
+}
+
+/// Rewrites `filename` into a base name plus `path` for the split style
+pub fn apply_path_style(files: &mut [FileMeta], style: PathStyle) {
+    if style == PathStyle::Full {
+        return;
+    }
+    for file in files {
+        let base_name = Path::new(&file.filename)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        if let Some(base_name) = base_name {
+            file.path = Some(std::mem::replace(&mut file.filename, base_name));
+        }
+    }
+}
+
+/// Rewrites `filename` and `folder` relative to the scan root they were found under.
+/// With several roots, paths are prefixed by the root's directory name.
+pub fn relativize_paths(files: &mut [FileMeta], roots: &[String]) {
+    let roots: Vec<(PathBuf, Option<PathBuf>, PathBuf)> = roots
+        .iter()
+        .enumerate()
+        .map(|(idx, root)| {
+            let root = PathBuf::from(root);
+            let canonical = root.canonicalize().ok();
+            let prefix = if roots.len() > 1 {
+                canonical
+                    .as_deref()
+                    .unwrap_or(&root)
+                    .file_name()
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| PathBuf::from(idx.to_string()))
+            } else {
+                PathBuf::new()
+            };
+            (root, canonical, prefix)
+        })
+        .collect();
+
+    let relative_path = |path: &str| -> Option<PathBuf> {
+        let path = Path::new(path);
+        roots.iter().find_map(|(root, canonical, prefix)| {
+            path.strip_prefix(root)
+                .ok()
+                .or_else(|| path.strip_prefix(canonical.as_ref()?).ok())
+                .map(|rel| prefix.join(rel))
+        })
+    };
+
+    for file in files {
+        if let Some(relative) = relative_path(&file.filename) {
+            file.folder = relative
+                .parent()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default();
+            file.filename = relative.display().to_string();
+        }
+        for duplicate in &mut file.duplicate_paths {
+            if let Some(relative) = relative_path(duplicate) {
+                *duplicate = relative.display().to_string();
+            }
+        }
+    }
+}
+
+/// Expands `{date}`, `{datetime}` and `{root}` (the scan folder's name) in an
+/// `--output-file` path, e.g. `scan-{root}-{date}.json`
+pub fn expand_output_path(template: &str, root: Option<&str>) -> String {
+    let now = chrono::Local::now();
+    let root_name = root
+        .map(Path::new)
+        .and_then(|root| {
+            root.file_name()
+                .map(PathBuf::from)
+                .or_else(|| root.canonicalize().ok()?.file_name().map(PathBuf::from))
+        })
+        .map(|name| name.display().to_string())
+        .unwrap_or_else(|| "root".to_string());
+
+    template
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{datetime}", &now.format("%Y-%m-%dT%H%M%S").to_string())
+        .replace("{root}", &root_name)
+}
+
+/// Where and how results are written
+#[derive(Clone, Debug)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    pub output_file: Option<String>,
+    /// Single-byte CSV field delimiter
+    pub csv_delimiter: u8,
+    /// Write a CSV header row
+    pub csv_header: bool,
+    /// Add to the end of `output_file` instead of replacing it (NDJSON and CSV)
+    pub append: bool,
+    /// Indent JSON output instead of writing it on one line
+    pub pretty: bool,
+    /// `FileMeta` fields to emit, in order; empty emits every field
+    pub fields: Vec<String>,
+    /// Compression of text formats written to `output_file`
+    pub compression: OutputCompression,
+    /// Colors and emoji of console output
+    pub console: ConsoleStyle,
+    /// `--manifest-hash` fingerprint, wrapped around the JSON array and
+    /// appended as a final NDJSON record
+    pub manifest_hash: Option<String>,
+    /// When the scan started, stored as SQLite's `scan_timestamp`
+    pub scan_started: chrono::DateTime<chrono::Utc>,
+}
+
+/// `FileMeta` fields that `--output-fields` can select
+pub const FILE_FIELDS: &[&str] = &[
+    "filename",
+    "path",
+    "path_bytes",
+    "folder",
+    "size",
+    "mime",
+    "hash",
+    "hash_algo",
+    "filetype",
+    "duplicate_paths",
+    "modified",
+    "created",
+    "width",
+    "height",
+    "duration_secs",
+    "codec",
+    "camera_make",
+    "camera_model",
+    "taken_at",
+    "latitude",
+    "longitude",
+    "chunks",
+];
+
+/// Validates one `--output-fields` entry
+pub fn parse_field(input: &str) -> Result<String, String> {
+    let field = input.trim();
+    if FILE_FIELDS.contains(&field) {
+        Ok(field.to_string())
+    } else {
+        Err(format!(
+            "unknown field {:?} (expected one of: {})",
+            field,
+            FILE_FIELDS.join(", ")
+        ))
+    }
+}
+
+/// A file restricted to the selected fields; fields the file has no value for
+/// are left out, like `None` fields in the full output
+struct ProjectedFile<'a> {
+    values: Vec<(&'a str, serde_json::Value)>,
+}
+
+impl<'a> ProjectedFile<'a> {
+    fn new(file: &FileMeta, fields: &'a [String]) -> Self {
+        let serde_json::Value::Object(mut object) =
+            serde_json::to_value(file).expect("Failed to serialize file metadata")
+        else {
+            unreachable!("FileMeta serializes to a JSON object");
+        };
+        let values = fields
+            .iter()
+            .filter_map(|field| Some((field.as_str(), object.remove(field)?)))
+            .collect();
+        Self { values }
+    }
+
+    fn get(&self, field: &str) -> Option<&serde_json::Value> {
+        self.values
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, value)| value)
+    }
+}
+
+impl Serialize for ProjectedFile<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.values.len()))?;
+        for (field, value) in &self.values {
+            map.serialize_entry(field, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Renders a projected value as plain text, without quotes around strings
+fn field_text(value: Option<&serde_json::Value>) -> String {
+    match value {
+        None => String::new(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// Renders the files in the selected format and prints or saves them
+pub fn write_files(files: &[FileMeta], options: &OutputOptions) {
+    if !options.fields.is_empty() {
+        let content = render_projected(files, options);
+        match options.format {
+            OutputFormat::Console => print!("{}", content),
+            _ => emit_output(&content, options),
+        }
+        return;
+    }
+    let format = options.format;
+    let output_file = options.output_file.as_deref();
+    match format {
+        // Console output is always printed, never saved
+        OutputFormat::Console => print!("{}", generate_console(files, options.console)),
+        OutputFormat::Tree => print!("{}", generate_tree(files, options.console)),
+        OutputFormat::Json => match output_file {
+            Some(output_file) => {
+                stream_json(files, output_file, options)
+                    .unwrap_or_else(|e| panic!("Failed to write JSON file: {}", e));
+                status!("💾 JSON output saved to: {}", output_file);
+            }
+            None => emit(&json_files(files, options), None, format.label()),
+        },
+        OutputFormat::Ndjson => emit_output(&ndjson_files(files, options), options),
+        OutputFormat::Csv => emit_output(&generate_csv(files, options), options),
+        OutputFormat::Toml => emit_output(&generate_toml(files), options),
+        OutputFormat::Yaml => emit_output(&generate_yaml(files), options),
+        OutputFormat::Sqlite => {
+            let output_file = output_file.expect("--output-file is required for sqlite output");
+            let rows = write_sqlite(files, output_file, options.scan_started)
+                .unwrap_or_else(|e| panic!("Failed to write SQLite database: {}", e));
+            status!("💾 SQLite output saved to: {} ({} rows)", output_file, rows);
+        }
+        OutputFormat::Parquet => {
+            let output_file = output_file.expect("--output-file is required for parquet output");
+            write_parquet(files, output_file)
+                .unwrap_or_else(|e| panic!("Failed to write Parquet file: {}", e));
+            status!("💾 Parquet output saved to: {}", output_file);
+        }
+    }
+}
+
+/// Prints or saves only the scan totals for `--stats-only`; on the console
+/// the summary printed after the output is the whole payload
+pub fn write_stats(stats: &ScanStats, options: &OutputOptions) {
+    let content = match options.format {
+        OutputFormat::Console | OutputFormat::Tree => return,
+        OutputFormat::Json => generate_json(stats, options.pretty),
+        OutputFormat::Ndjson => generate_ndjson(std::slice::from_ref(stats)),
+        OutputFormat::Toml => toml::to_string(stats).expect("Failed to serialize to TOML"),
+        OutputFormat::Yaml => generate_yaml(stats),
+        OutputFormat::Csv | OutputFormat::Sqlite | OutputFormat::Parquet => {
+            unreachable!("rejected during argument validation")
+        }
+    };
+    emit_output(&content, options);
+}
+
+/// Renders the files exactly as [`write_files`] would write them; `None` for
+/// SQLite and Parquet, which only exist as files
+pub fn render(files: &[FileMeta], options: &OutputOptions) -> Option<String> {
+    if !options.fields.is_empty() {
+        return options
+            .format
+            .supports_fields()
+            .then(|| render_projected(files, options));
+    }
+    let content = match options.format {
+        OutputFormat::Console => generate_console(files, options.console),
+        OutputFormat::Tree => generate_tree(files, options.console),
+        OutputFormat::Json => json_files(files, options),
+        OutputFormat::Ndjson => ndjson_files(files, options),
+        OutputFormat::Csv => generate_csv(files, options),
+        OutputFormat::Toml => generate_toml(files),
+        OutputFormat::Yaml => generate_yaml(files),
+        OutputFormat::Sqlite | OutputFormat::Parquet => return None,
+    };
+    Some(content)
+}
+
+/// Renders only the `--output-fields` selection, in the requested order
+fn render_projected(files: &[FileMeta], options: &OutputOptions) -> String {
+    let fields = &options.fields;
+    let projected: Vec<ProjectedFile> = files
+        .iter()
+        .map(|file| ProjectedFile::new(file, fields))
+        .collect();
+    match options.format {
+        OutputFormat::Console => {
+            let mut output = String::new();
+            for file in &projected {
+                let values: Vec<String> = file
+                    .values
+                    .iter()
+                    .map(|(field, value)| {
+                        let text = options.console.paint_field(field, field_text(Some(value)));
+                        format!("{}: {}", field, text)
+                    })
+                    .collect();
+                output.push_str(&format!(
+                    "{}{}\n",
+                    options.console.icon("📄"),
+                    values.join(", ")
+                ));
+            }
+            output
+        }
+        OutputFormat::Json => json_files(&projected, options),
+        OutputFormat::Ndjson => ndjson_files(&projected, options),
+        OutputFormat::Csv => {
+            let mut writer = csv_writer(options);
+            if options.csv_header {
+                writer
+                    .write_record(fields)
+                    .expect("Failed to serialize to CSV");
+            }
+            for file in &projected {
+                writer
+                    .write_record(fields.iter().map(|field| field_text(file.get(field))))
+                    .expect("Failed to serialize to CSV");
+            }
+            finish_csv(writer)
+        }
+        OutputFormat::Toml => toml_document(files, &projected),
+        OutputFormat::Yaml => generate_yaml(&projected),
+        OutputFormat::Sqlite | OutputFormat::Parquet | OutputFormat::Tree => {
+            unreachable!("rejected during argument validation")
+        }
+    }
+}
+
+/// Saves rendered output to `output_file`, or prints it when none is given
+pub fn emit(content: &str, output_file: Option<&str>, label: &str) {
+    if let Some(output_file) = output_file {
+        std::fs::write(output_file, content)
+            .unwrap_or_else(|e| panic!("Failed to write {} file: {}", label, e));
+        status!("💾 {} output saved to: {}", label, output_file);
+    } else {
+        // An empty payload prints nothing, not a blank line NDJSON readers would reject
+        let content = content.trim_end();
+        if !content.is_empty() {
+            println!("{}", content);
+        }
+    }
+}
+
+/// Like [`emit`], but honors `--append` and `--compress-output`
+pub fn emit_output(content: &str, options: &OutputOptions) {
+    let label = options.format.label();
+    match options.output_file.as_deref() {
+        Some(output_file) => {
+            write_output(output_file, content, options).unwrap_or_else(|e| {
+                let action = if options.append { "append to" } else { "write" };
+                panic!("Failed to {} {} file: {}", action, label, e)
+            });
+            let action = if options.append {
+                "appended to"
+            } else {
+                "saved to"
+            };
+            status!("💾 {} output {}: {}", label, action, output_file);
+        }
+        None => emit(content, None, label),
+    }
+}
+
+fn write_output(output_file: &str, content: &str, options: &OutputOptions) -> std::io::Result<()> {
+    let mut writer = OutputWriter::open(output_file, options.compression, options.append)?;
+    writer.write_all(content.as_bytes())?;
+    // Appended records must start on a fresh line
+    if options.append && !content.is_empty() && !content.ends_with('\n') {
+        writer.write_all(b"\n")?;
+    }
+    writer.finish()
+}
+
+/// Whether `--append` would add to a file that already has content, in which
+/// case a CSV header must not be repeated
+pub fn appending_to_existing(output_file: Option<&str>, append: bool) -> bool {
+    append
+        && output_file
+            .and_then(|path| std::fs::metadata(path).ok())
+            .is_some_and(|metadata| metadata.len() > 0)
+}
+
+/// Totals for a finished scan followed by one line per file type and the
+/// `--manifest-hash` if computed, e.g.
+/// `📊 14 files, 12 unique, 31.3 KB total, 5.9 KB saved by dedup`
+/// and `   image: 3 files, 1.2 MB`
+pub fn generate_summary(stats: &ScanStats) -> String {
+    let mut summary = format!(
+        "📊 {} files, {} unique, {} total, {} saved by dedup\n",
+        stats.unique_files + stats.duplicate_files,
+        stats.unique_files,
+        format_size(stats.total_bytes),
+        format_size(stats.duplicate_bytes)
+    );
+    for (filetype, totals) in &stats.by_filetype {
+        summary.push_str(&format!(
+            "   {}: {} files, {}\n",
+            filetype,
+            totals.count,
+            format_size(totals.total_size)
+        ));
+    }
+    if let Some(manifest_hash) = &stats.manifest_hash {
+        summary.push_str(&format!("🔏 Manifest hash: {}\n", manifest_hash));
+    }
+    summary
+}
+
+/// Formats a byte count with a binary unit, e.g. `512 B` or `1.5 MB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KB", "MB", "GB", "TB", "PB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+pub fn generate_console(files: &[FileMeta], style: ConsoleStyle) -> String {
+    let mut output = String::new();
+    for file in files {
+        output.push_str(&format!(
+            "{}{} ({} bytes, {}, hash: {}, type: {}",
+            style.icon("📄"),
+            file.filename,
+            style.paint(file.size, AnsiColors::Yellow),
+            file.mime,
+            style.paint(&file.hash, AnsiColors::Magenta),
+            style.paint(&file.filetype, AnsiColors::Cyan)
+        ));
+        if let Some(modified) = &file.modified {
+            output.push_str(&format!(", modified: {}", modified));
+        }
+        output.push_str(")\n");
+    }
+    output
+}
+
+/// Renders one header per directory with its file count and total size,
+/// followed by its files indented beneath it, e.g.
+/// `📁 /photos/2024 (2 files, 3.1 MB)` then `   📄 a.jpg (...)`
+pub fn generate_tree(files: &[FileMeta], style: ConsoleStyle) -> String {
+    // Files keep their `--sort` order within each directory
+    let mut by_folder: BTreeMap<&str, Vec<&FileMeta>> = BTreeMap::new();
+    for file in files {
+        by_folder.entry(&file.folder).or_default().push(file);
+    }
+
+    let mut output = String::new();
+    for (folder, files) in by_folder {
+        let total_size: u64 = files.iter().map(|file| file.size).sum();
+        output.push_str(&format!(
+            "{}{} ({} files, {})\n",
+            style.icon("📁"),
+            folder,
+            files.len(),
+            style.paint(format_size(total_size), AnsiColors::Yellow)
+        ));
+        for file in files {
+            let name = Path::new(file.full_path())
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_else(|| file.full_path().into());
+            output.push_str(&format!(
+                "   {}{} ({} bytes, hash: {}, type: {})\n",
+                style.icon("📄"),
+                name,
+                style.paint(file.size, AnsiColors::Yellow),
+                style.paint(&file.hash, AnsiColors::Magenta),
+                style.paint(&file.filetype, AnsiColors::Cyan)
+            ));
+        }
+    }
+    output
+}
+
+pub fn generate_json<T: Serialize + ?Sized>(value: &T, pretty: bool) -> String {
+    let json = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+    json.expect("Failed to serialize to JSON")
+}
+
+/// Streams the JSON array to disk element by element instead of building one string
+fn stream_json(
+    files: &[FileMeta],
+    output_file: &str,
+    options: &OutputOptions,
+) -> std::io::Result<()> {
+    let mut writer = OutputWriter::open(output_file, options.compression, false)?;
+    match &options.manifest_hash {
+        Some(manifest_hash) => {
+            let document = FingerprintedFiles {
+                manifest_hash,
+                files,
+            };
+            write_json(&mut writer, &document, options.pretty)?;
+        }
+        None => write_json(&mut writer, files, options.pretty)?,
+    }
+    writer.finish()
+}
+
+fn write_json<W: Write, T: Serialize + ?Sized>(
+    writer: W,
+    value: &T,
+    pretty: bool,
+) -> serde_json::Result<()> {
+    if pretty {
+        serde_json::to_writer_pretty(writer, value)
+    } else {
+        serde_json::to_writer(writer, value)
+    }
+}
+
+/// `--output json` under `--manifest-hash`: the file array alongside its fingerprint
+#[derive(Serialize)]
+struct FingerprintedFiles<'a, T> {
+    manifest_hash: &'a str,
+    files: &'a [T],
+}
+
+/// The file array as JSON, wrapped with the manifest hash when there is one
+fn json_files<T: Serialize>(files: &[T], options: &OutputOptions) -> String {
+    match &options.manifest_hash {
+        Some(manifest_hash) => generate_json(
+            &FingerprintedFiles {
+                manifest_hash,
+                files,
+            },
+            options.pretty,
+        ),
+        None => generate_json(files, options.pretty),
+    }
+}
+
+/// One line per file, then a `{"manifest_hash": ...}` record when there is one
+fn ndjson_files<T: Serialize>(files: &[T], options: &OutputOptions) -> String {
+    let mut output = generate_ndjson(files);
+    if let Some(manifest_hash) = &options.manifest_hash {
+        output.push_str(&generate_json(
+            &serde_json::json!({ "manifest_hash": manifest_hash }),
+            false,
+        ));
+        output.push('\n');
+    }
+    output
+}
+
+/// Top-level `--output toml` document
+#[derive(Serialize)]
+struct TomlDocument<'a, T> {
+    scan: TomlScan,
+    files: &'a [T],
+}
+
+#[derive(Serialize)]
+struct TomlScan {
+    timestamp: String,
+    total_files: usize,
+    total_size: u64,
+}
+
+pub fn generate_toml(files: &[FileMeta]) -> String {
+    toml_document(files, files)
+}
+
+/// Builds the TOML document with totals from `files` and `entries` as its tables
+fn toml_document<T: Serialize>(files: &[FileMeta], entries: &[T]) -> String {
+    let document = TomlDocument {
+        scan: TomlScan {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            total_files: files.len(),
+            total_size: files.iter().map(|file| file.size).sum(),
+        },
+        files: entries,
+    };
+    toml::to_string(&document).expect("Failed to serialize to TOML")
+}
+
+pub fn generate_yaml<T: Serialize + ?Sized>(value: &T) -> String {
+    serde_yaml::to_string(value).expect("Failed to serialize to YAML")
+}
+
+/// Serializes each item as a compact JSON object on its own line
+pub fn generate_ndjson<T: Serialize>(items: &[T]) -> String {
+    let mut output = String::new();
+    for item in items {
+        output.push_str(&serde_json::to_string(item).expect("Failed to serialize to JSON"));
+        output.push('\n');
+    }
+    output
+}
+
+/// One CSV record; borrowed so rows serialize without cloning
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    filename: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'a str>,
+    folder: &'a str,
+    size: u64,
+    mime: &'a str,
+    hash: &'a str,
+    hash_algo: &'a str,
+    filetype: &'a str,
+    modified: Option<&'a str>,
+    created: Option<&'a str>,
+}
+
+impl<'a> From<&'a FileMeta> for CsvRow<'a> {
+    fn from(file: &'a FileMeta) -> Self {
+        Self {
+            filename: &file.filename,
+            path: file.path.as_deref(),
+            folder: &file.folder,
+            size: file.size,
+            mime: &file.mime,
+            hash: &file.hash,
+            hash_algo: &file.hash_algo,
+            filetype: &file.filetype,
+            modified: file.modified.as_deref(),
+            created: file.created.as_deref(),
+        }
+    }
+}
+
+/// Renders RFC 4180 CSV, quoting fields with delimiters, quotes, or newlines
+pub fn generate_csv(files: &[FileMeta], options: &OutputOptions) -> String {
+    let mut writer = csv_writer(options);
+    for file in files {
+        writer
+            .serialize(CsvRow::from(file))
+            .expect("Failed to serialize to CSV");
+    }
+    finish_csv(writer)
+}
+
+/// In-memory CSV writer honoring the delimiter and header settings
+pub fn csv_writer(options: &OutputOptions) -> csv::Writer<Vec<u8>> {
+    csv::WriterBuilder::new()
+        .delimiter(options.csv_delimiter)
+        .has_headers(options.csv_header)
+        .from_writer(Vec::new())
+}
+
+/// Extracts the rendered CSV text from an in-memory writer
+pub fn finish_csv(writer: csv::Writer<Vec<u8>>) -> String {
+    let bytes = writer.into_inner().expect("Failed to flush CSV");
+    String::from_utf8(bytes).expect("CSV output is not valid UTF-8")
+}
+
+/// Writes files into a `files` table in one transaction, upserting on the hash
+///
+/// Copies of one file share a row holding the last path written. Empty hashes
+/// (`--no-hash`) are stored as NULL and replace the row for the same path; list
+/// fields are stored as JSON arrays. Every row records `scan_started` as its
+/// `scan_timestamp`.
+pub fn write_sqlite(
+    files: &[FileMeta],
+    db_path: &str,
+    scan_started: chrono::DateTime<chrono::Utc>,
+) -> rusqlite::Result<usize> {
+    let mut conn = rusqlite::Connection::open(db_path)?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS files (
+            hash TEXT PRIMARY KEY,
+            path TEXT NOT NULL,
+            filename TEXT NOT NULL,
+            path_bytes TEXT,
+            folder TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            mime TEXT NOT NULL,
+            hash_algo TEXT,
+            filetype TEXT NOT NULL,
+            duplicate_paths TEXT NOT NULL,
+            modified TEXT,
+            created TEXT,
+            width INTEGER,
+            height INTEGER,
+            duration_secs REAL,
+            codec TEXT,
+            camera_make TEXT,
+            camera_model TEXT,
+            taken_at TEXT,
+            latitude REAL,
+            longitude REAL,
+            chunks TEXT NOT NULL,
+            scan_timestamp TEXT NOT NULL
+        )",
+    )?;
+
+    let scan_timestamp = scan_started.to_rfc3339();
+    let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+    let json_list =
+        |values: &[String]| serde_json::to_string(values).expect("Failed to serialize list");
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO files (
+                path, filename, path_bytes, folder, size, mime, hash, hash_algo, filetype,
+                duplicate_paths, modified, created, width, height, duration_secs, codec,
+                camera_make, camera_model, taken_at, latitude, longitude, chunks, scan_timestamp
+             )
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+                     ?17, ?18, ?19, ?20, ?21, ?22, ?23)
+             ON CONFLICT(hash) DO UPDATE SET
+                path = excluded.path,
+                filename = excluded.filename,
+                path_bytes = excluded.path_bytes,
+                folder = excluded.folder,
+                size = excluded.size,
+                mime = excluded.mime,
+                hash_algo = excluded.hash_algo,
+                filetype = excluded.filetype,
+                duplicate_paths = excluded.duplicate_paths,
+                modified = excluded.modified,
+                created = excluded.created,
+                width = excluded.width,
+                height = excluded.height,
+                duration_secs = excluded.duration_secs,
+                codec = excluded.codec,
+                camera_make = excluded.camera_make,
+                camera_model = excluded.camera_model,
+                taken_at = excluded.taken_at,
+                latitude = excluded.latitude,
+                longitude = excluded.longitude,
+                chunks = excluded.chunks,
+                scan_timestamp = excluded.scan_timestamp",
+        )?;
+        // NULL hashes never conflict, so an unhashed file replaces its own earlier row
+        let mut clear_unhashed =
+            tx.prepare("DELETE FROM files WHERE hash IS NULL AND path = ?1")?;
+        for file in files {
+            let hash = non_empty(&file.hash);
+            if hash.is_none() {
+                clear_unhashed.execute([file.full_path()])?;
+            }
+            stmt.execute(rusqlite::params![
+                file.full_path(),
+                file.filename,
+                file.path_bytes,
+                file.folder,
+                file.size as i64,
+                file.mime,
+                hash,
+                non_empty(&file.hash_algo),
+                file.filetype,
+                json_list(&file.duplicate_paths),
+                file.modified,
+                file.created,
+                file.width,
+                file.height,
+                file.duration_secs,
+                file.codec,
+                file.camera_make,
+                file.camera_model,
+                file.taken_at,
+                file.latitude,
+                file.longitude,
+                json_list(&file.chunks),
+                scan_timestamp,
+            ])?;
+        }
+    }
+    tx.commit()?;
+
+    Ok(files.len())
+}
+
+/// Writes files as a single Parquet row group with one column per `FileMeta` field
+///
+/// `modified` and `created` become UTC timestamps; `taken_at` stays text since
+/// EXIF capture times often carry no UTC offset.
+pub fn write_parquet(files: &[FileMeta], path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let string_column = |values: Vec<&str>| -> ArrayRef { Arc::new(StringArray::from(values)) };
+    let optional_string_column = |value: fn(&FileMeta) -> Option<&str>| -> ArrayRef {
+        Arc::new(files.iter().map(value).collect::<StringArray>())
+    };
+    let float_column = |value: fn(&FileMeta) -> Option<f64>| -> ArrayRef {
+        Arc::new(files.iter().map(value).collect::<Float64Array>())
+    };
+    let pixels_column = |value: fn(&FileMeta) -> Option<u32>| -> ArrayRef {
+        Arc::new(files.iter().map(value).collect::<UInt32Array>())
+    };
+    let timestamp_column = |value: fn(&FileMeta) -> Option<&str>| -> ArrayRef {
+        let micros = files.iter().map(|file| {
+            let time = chrono::DateTime::parse_from_rfc3339(value(file)?).ok()?;
+            Some(time.timestamp_micros())
+        });
+        Arc::new(
+            micros
+                .collect::<TimestampMicrosecondArray>()
+                .with_timezone("UTC"),
+        )
+    };
+    let list_column = |values: fn(&FileMeta) -> &[String]| -> ArrayRef {
+        let mut builder = ListBuilder::new(StringBuilder::new());
+        for file in files {
+            for value in values(file) {
+                builder.values().append_value(value);
+            }
+            builder.append(true);
+        }
+        Arc::new(builder.finish())
+    };
+
+    let list = || DataType::List(Arc::new(Field::new("item", DataType::Utf8, true)));
+    let timestamp = || DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("filename", DataType::Utf8, false),
+        Field::new("path", DataType::Utf8, true),
+        Field::new("path_bytes", DataType::Utf8, true),
+        Field::new("folder", DataType::Utf8, false),
+        Field::new("size", DataType::Int64, false),
+        Field::new("mime", DataType::Utf8, false),
+        Field::new("hash", DataType::Utf8, false),
+        Field::new("hash_algo", DataType::Utf8, false),
+        Field::new("filetype", DataType::Utf8, false),
+        Field::new("duplicate_paths", list(), false),
+        Field::new("modified", timestamp(), true),
+        Field::new("created", timestamp(), true),
+        Field::new("width", DataType::UInt32, true),
+        Field::new("height", DataType::UInt32, true),
+        Field::new("duration_secs", DataType::Float64, true),
+        Field::new("codec", DataType::Utf8, true),
+        Field::new("camera_make", DataType::Utf8, true),
+        Field::new("camera_model", DataType::Utf8, true),
+        Field::new("taken_at", DataType::Utf8, true),
+        Field::new("latitude", DataType::Float64, true),
+        Field::new("longitude", DataType::Float64, true),
+        Field::new("chunks", list(), false),
+    ]));
+
+    let columns: Vec<ArrayRef> = vec![
+        string_column(files.iter().map(|f| f.filename.as_str()).collect()),
+        optional_string_column(|f| f.path.as_deref()),
+        optional_string_column(|f| f.path_bytes.as_deref()),
+        string_column(files.iter().map(|f| f.folder.as_str()).collect()),
+        Arc::new(Int64Array::from_iter_values(
+            files.iter().map(|f| f.size as i64),
+        )),
+        string_column(files.iter().map(|f| f.mime.as_str()).collect()),
+        string_column(files.iter().map(|f| f.hash.as_str()).collect()),
+        string_column(files.iter().map(|f| f.hash_algo.as_str()).collect()),
+        string_column(files.iter().map(|f| f.filetype.as_str()).collect()),
+        list_column(|f| &f.duplicate_paths),
+        timestamp_column(|f| f.modified.as_deref()),
+        timestamp_column(|f| f.created.as_deref()),
+        pixels_column(|f| f.width),
+        pixels_column(|f| f.height),
+        float_column(|f| f.duration_secs),
+        optional_string_column(|f| f.codec.as_deref()),
+        optional_string_column(|f| f.camera_make.as_deref()),
+        optional_string_column(|f| f.camera_model.as_deref()),
+        optional_string_column(|f| f.taken_at.as_deref()),
+        float_column(|f| f.latitude),
+        float_column(|f| f.longitude),
+        list_column(|f| &f.chunks),
+    ];
+
+    let batch = RecordBatch::try_new(Arc::clone(&schema), columns)?;
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(format: OutputFormat) -> OutputOptions {
+        OutputOptions {
+            format,
+            output_file: None,
+            csv_delimiter: b',',
+            csv_header: true,
+            append: false,
+            pretty: false,
+            fields: Vec::new(),
+            compression: OutputCompression::None,
+            console: ConsoleStyle::default(),
+            manifest_hash: None,
+            scan_started: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn ndjson_writes_one_standalone_object_per_file() {
+        let files = FileMeta::test_files(3);
+        let ndjson = render(&files, &options(OutputFormat::Ndjson)).unwrap();
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), files.len());
+        for (line, file) in lines.iter().zip(&files) {
+            let object: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(object.is_object());
+            assert_eq!(object["filename"], file.filename.as_str());
+        }
+    }
+
+    #[test]
+    fn streamed_json_file_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scan.json");
+        let files = FileMeta::test_files(20_000);
+        let options = OutputOptions {
+            output_file: Some(path.display().to_string()),
+            ..options(OutputFormat::Json)
+        };
+        write_files(&files, &options);
+
+        let parsed: Vec<FileMeta> =
+            serde_json::from_reader(std::io::BufReader::new(File::open(&path).unwrap())).unwrap();
+        assert_eq!(parsed.len(), files.len());
+        assert!(parsed
+            .iter()
+            .zip(&files)
+            .all(|(a, b)| a.filename == b.filename && a.hash == b.hash));
+    }
+
+    #[test]
+    fn streamed_json_file_wraps_files_with_the_manifest_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scan.json");
+        let files = FileMeta::test_files(3);
+        let options = OutputOptions {
+            output_file: Some(path.display().to_string()),
+            manifest_hash: Some("abc123".to_string()),
+            ..options(OutputFormat::Json)
+        };
+        write_files(&files, &options);
+
+        let parsed: serde_json::Value =
+            serde_json::from_reader(std::io::BufReader::new(File::open(&path).unwrap())).unwrap();
+        assert_eq!(parsed["manifest_hash"], "abc123");
+        assert_eq!(parsed["files"].as_array().unwrap().len(), 3);
+    }
+
+    fn row_count(db_path: &str) -> i64 {
+        rusqlite::Connection::open(db_path)
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM files", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn sqlite_upserts_by_hash_across_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("scan.db").display().to_string();
+
+        // Same content under two names shares a row; unhashed files get one each
+        let mut scanned = vec![
+            FileMeta::for_test("/photos/a.jpg"),
+            FileMeta::for_test("/photos/b.jpg"),
+        ];
+        scanned[1].hash = scanned[0].hash.clone();
+        scanned[1].width = Some(640);
+        scanned[1].latitude = Some(-33.5);
+        let mut unhashed = FileMeta::for_test("/photos/c.jpg");
+        unhashed.hash.clear();
+        unhashed.hash_algo.clear();
+        scanned.push(unhashed.clone());
+        unhashed.filename = "/photos/d.jpg".to_string();
+        scanned.push(unhashed);
+
+        assert_eq!(
+            write_sqlite(&scanned, &db_path, chrono::Utc::now()).unwrap(),
+            4
+        );
+        assert_eq!(row_count(&db_path), 3);
+
+        // Re-running updates rows in place instead of adding new ones
+        scanned[1].size = 99;
+        write_sqlite(&scanned, &db_path, chrono::Utc::now()).unwrap();
+        assert_eq!(row_count(&db_path), 3);
+
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        let (path, size, width, latitude): (String, i64, Option<u32>, Option<f64>) = conn
+            .query_row(
+                "SELECT path, size, width, latitude FROM files WHERE hash = ?1",
+                [&scanned[0].hash],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            (path.as_str(), size, width, latitude),
+            ("/photos/b.jpg", 99, Some(640), Some(-33.5))
+        );
+        let unhashed: i64 = conn
+            .query_row("SELECT COUNT(*) FROM files WHERE hash IS NULL", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(unhashed, 2);
+    }
+
+    #[test]
+    fn scanned_directory_round_trips_through_sqlite() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("photos");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        for i in 0..5u8 {
+            std::fs::write(root.join(format!("sub/{}.jpg", i)), vec![i; 2048]).unwrap();
+        }
+        let report = crate::scanner::scan_folder(
+            &[root.display().to_string()],
+            &crate::scanner::ScanOptions::default(),
+            None,
+        );
+        let db_path = dir.path().join("scan.db").display().to_string();
+        write_sqlite(&report.files, &db_path, report.stats.started_at).unwrap();
+        assert_eq!(row_count(&db_path), 5);
+
+        // Every row carries the scan start, not the time it was written
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        let timestamps: Vec<String> = conn
+            .prepare("SELECT DISTINCT scan_timestamp FROM files")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(timestamps, [report.stats.started_at.to_rfc3339()]);
+    }
+
+    #[test]
+    fn parquet_reads_back_with_typed_nullable_columns() {
+        use arrow_array::cast::AsArray;
+        use arrow_array::types::{Float64Type, TimestampMicrosecondType};
+        use arrow_array::Array;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scan.parquet");
+        let mut files = FileMeta::test_files(3);
+        files[0].modified = Some("2024-05-04T11:02:01+00:00".to_string());
+        files[0].latitude = Some(-33.5);
+        files[0].chunks = vec!["c1".to_string(), "c2".to_string()];
+        write_parquet(&files, path.to_str().unwrap()).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 3);
+
+        let batch = &batches[0];
+        let schema = batch.schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, FILE_FIELDS);
+        let field = |name: &str| schema.field_with_name(name).unwrap().clone();
+        assert_eq!(field("size").data_type(), &DataType::Int64);
+        assert_eq!(field("latitude").data_type(), &DataType::Float64);
+        assert!(field("latitude").is_nullable());
+        assert!(matches!(
+            field("modified").data_type(),
+            DataType::Timestamp(TimeUnit::Microsecond, Some(_))
+        ));
+        assert!(matches!(field("chunks").data_type(), DataType::List(_)));
+
+        let column = |name: &str| batch.column(schema.index_of(name).unwrap()).clone();
+        let modified = column("modified");
+        let modified = modified.as_primitive::<TimestampMicrosecondType>();
+        assert_eq!(modified.value(0), 1_714_820_521_000_000);
+        assert!(modified.is_null(1));
+        let latitude = column("latitude");
+        assert_eq!(latitude.as_primitive::<Float64Type>().value(0), -33.5);
+        assert_eq!(column("chunks").as_list::<i32>().value(0).len(), 2);
+        assert!(column("path").is_null(0));
+    }
+
+    #[test]
+    fn csv_quotes_commas_and_newlines_round_trip() {
+        let names = [
+            "/photos/say \"cheese\".jpg",
+            "/photos/a, b.jpg",
+            "/photos/line\nbreak.jpg",
+        ];
+        let files: Vec<FileMeta> = names.iter().map(|name| FileMeta::for_test(name)).collect();
+        let csv = render(&files, &options(OutputFormat::Csv)).unwrap();
+
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let headers = reader.headers().unwrap().clone();
+        assert_eq!(&headers[0], "filename");
+        let filenames: Vec<String> = reader
+            .records()
+            .map(|record| record.unwrap()[0].to_string())
+            .collect();
+        assert_eq!(filenames, names);
+    }
+
+    #[test]
+    fn csv_honors_tab_delimiter_and_header_suppression() {
+        let files = FileMeta::test_files(2);
+        let tabbed = OutputOptions {
+            csv_delimiter: b'\t',
+            ..options(OutputFormat::Csv)
+        };
+        let csv = render(&files, &tabbed).unwrap();
+        let header = csv.lines().next().unwrap();
+        assert!(header.starts_with("filename\tfolder\tsize\t"), "{}", header);
+
+        let headerless = OutputOptions {
+            csv_header: false,
+            ..tabbed
+        };
+        let csv = render(&files, &headerless).unwrap();
+        assert_eq!(csv.lines().count(), 2);
+        assert!(csv.starts_with("/photos/0.jpg\t/photos\t1234\t"), "{}", csv);
+    }
+
+    #[test]
+    fn split_path_style_joins_folder_and_filename_back_to_path() {
+        let mut files = vec![FileMeta::for_test("/photos/2024/beach.jpg")];
+        files[0].folder = "/photos/2024".to_string();
+        apply_path_style(&mut files, PathStyle::Split);
+
+        let split = &files[0];
+        assert_eq!(split.filename, "beach.jpg");
+        assert_eq!(split.path.as_deref(), Some("/photos/2024/beach.jpg"));
+        assert_eq!(
+            format!("{}/{}", split.folder, split.filename),
+            split.path.as_deref().unwrap()
+        );
+        assert_eq!(split.full_path(), "/photos/2024/beach.jpg");
+
+        let mut unchanged = vec![FileMeta::for_test("/photos/a.jpg")];
+        apply_path_style(&mut unchanged, PathStyle::Full);
+        assert_eq!(unchanged[0].filename, "/photos/a.jpg");
+        assert!(unchanged[0].path.is_none());
+    }
+
+    #[test]
+    fn relative_paths_start_at_the_first_subdirectory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("albums/2024")).unwrap();
+        std::fs::write(dir.path().join("albums/2024/a.jpg"), vec![1; 2048]).unwrap();
+        std::fs::write(dir.path().join("albums/b.jpg"), vec![2; 2048]).unwrap();
+        let roots = [dir.path().display().to_string()];
+        let mut files =
+            crate::scanner::scan_folder(&roots, &crate::scanner::ScanOptions::default(), None)
+                .files;
+
+        relativize_paths(&mut files, &roots);
+        let paths: Vec<(&str, &str)> = files
+            .iter()
+            .map(|file| (file.filename.as_str(), file.folder.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("albums/2024/a.jpg", "albums/2024"),
+                ("albums/b.jpg", "albums")
+            ]
+        );
+    }
+
+    #[test]
+    fn relative_paths_from_several_roots_are_prefixed_by_root_name() {
+        let mut files = vec![
+            FileMeta::for_test("/data/photos/a.jpg"),
+            FileMeta::for_test("/data/videos/b.mp4"),
+        ];
+        relativize_paths(
+            &mut files,
+            &["/data/photos".to_string(), "/data/videos".to_string()],
+        );
+        let names: Vec<&str> = files.iter().map(|file| file.filename.as_str()).collect();
+        assert_eq!(names, ["photos/a.jpg", "videos/b.mp4"]);
+    }
+
+    #[test]
+    fn size_descending_puts_the_largest_file_first() {
+        let mut files = FileMeta::test_files(3);
+        files[0].size = 10;
+        files[1].size = 3000;
+        files[2].size = 200;
+        sort_files(&mut files, SortField::Size, true);
+        let sizes: Vec<u64> = files.iter().map(|file| file.size).collect();
+        assert_eq!(sizes, [3000, 200, 10]);
+
+        sort_files(&mut files, SortField::Name, false);
+        assert_eq!(files[0].filename, "/photos/0.jpg");
+    }
+
+    #[test]
+    fn output_path_placeholders_expand_to_today_and_the_root_name() {
+        let before = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let expanded = expand_output_path("scan-{date}.json", None);
+        let after = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert!(
+            expanded == format!("scan-{}.json", before)
+                || expanded == format!("scan-{}.json", after),
+            "{}",
+            expanded
+        );
+
+        let expanded = expand_output_path("{root}-{datetime}.csv", Some("/mnt/photos/"));
+        assert!(expanded.starts_with("photos-"), "{}", expanded);
+        // e.g. 2024-06-01T031500
+        assert_eq!(expanded.len(), "photos-".len() + 17 + ".csv".len());
+    }
+
+    #[test]
+    fn summary_reports_unique_count_and_total_size_of_a_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.jpg"), vec![1; 1024 * 1024]).unwrap();
+        std::fs::write(dir.path().join("b.jpg"), vec![2; 512 * 1024]).unwrap();
+        std::fs::write(dir.path().join("copy-of-b.jpg"), vec![2; 512 * 1024]).unwrap();
+
+        let mut scan_options = crate::scanner::ScanOptions::default();
+        scan_options.process.progress = crate::progress::ProgressMode::None;
+        let root = dir.path().to_str().unwrap().to_string();
+        let report = crate::scanner::scan_folder(&[root], &scan_options, None);
+
+        let summary = generate_summary(&report.stats);
+        assert_eq!(
+            summary.lines().next().unwrap(),
+            "📊 3 files, 2 unique, 1.5 MB total, 512.0 KB saved by dedup"
+        );
+        assert!(
+            summary.contains("   image: 2 files, 1.5 MB\n"),
+            "{}",
+            summary
+        );
+    }
+
+    #[test]
+    fn sizes_are_formatted_with_binary_units() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+
+    #[test]
+    fn toml_output_reparses_with_every_file() {
+        let files = FileMeta::test_files(3);
+        let parsed: toml::Table = toml::from_str(&generate_toml(&files)).unwrap();
+
+        let scan = parsed["scan"].as_table().unwrap();
+        assert_eq!(scan["total_files"].as_integer(), Some(3));
+        assert_eq!(scan["total_size"].as_integer(), Some(3 * 1234));
+        let entries = parsed["files"].as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0]["size"].as_integer(), Some(1234));
+
+        let reparsed: Vec<FileMeta> = parsed["files"].clone().try_into().unwrap();
+        let names: Vec<&str> = reparsed.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(names, ["/photos/0.jpg", "/photos/1.jpg", "/photos/2.jpg"]);
+    }
+
+    #[test]
+    fn yaml_output_parses_back_with_matching_hashes() {
+        let mut files = FileMeta::test_files(2);
+        // Characters YAML would otherwise read as a mapping, comment or anchor
+        files.push(FileMeta::for_test("/photos/a: b #1 &x.jpg"));
+        files.push(FileMeta::for_test("- 'quoted' \"too\".jpg"));
+
+        let parsed: Vec<FileMeta> = serde_yaml::from_str(&generate_yaml(&files)).unwrap();
+        let round_trip: Vec<(&str, &str)> = parsed
+            .iter()
+            .map(|f| (f.filename.as_str(), f.hash.as_str()))
+            .collect();
+        let expected: Vec<(&str, &str)> = files
+            .iter()
+            .map(|f| (f.filename.as_str(), f.hash.as_str()))
+            .collect();
+        assert_eq!(round_trip, expected);
+    }
+
+    #[test]
+    fn output_fields_project_csv_columns_in_the_given_order() {
+        let files = FileMeta::test_files(2);
+        let projected = OutputOptions {
+            fields: vec!["hash".to_string(), "size".to_string()],
+            ..options(OutputFormat::Csv)
+        };
+        let csv = render(&files, &projected).unwrap();
+        let expected = format!(
+            "hash,size\n{},1234\n{},1234\n",
+            files[0].hash, files[1].hash
+        );
+        assert_eq!(csv, expected);
+    }
+
+    #[test]
+    fn unknown_output_fields_are_rejected() {
+        assert_eq!(parse_field(" size "), Ok("size".to_string()));
+        let error = parse_field("checksum").unwrap_err();
+        assert!(error.contains("unknown field \"checksum\""), "{}", error);
+    }
+
+    #[test]
+    fn compressed_output_files_decompress_to_the_same_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = FileMeta::test_files(50);
+        for compression in [OutputCompression::Gzip, OutputCompression::Zstd] {
+            let path =
+                compression.apply_extension(&dir.path().join("scan.json").display().to_string());
+            let options = OutputOptions {
+                output_file: Some(path.clone()),
+                compression,
+                ..options(OutputFormat::Json)
+            };
+            write_files(&files, &options);
+
+            let file = File::open(&path).unwrap();
+            let parsed: Vec<FileMeta> = match compression {
+                OutputCompression::Gzip => {
+                    serde_json::from_reader(flate2::read::GzDecoder::new(file)).unwrap()
+                }
+                _ => serde_json::from_reader(zstd::Decoder::new(file).unwrap()).unwrap(),
+            };
+            assert_eq!(parsed.len(), files.len());
+            assert!(parsed.iter().zip(&files).all(|(a, b)| a.hash == b.hash));
+        }
+    }
+
+    #[test]
+    fn compressed_extension_is_added_once() {
+        assert_eq!(
+            OutputCompression::Gzip.apply_extension("scan.json"),
+            "scan.json.gz"
+        );
+        assert_eq!(
+            OutputCompression::Gzip.apply_extension("scan.json.gz"),
+            "scan.json.gz"
+        );
+        assert_eq!(
+            OutputCompression::Zstd.apply_extension("scan.csv"),
+            "scan.csv.zst"
+        );
+        assert_eq!(
+            OutputCompression::None.apply_extension("scan.csv"),
+            "scan.csv"
+        );
+    }
+
+    #[test]
+    fn tree_lists_files_indented_under_their_directory() {
+        let in_folder = |folder: &str, name: &str| FileMeta {
+            folder: folder.to_string(),
+            ..FileMeta::for_test(&format!("{}/{}", folder, name))
+        };
+        let files = vec![
+            in_folder("/photos/trip", "b.jpg"),
+            in_folder("/photos", "a.jpg"),
+            in_folder("/photos/trip", "c.jpg"),
+        ];
+        let plain = ConsoleStyle {
+            color: false,
+            emoji: false,
+        };
+
+        let tree = generate_tree(&files, plain);
+        let line = |file: &FileMeta, name: &str| {
+            format!("   {} (1234 bytes, hash: {}, type: image)", name, file.hash)
+        };
+        let expected = [
+            "/photos (1 files, 1.2 KB)".to_string(),
+            line(&files[1], "a.jpg"),
+            "/photos/trip (2 files, 2.4 KB)".to_string(),
+            line(&files[0], "b.jpg"),
+            line(&files[2], "c.jpg"),
+        ];
+        assert_eq!(tree.lines().collect::<Vec<_>>(), expected);
+    }
+}
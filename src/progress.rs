@@ -0,0 +1,144 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use serde::Serialize;
+
+const PROGRESS_BAR_TEMPLATE: &str =
+    "[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({file_rate}, {throughput}, ETA {eta}) - {msg}";
+const PROGRESS_CHARS: &str = "##-";
+const JSON_EMIT_INTERVAL: u64 = 100; // Emit a JSON progress line every 100 files
+
+/// How scan progress is reported
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// Interactive progress bar
+    #[default]
+    Bar,
+    /// Newline-delimited JSON objects on stderr
+    Json,
+    /// No progress output
+    None,
+}
+
+/// A single line of `--progress json` output
+#[derive(Serialize)]
+struct ProgressEvent {
+    processed: u64,
+    total: u64,
+    bytes_processed: u64,
+    elapsed_ms: u64,
+}
+
+/// Progress reporter shared across scanner threads
+pub struct Progress {
+    mode: ProgressMode,
+    bar: Option<ProgressBar>,
+    total: u64,
+    processed: AtomicU64,
+    /// Bytes of processed files; shared with the bar's throughput column
+    bytes: Arc<AtomicU64>,
+    start_time: Instant,
+}
+
+impl Progress {
+    pub fn new(mode: ProgressMode, total: u64) -> Self {
+        let bytes = Arc::new(AtomicU64::new(0));
+        let bar = (mode == ProgressMode::Bar).then(|| {
+            let bar = ProgressBar::new(total);
+            let bar_bytes = Arc::clone(&bytes);
+            bar.set_style(
+                ProgressStyle::with_template(PROGRESS_BAR_TEMPLATE)
+                    .unwrap()
+                    .with_key(
+                        "file_rate",
+                        |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+                            let _ = write!(w, "{:.1} files/s", state.per_sec());
+                        },
+                    )
+                    .with_key(
+                        "throughput",
+                        move |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+                            let secs = state.elapsed().as_secs_f64().max(0.001);
+                            let mb = bar_bytes.load(Ordering::Relaxed) as f64 / (1024.0 * 1024.0);
+                            let _ = write!(w, "{:.1} MB/s", mb / secs);
+                        },
+                    )
+                    .progress_chars(PROGRESS_CHARS),
+            );
+            bar
+        });
+
+        Self {
+            mode,
+            bar,
+            total,
+            processed: AtomicU64::new(0),
+            bytes,
+            start_time: Instant::now(),
+        }
+    }
+
+    /// Total bytes of the files processed so far
+    pub fn bytes_processed(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Records `count` newly processed files totalling `bytes`
+    pub fn inc(&self, count: u64, bytes: u64) {
+        if count == 0 {
+            return;
+        }
+
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        let previous = self.processed.fetch_add(count, Ordering::Relaxed);
+        match self.mode {
+            ProgressMode::Bar => {
+                if let Some(bar) = &self.bar {
+                    bar.inc(count);
+                }
+            }
+            ProgressMode::Json => {
+                // Only emit when we cross an interval boundary
+                let current = previous + count;
+                if current / JSON_EMIT_INTERVAL != previous / JSON_EMIT_INTERVAL {
+                    self.emit_json(current);
+                }
+            }
+            ProgressMode::None => {}
+        }
+    }
+
+    /// Updates the bar message (ignored outside bar mode)
+    pub fn set_message(&self, message: String) {
+        if let Some(bar) = &self.bar {
+            bar.set_message(message);
+        }
+    }
+
+    /// Finishes reporting, always emitting a final line in JSON mode
+    pub fn finish(&self, message: &'static str) {
+        match self.mode {
+            ProgressMode::Bar => {
+                if let Some(bar) = &self.bar {
+                    bar.finish_with_message(message);
+                }
+            }
+            ProgressMode::Json => self.emit_json(self.processed.load(Ordering::Relaxed)),
+            ProgressMode::None => {}
+        }
+    }
+
+    fn emit_json(&self, processed: u64) {
+        let event = ProgressEvent {
+            processed,
+            total: self.total,
+            bytes_processed: self.bytes_processed(),
+            elapsed_ms: self.start_time.elapsed().as_millis() as u64,
+        };
+        if let Ok(line) = serde_json::to_string(&event) {
+            eprintln!("{}", line);
+        }
+    }
+}
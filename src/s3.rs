@@ -0,0 +1,156 @@
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::primitives::ByteStream;
+
+/// Bucket and connection settings for `--s3-bucket` uploads
+#[derive(Clone, Debug)]
+pub struct S3Target {
+    pub bucket: String,
+    pub key: String,
+    /// Custom endpoint for S3-compatible stores such as MinIO; `None` uses AWS
+    pub endpoint: Option<String>,
+    pub region: String,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+}
+
+/// Writes `body` to `target` as a single object
+pub fn put_object(
+    target: &S3Target,
+    body: Vec<u8>,
+    content_type: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (Some(access_key_id), Some(secret_access_key)) =
+        (&target.access_key_id, &target.secret_access_key)
+    else {
+        return Err(
+            "S3 credentials missing: pass --s3-access-key-id and --s3-secret-access-key \
+             or set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY"
+                .into(),
+        );
+    };
+
+    let mut config = aws_sdk_s3::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new(target.region.clone()))
+        .credentials_provider(Credentials::new(
+            access_key_id,
+            secret_access_key,
+            None,
+            None,
+            "biebie",
+        ));
+    if let Some(endpoint) = &target.endpoint {
+        // MinIO and most other S3-compatible stores only support path-style URLs
+        config = config.endpoint_url(endpoint).force_path_style(true);
+    }
+    let client = aws_sdk_s3::Client::from_conf(config.build());
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime
+        .block_on(
+            client
+                .put_object()
+                .bucket(&target.bucket)
+                .key(&target.key)
+                .content_type(content_type)
+                .body(ByteStream::from(body))
+                .send(),
+        )
+        .map_err(|e| DisplayErrorContext(e).to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    /// A PUT as the mock endpoint saw it
+    struct Received {
+        request_line: String,
+        content_type: String,
+        body: Vec<u8>,
+    }
+
+    /// Accepts one request on localhost, answers 200, and yields what was sent
+    fn mock_endpoint() -> (String, JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let (mut length, mut content_type) = (0, String::new());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                if let Some((name, value)) = line.trim_end().split_once(':') {
+                    match name.to_ascii_lowercase().as_str() {
+                        "content-length" => length = value.trim().parse().unwrap(),
+                        "content-type" => content_type = value.trim().to_string(),
+                        _ => {}
+                    }
+                }
+                line.clear();
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            Received {
+                request_line: request_line.trim_end().to_string(),
+                content_type,
+                body,
+            }
+        });
+        (endpoint, handle)
+    }
+
+    fn target(endpoint: Option<String>) -> S3Target {
+        S3Target {
+            bucket: "inventory".to_string(),
+            key: "scans/2024-06-01.json".to_string(),
+            endpoint,
+            region: "us-east-1".to_string(),
+            access_key_id: Some("minio".to_string()),
+            secret_access_key: Some("minio-secret".to_string()),
+        }
+    }
+
+    #[test]
+    fn object_is_put_under_the_bucket_and_key() {
+        let (endpoint, request) = mock_endpoint();
+        let body = br#"[{"filename":"/photos/a.jpg"}]"#.to_vec();
+        put_object(&target(Some(endpoint)), body.clone(), "application/json").unwrap();
+
+        let received = request.join().unwrap();
+        // Path-style addressing puts the bucket in the path, as MinIO expects
+        assert!(
+            received
+                .request_line
+                .starts_with("PUT /inventory/scans/2024-06-01.json"),
+            "{}",
+            received.request_line
+        );
+        assert_eq!(received.content_type, "application/json");
+        assert_eq!(received.body, body);
+    }
+
+    #[test]
+    fn missing_credentials_are_reported_before_connecting() {
+        let target = S3Target {
+            access_key_id: None,
+            ..target(Some("http://127.0.0.1:9".to_string()))
+        };
+        let error = put_object(&target, Vec::new(), "application/json").unwrap_err();
+        assert!(
+            error.to_string().contains("S3 credentials missing"),
+            "{}",
+            error
+        );
+    }
+}
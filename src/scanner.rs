@@ -1,145 +1,1281 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 
-use blake3::hash;
-use dashmap::DashMap;
-use indicatif::{ProgressBar, ProgressStyle};
+use serde::{Serialize, Serializer};
+
+use dashmap::{DashMap, DashSet};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use jwalk::WalkDir;
+use log::{debug, info, warn};
 use memmap2::Mmap;
 use mime_guess::from_path;
 use rayon::{prelude::*, ThreadPoolBuilder};
-use walkdir::WalkDir;
 
-use crate::hash::compute_sample_hash;
-use crate::uploader::FileMeta;
+use crate::archive;
+use crate::checkpoint::Checkpoint;
+use crate::hash::{
+    auto_sample_points, chunk_hashes, compute_sample_hash, HashAlgorithm, HashEncoding,
+    DEFAULT_SAMPLE_SIZE,
+};
+use crate::media::{self, ExtractOptions};
+use crate::progress::{Progress, ProgressMode};
+use crate::status;
+use crate::uploader::{encode_path_bytes, FileMeta};
+
+pub const LARGE_FILE_THRESHOLD: u64 = 10 * 1024 * 1024; // 10MB - reduced for better memory mapping usage
+pub const VERY_LARGE_FILE_THRESHOLD: u64 = 100 * 1024 * 1024; // 100MB
+const WORKLOAD_SAMPLE_FILES: usize = 16; // Files timed by `--workload auto`
+const WORKLOAD_SAMPLE_BYTES: usize = 256 * 1024; // Bytes read from each sampled file
+
+/// A file or directory that could not be scanned, with the reason
+pub type ScanError = (PathBuf, String);
+
+/// Directory entry yielded by the parallel walker
+type WalkEntry = jwalk::DirEntry<((), ())>;
+
+/// Position of a processed file, `(batch index, index within the batch, archive
+/// entry)` where entry 0 is the file itself; unique even when the same path is
+/// discovered more than once
+type ResultKey = (usize, usize, usize);
+
+/// Identity of a visited directory, used to break symlink cycles
+#[cfg(unix)]
+type DirKey = (u64, u64); // (device, inode)
+#[cfg(not(unix))]
+type DirKey = PathBuf; // canonical path
+
+/// Shared state for the processing stage of a single scan
+struct ScanContext<'a> {
+    progress: &'a Progress,
+    /// Path of the first file kept for each hash, reopened to confirm sampled matches
+    seen_hashes: Arc<DashMap<String, PathBuf>>,
+    /// Paths of skipped duplicates by hash, collected in `group` mode
+    duplicate_paths: DashMap<String, Vec<String>>,
+    errors: &'a DashMap<PathBuf, String>,
+    options: &'a ScanOptions,
+    checkpoint: Option<&'a Checkpoint>,
+    /// Unique files kept so far, checked against `limit`
+    collected: AtomicUsize,
+    /// Files handed to hashing so far, checked against `max_files`
+    processed: AtomicUsize,
+    /// Files dropped or grouped because their hash was already seen
+    duplicates_seen: AtomicUsize,
+    /// Combined size of the files counted in `duplicates_seen`
+    duplicate_bytes: AtomicU64,
+}
+
+impl ScanContext<'_> {
+    /// Whether enough files were collected to stop hashing new ones
+    fn limit_reached(&self) -> bool {
+        self.options
+            .process
+            .limit
+            .is_some_and(|limit| self.collected.load(Ordering::Relaxed) >= limit)
+    }
+
+    /// Whether `max_files` files were already handed to hashing
+    fn max_files_reached(&self) -> bool {
+        self.options
+            .process
+            .max_files
+            .is_some_and(|max| self.processed.load(Ordering::Relaxed) >= max)
+    }
+
+    /// Reserves one of the `max_files` slots; `false` once all are taken
+    fn claim_file(&self) -> bool {
+        match self.options.process.max_files {
+            Some(max) => self.processed.fetch_add(1, Ordering::Relaxed) < max,
+            None => true,
+        }
+    }
+}
+
+/// Everything a scan produced: the files, summary numbers, and unreadable paths
+#[derive(Serialize)]
+pub struct ScanReport {
+    pub files: Vec<FileMeta>,
+    pub stats: ScanStats,
+    #[serde(serialize_with = "serialize_errors")]
+    pub errors: Vec<ScanError>,
+    /// Directories whose files were all filtered out (only with `WalkOptions::report_skipped`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_dirs: Vec<SkippedDir>,
+}
+
+/// A directory that yielded no processable files, with why its files were passed over
+#[derive(Clone, Debug, Serialize)]
+pub struct SkippedDir {
+    pub path: String,
+    pub skipped: SkipCounts,
+}
+
+/// Why discovery passed over a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SkipReason {
+    Hidden,
+    Excluded,
+    NotIncluded,
+    BelowMinSize,
+    AboveMaxSize,
+}
+
+/// Files passed over in one directory, by reason
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct SkipCounts {
+    /// Names starting with `.`
+    pub hidden: usize,
+    /// Matched by `--exclude`, `--exclude-from`, or a `.gitignore`
+    pub excluded: usize,
+    /// Extension not listed in `--include`
+    pub not_included: usize,
+    pub below_min_size: usize,
+    pub above_max_size: usize,
+    /// Accepted, but the directory held fewer than `--min-files` of them
+    pub below_min_files: usize,
+}
+
+impl SkipCounts {
+    fn add(&mut self, reason: SkipReason) {
+        let count = match reason {
+            SkipReason::Hidden => &mut self.hidden,
+            SkipReason::Excluded => &mut self.excluded,
+            SkipReason::NotIncluded => &mut self.not_included,
+            SkipReason::BelowMinSize => &mut self.below_min_size,
+            SkipReason::AboveMaxSize => &mut self.above_max_size,
+        };
+        *count += 1;
+    }
+
+    pub fn total(&self) -> usize {
+        self.hidden
+            + self.excluded
+            + self.not_included
+            + self.below_min_size
+            + self.above_max_size
+            + self.below_min_files
+    }
+
+    /// Non-zero reasons, e.g. `12 below --min-size, 1 hidden`
+    pub fn describe(&self) -> String {
+        [
+            (self.below_min_size, "below --min-size"),
+            (self.above_max_size, "above --max-size"),
+            (
+                self.below_min_files,
+                "in a directory with fewer than --min-files files",
+            ),
+            (self.not_included, "not in --include"),
+            (self.excluded, "excluded"),
+            (self.hidden, "hidden"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, reason)| format!("{} {}", count, reason))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+impl ScanReport {
+    /// Drops the stats and errors, keeping only the scanned files
+    pub fn into_files(self) -> Vec<FileMeta> {
+        self.files
+    }
+}
+
+/// Summary numbers for a finished scan
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ScanStats {
+    /// Files accepted during discovery
+    pub files_found: usize,
+    /// Distinct hashes among the returned files
+    pub unique_files: usize,
+    /// Files whose hash matched one seen earlier in the scan
+    pub duplicate_files: usize,
+    /// Bytes that deduplication saves: the combined size of `duplicate_files`
+    pub duplicate_bytes: u64,
+    /// Combined size of the returned files
+    pub total_bytes: u64,
+    /// Count and combined size of the returned files per `filetype`
+    pub by_filetype: BTreeMap<String, FiletypeTotals>,
+    /// When the scan began
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub elapsed_secs: f64,
+    /// Hashing threads used; 0 when nothing was hashed
+    pub threads: usize,
+    /// Fingerprint of the result set (only with `--manifest-hash`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest_hash: Option<String>,
+}
+
+/// Number and combined size of the files of one type
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FiletypeTotals {
+    pub count: usize,
+    pub total_size: u64,
+}
+
+/// Groups files by `filetype` (image, video, audio, document, archive, other)
+pub fn filetype_breakdown(files: &[FileMeta]) -> BTreeMap<String, FiletypeTotals> {
+    let mut breakdown: BTreeMap<String, FiletypeTotals> = BTreeMap::new();
+    for file in files {
+        let totals = breakdown.entry(file.filetype.clone()).or_default();
+        totals.count += 1;
+        totals.total_size += file.size;
+    }
+    breakdown
+}
 
-const PROGRESS_BAR_TEMPLATE: &str = "[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} - {msg}";
-const PROGRESS_CHARS: &str = "##-";
-const LARGE_FILE_THRESHOLD: u64 = 10 * 1024 * 1024; // 10MB - reduced for better memory mapping usage
-const VERY_LARGE_FILE_THRESHOLD: u64 = 100 * 1024 * 1024; // 100MB
+/// Writes scan errors as `{path, error}` objects instead of bare pairs
+fn serialize_errors<S: Serializer>(errors: &[ScanError], serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Entry<'a> {
+        path: &'a std::path::Path,
+        error: &'a str,
+    }
+    serializer.collect_seq(errors.iter().map(|(path, error)| Entry { path, error }))
+}
 
 /// Directory processing unit for hierarchical scanning
 #[derive(Debug)]
 struct DirBatch {
     path: String,
-    files: Vec<walkdir::DirEntry>,
+    files: Vec<DiscoveredFile>,
     depth: usize,
+    /// Files filtered out of this directory, tracked with `WalkOptions::report_skipped`
+    skipped: SkipCounts,
+}
+
+/// A file accepted during discovery, with the metadata read while filtering it
+#[derive(Debug)]
+struct DiscoveredFile {
+    path: PathBuf,
+    /// Stat result reused by processing; errors surface when the file is hashed
+    metadata: std::io::Result<fs::Metadata>,
+    /// Position in the depth-first, name-sorted walk; only tracked for `--order discovery`
+    discovered: usize,
+}
+
+impl DiscoveredFile {
+    fn metadata(&self) -> std::io::Result<&fs::Metadata> {
+        self.metadata
+            .as_ref()
+            .map_err(|e| std::io::Error::new(e.kind(), e.to_string()))
+    }
+}
+
+/// Controls how file contents are hashed
+#[derive(Clone, Copy, Debug)]
+pub struct HashStrategy {
+    pub algorithm: HashAlgorithm,
+    /// Always hash the whole file instead of sampling very large ones
+    pub full_hash: bool,
+    /// Files above this size are hashed via memory mapping
+    pub large_threshold: u64,
+    /// Files above this size are hashed by sampling
+    pub sample_threshold: u64,
+    /// Size of each sampled region in bytes
+    pub sample_size: usize,
+    /// Number of sampled regions; `None` picks a count from the file size
+    pub sample_points: Option<usize>,
+    /// Hash files that match on a sampled hash in full before treating them
+    /// as duplicates
+    pub rehash_on_size_match: bool,
+    /// Also record content-defined chunk hashes for similarity comparison
+    pub chunk_hashes: bool,
+    /// Text encoding of the digests in `FileMeta.hash`
+    pub encoding: HashEncoding,
+    /// Never read file contents to hash them, leaving `hash` empty; every file
+    /// is then kept, as duplicates cannot be told apart
+    pub no_hash: bool,
+}
+
+impl HashStrategy {
+    /// Whether a file of this size is hashed by sampling rather than in full
+    pub fn samples(&self, file_size: u64) -> bool {
+        file_size > self.sample_threshold && !self.full_hash
+    }
+
+    /// Names how a file of this size is hashed: checkpointed hashes are only
+    /// reusable under the same algorithm, encoding, and sampling
+    pub fn checkpoint_key(&self, file_size: u64) -> String {
+        let mut key = self.algorithm.name().to_string();
+        if self.encoding != HashEncoding::Hex {
+            key = format!("{}-{}", key, self.encoding.name());
+        }
+        if self.samples(file_size) {
+            let sample_points = self
+                .sample_points
+                .unwrap_or_else(|| auto_sample_points(file_size));
+            key = format!("{}-sampled-{}x{}", key, sample_points, self.sample_size);
+        }
+        key
+    }
+}
+
+impl Default for HashStrategy {
+    fn default() -> Self {
+        Self {
+            algorithm: HashAlgorithm::default(),
+            full_hash: false,
+            large_threshold: LARGE_FILE_THRESHOLD,
+            sample_threshold: VERY_LARGE_FILE_THRESHOLD,
+            sample_size: DEFAULT_SAMPLE_SIZE,
+            sample_points: None,
+            rehash_on_size_match: false,
+            chunk_hashes: false,
+            no_hash: false,
+            encoding: HashEncoding::default(),
+        }
+    }
+}
+
+/// Controls how the directory tree is walked
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WalkOptions {
+    /// Follow symbolic links while walking
+    pub follow_symlinks: bool,
+    /// Maximum depth below the scan root; files directly in the root are depth 1
+    pub max_depth: Option<usize>,
+    /// Skip paths ignored by `.gitignore` files met along the way, or above the root
+    pub respect_gitignore: bool,
+    /// Drop directories with fewer accepted files than this, counting only the
+    /// files directly inside each one; subdirectories are judged on their own
+    pub min_files: usize,
+    /// Collect directories whose files were all filtered out into `ScanReport::skipped_dirs`
+    pub report_skipped: bool,
 }
 
-/// Scans a folder recursively and returns metadata for all unique files
-pub fn scan_folder(folder: &str) -> Vec<FileMeta> {
+/// What to do with files whose hash was already seen
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateMode {
+    /// Emit only the first file for each hash
+    #[default]
+    Skip,
+    /// Emit every file, duplicates included
+    KeepAll,
+    /// Emit unique files with the paths of their duplicates attached
+    Group,
+}
+
+/// Controls the processing stage after discovery
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcessOptions {
+    pub progress: ProgressMode,
+    /// Stop after discovery without hashing
+    pub dry_run: bool,
+    pub duplicates: DuplicateMode,
+    pub extract: ExtractOptions,
+    /// Stop once this many unique files were collected
+    pub limit: Option<usize>,
+    /// Stop once this many files were hashed, duplicates and unreadable files included
+    pub max_files: Option<usize>,
+    /// Also emit one entry per file inside zip and tar archives
+    pub expand_archives: bool,
+    /// Hashing thread count; `None` uses the heuristic
+    pub threads: Option<usize>,
+    /// Drives the thread count heuristic when `threads` is not set
+    pub workload: Workload,
+    pub order: ResultOrder,
+}
+
+/// Order of the files returned by a scan
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResultOrder {
+    /// Sorted by filename
+    #[default]
+    Name,
+    /// The order the walk found files in: depth-first, by name within each directory
+    Discovery,
+    /// Whatever order the worker threads finished in; skips sorting entirely
+    #[value(name = "none")]
+    Unordered,
+}
+
+/// Whether hashing is expected to wait on storage or on the CPU
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Workload {
+    /// Oversubscribe threads to hide storage latency
+    #[default]
+    Io,
+    /// One thread per physical core
+    Cpu,
+    /// Time reading vs hashing a few files and pick one
+    Auto,
+}
+
+/// Every setting that shapes a scan, built once and shared by each stage
+#[derive(Clone, Default)]
+pub struct ScanOptions {
+    pub filter: FileFilter,
+    pub hash: HashStrategy,
+    pub walk: WalkOptions,
+    pub process: ProcessOptions,
+}
+
+/// File selection rules applied during discovery
+#[derive(Clone)]
+pub struct FileFilter {
+    /// Lowercase extensions without the leading dot; empty means all
+    includes: Vec<String>,
+    excludes: GlobSet,
+    /// Patterns from `--exclude-from`, matched relative to the scan root
+    ignore: Option<Gitignore>,
+    /// Minimum file size in bytes; 0 disables the check
+    min_size: u64,
+    /// Maximum file size in bytes; `None` means unlimited
+    max_size: Option<u64>,
+}
+
+impl FileFilter {
+    pub fn new(
+        includes: &[String],
+        excludes: GlobSet,
+        min_size: u64,
+        max_size: Option<u64>,
+    ) -> Self {
+        let includes = includes
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect();
+        Self {
+            includes,
+            excludes,
+            ignore: None,
+            min_size,
+            max_size,
+        }
+    }
+
+    /// Adds gitignore-style patterns on top of the exclude globs
+    pub fn with_ignore(mut self, ignore: Gitignore) -> Self {
+        self.ignore = Some(ignore);
+        self
+    }
+
+    fn has_excludes(&self) -> bool {
+        !self.excludes.is_empty() || self.ignore.is_some()
+    }
+
+    /// Checks a path relative to the scan root against the exclude globs and
+    /// the ignore file, where a `!` pattern can re-include what an earlier
+    /// pattern in the file ignored
+    fn excludes_path(&self, relative: &Path, is_dir: bool) -> bool {
+        self.excludes.is_match(relative)
+            || self
+                .ignore
+                .as_ref()
+                .is_some_and(|ignore| ignore.matched(relative, is_dir).is_ignore())
+    }
+
+    /// Like [`Self::excludes_path`] for a listed file, which was not reached
+    /// through its directories, so their patterns are checked as well
+    fn excludes_listed(&self, path: &Path) -> bool {
+        if self.excludes.is_match(path) {
+            return true;
+        }
+        let Some(ignore) = &self.ignore else {
+            return false;
+        };
+        // Listed paths have no scan root; anchored patterns match from the top
+        let relative: PathBuf = path
+            .components()
+            .filter(|component| matches!(component, std::path::Component::Normal(_)))
+            .collect();
+        ignore
+            .matched_path_or_any_parents(&relative, false)
+            .is_ignore()
+    }
+
+    /// Checks the file extension against the `--include` whitelist
+    fn is_included(&self, path: &std::path::Path) -> bool {
+        if self.includes.is_empty() {
+            return true;
+        }
+
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| {
+                self.includes
+                    .iter()
+                    .any(|inc| inc.eq_ignore_ascii_case(ext))
+            })
+            .unwrap_or(false)
+    }
+
+    /// Checks a file size against `--min-size` and `--max-size`
+    pub fn size_in_range(&self, size: u64) -> bool {
+        size >= self.min_size && self.max_size.is_none_or(|max| size <= max)
+    }
+}
+
+impl Default for FileFilter {
+    fn default() -> Self {
+        Self::new(&[], GlobSet::empty(), 0, None)
+    }
+}
+
+/// Compiles `--exclude` patterns into a single glob set
+///
+/// A trailing slash marks a directory pattern (`cache/`), which matches a
+/// directory of that name at any depth along with everything beneath it.
+pub fn build_exclude_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        if let Some(dir_pattern) = pattern.strip_suffix('/') {
+            let dir_pattern = if dir_pattern.starts_with("**/") {
+                dir_pattern.to_string()
+            } else {
+                format!("**/{}", dir_pattern.trim_start_matches('/'))
+            };
+            builder.add(Glob::new(&dir_pattern)?);
+            builder.add(Glob::new(&format!("{}/**", dir_pattern))?);
+        } else {
+            builder.add(Glob::new(pattern)?);
+        }
+    }
+    builder.build()
+}
+
+/// Reads a gitignore-syntax file for `--exclude-from`; its patterns apply
+/// relative to each scan root rather than to the file's own directory
+pub fn build_ignore_file(path: &Path) -> Result<Gitignore, ignore::Error> {
+    let mut builder = GitignoreBuilder::new("");
+    if let Some(e) = builder.add(path) {
+        return Err(e);
+    }
+    builder.build()
+}
+
+/// Walks the folders with the same filtering as a scan and returns file paths
+/// without hashing anything
+pub fn discover_files(folders: &[String], options: &ScanOptions) -> (Vec<PathBuf>, Vec<ScanError>) {
+    let errors = DashMap::new();
+    let mut paths: Vec<PathBuf> = folders
+        .iter()
+        .flat_map(|folder| discover_nested_structure(folder, options, &errors))
+        .flat_map(|batch| batch.files.into_iter().map(|file| file.path))
+        .collect();
+    paths.sort();
+    (paths, collect_errors(errors))
+}
+
+/// Scans one or more folders recursively and reports metadata for all unique files
+///
+/// Duplicates are detected across all roots, not just within each one.
+/// Paths that could not be read are reported alongside the results.
+pub fn scan_folder(
+    folders: &[String],
+    options: &ScanOptions,
+    checkpoint: Option<&Checkpoint>,
+) -> ScanReport {
     print_system_info();
 
     let start_time = Instant::now();
+    let started_at = chrono::Utc::now();
 
-    // Parallel processing with Rayon
-    let thread_count = determine_optimal_thread_count();
-    let custom_pool = ThreadPoolBuilder::new()
-        .num_threads(thread_count)
-        .thread_name(|i| format!("scanner-{}", i))
-        .build()
-        .expect("Failed to create custom thread pool");
+    let errors = DashMap::new();
 
-    println!("Created custom ThreadPool with {} threads", thread_count);
+    // Discover and organize files by directory hierarchy, merging all roots
+    let mut dir_batches: Vec<DirBatch> = Vec::new();
+    for folder in folders {
+        // Each walk numbers its files from zero, so later roots continue after earlier ones
+        let offset = dir_batches
+            .iter()
+            .flat_map(|batch| &batch.files)
+            .map(|file| file.discovered + 1)
+            .max()
+            .unwrap_or_default();
+        let mut batches = discover_nested_structure(folder, options, &errors);
+        for file in batches.iter_mut().flat_map(|batch| &mut batch.files) {
+            file.discovered += offset;
+        }
+        dir_batches.extend(batches);
+    }
+
+    if dir_batches.iter().all(|batch| batch.files.is_empty()) {
+        status!("No files found in folder: {}", folders.join(", "));
+    }
+    process_batches(
+        dir_batches,
+        errors,
+        options,
+        checkpoint,
+        start_time,
+        started_at,
+    )
+}
+
+/// Scans an explicit list of files instead of walking a directory tree
+///
+/// The list goes through the same filters as a walk. Paths that do not exist
+/// or are not regular files are reported as errors.
+pub fn scan_files(
+    paths: &[PathBuf],
+    options: &ScanOptions,
+    checkpoint: Option<&Checkpoint>,
+) -> ScanReport {
+    print_system_info();
+
+    let start_time = Instant::now();
+    let started_at = chrono::Utc::now();
+
+    let errors = DashMap::new();
+    let dir_batches = group_listed_files(paths, &options.filter, &errors);
+
+    if dir_batches.is_empty() {
+        status!("No files found in file list");
+    }
+    process_batches(
+        dir_batches,
+        errors,
+        options,
+        checkpoint,
+        start_time,
+        started_at,
+    )
+}
 
-    // Discover and organize files by directory hierarchy
-    let dir_batches = discover_nested_structure(folder);
+/// Hashes discovered batches and assembles the report shared by every scan source
+fn process_batches(
+    mut dir_batches: Vec<DirBatch>,
+    errors: DashMap<PathBuf, String>,
+    options: &ScanOptions,
+    checkpoint: Option<&Checkpoint>,
+    start_time: Instant,
+    started_at: chrono::DateTime<chrono::Utc>,
+) -> ScanReport {
+    dir_batches.sort_by_key(|batch| std::cmp::Reverse(batch.depth));
+
+    let mut skipped_dirs: Vec<SkippedDir> = Vec::new();
+    dir_batches.retain(|batch| {
+        if !batch.files.is_empty() {
+            return true;
+        }
+        skipped_dirs.push(SkippedDir {
+            path: batch.path.clone(),
+            skipped: batch.skipped,
+        });
+        false
+    });
+    skipped_dirs.sort_by(|a, b| a.path.cmp(&b.path));
 
     if dir_batches.is_empty() {
-        println!("No files found in folder: {}", folder);
-        return Vec::new();
+        return ScanReport {
+            files: Vec::new(),
+            stats: ScanStats {
+                started_at,
+                elapsed_secs: start_time.elapsed().as_secs_f64(),
+                ..ScanStats::default()
+            },
+            errors: collect_errors(errors),
+            skipped_dirs,
+        };
     }
 
     let total_files: usize = dir_batches.iter().map(|batch| batch.files.len()).sum();
-    println!(
+    status!(
         "Found {} files in {} directories to process",
         total_files,
         dir_batches.len()
     );
 
-    let progress_bar = ProgressBar::new(total_files as u64);
-    progress_bar.set_style(
-        ProgressStyle::with_template(PROGRESS_BAR_TEMPLATE)
-            .unwrap()
-            .progress_chars(PROGRESS_CHARS),
-    );
+    // Dry run stops after discovery, before any file is opened for hashing
+    let process_options = options.process;
+    if process_options.dry_run {
+        let files = plan_dry_run(&dir_batches, &errors);
+        return ScanReport {
+            stats: ScanStats {
+                files_found: total_files,
+                total_bytes: files.iter().map(|file| file.size).sum(),
+                by_filetype: filetype_breakdown(&files),
+                started_at,
+                elapsed_secs: start_time.elapsed().as_secs_f64(),
+                ..ScanStats::default()
+            },
+            files,
+            errors: collect_errors(errors),
+            skipped_dirs,
+        };
+    }
+
+    // Parallel processing with Rayon, sized after discovery so `auto` can sample files
+    let thread_count = process_options.threads.unwrap_or_else(|| {
+        let workload = match process_options.workload {
+            // Without hashing there is only metadata I/O left, and sampling would read files
+            Workload::Auto if options.hash.no_hash => Workload::Io,
+            Workload::Auto => sample_workload(&dir_batches, options.hash.algorithm),
+            workload => workload,
+        };
+        determine_optimal_thread_count(workload, num_cpus::get(), num_cpus::get_physical())
+    });
+    let custom_pool = build_thread_pool(thread_count);
+
+    info!("Created custom ThreadPool with {} threads", thread_count);
+
+    let progress = Progress::new(process_options.progress, total_files as u64);
 
-    let duplicate_tracker = Arc::new(DashMap::new());
+    let context = ScanContext {
+        progress: &progress,
+        seen_hashes: Arc::new(DashMap::new()),
+        duplicate_paths: DashMap::new(),
+        errors: &errors,
+        options,
+        checkpoint,
+        collected: AtomicUsize::new(0),
+        processed: AtomicUsize::new(0),
+        duplicates_seen: AtomicUsize::new(0),
+        duplicate_bytes: AtomicU64::new(0),
+    };
 
     // Process using custom ThreadPool with Rayon scope for Thread Contention
-    let results = custom_pool.install(|| {
-        process_nested_folders_with_scope(&dir_batches, &progress_bar, &duplicate_tracker)
-    });
+    let results = custom_pool.install(|| process_nested_folders_with_scope(&dir_batches, &context));
+
+    progress.finish("Scan completed!");
+
+    if let Some(max_files) = process_options.max_files {
+        if max_files < total_files {
+            status!(
+                "⏹️  Stopped after {} of {} files (--max-files)",
+                max_files,
+                total_files
+            );
+        }
+    }
+
+    // Duplicates are dropped during the scan unless every file is kept, in
+    // which case they are counted here instead
+    let mut seen = std::collections::HashSet::new();
+    let mut duplicate_files = context.duplicates_seen.load(Ordering::Relaxed);
+    let mut duplicate_bytes = context.duplicate_bytes.load(Ordering::Relaxed);
+    for file in &results {
+        if !seen.insert(file.hash.as_str()) && !options.hash.no_hash {
+            duplicate_files += 1;
+            duplicate_bytes += file.size;
+        }
+    }
+    let stats = ScanStats {
+        files_found: total_files,
+        unique_files: if options.hash.no_hash {
+            results.len()
+        } else {
+            seen.len()
+        },
+        duplicate_files,
+        duplicate_bytes,
+        total_bytes: results.iter().map(|file| file.size).sum(),
+        by_filetype: filetype_breakdown(&results),
+        started_at,
+        elapsed_secs: start_time.elapsed().as_secs_f64(),
+        threads: thread_count,
+        manifest_hash: None,
+    };
+    print_completion_stats(&stats);
+
+    if let Some(checkpoint) = checkpoint {
+        status!("♻️  Reused {} hashes from checkpoint", checkpoint.hits());
+        if let Err(e) = checkpoint.flush() {
+            warn!("Failed to write checkpoint: {}", e);
+        }
+    }
+
+    ScanReport {
+        files: results,
+        stats,
+        errors: collect_errors(errors),
+        skipped_dirs,
+    }
+}
+
+/// Converts the concurrent error map into a list sorted by path
+fn collect_errors(errors: DashMap<PathBuf, String>) -> Vec<ScanError> {
+    let mut errors: Vec<ScanError> = errors.into_iter().collect();
+    errors.sort_by(|a, b| a.0.cmp(&b.0));
+    errors
+}
+
+/// Prints per-directory counts and lists planned files without hashing them
+fn plan_dry_run(dir_batches: &[DirBatch], errors: &DashMap<PathBuf, String>) -> Vec<FileMeta> {
+    status!("Dry run: no files will be hashed");
+
+    let mut sorted_batches: Vec<&DirBatch> = dir_batches.iter().collect();
+    sorted_batches.sort_by(|a, b| a.path.cmp(&b.path));
+    for batch in &sorted_batches {
+        debug!("Planned {} ({} files)", batch.path, batch.files.len());
+    }
+
+    let mut planned: Vec<FileMeta> = sorted_batches
+        .iter()
+        .flat_map(|batch| batch.files.iter())
+        .filter_map(|file| match plan_single_file(file) {
+            Ok(file_meta) => Some(file_meta),
+            Err(e) => {
+                errors.insert(file.path.clone(), e.to_string());
+                None
+            }
+        })
+        .collect();
+    planned.sort_by(|a, b| a.filename.cmp(&b.filename));
+
+    status!("Dry run total: {} files", planned.len());
+    planned
+}
+
+/// Renders a file timestamp as RFC 3339, or `None` when the platform lacks it
+fn format_timestamp(time: std::io::Result<std::time::SystemTime>) -> Option<String> {
+    time.ok()
+        .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+}
+
+/// Builds metadata from the directory entry alone, leaving the hash empty
+fn plan_single_file(file: &DiscoveredFile) -> std::io::Result<FileMeta> {
+    let path = &file.path;
+    let metadata = file.metadata()?;
+    let mime_type = from_path(path).first_or_octet_stream();
+    let mime_str = mime_type.essence_str();
 
-    progress_bar.finish_with_message("Scan completed!");
-    print_completion_stats(&start_time, results.len());
+    Ok(FileMeta {
+        filename: path.display().to_string(),
+        path: None,
+        path_bytes: encode_path_bytes(path),
+        folder: path
+            .parent()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default(),
+        size: metadata.len(),
+        mime: mime_str.to_string(),
+        hash: String::new(),
+        hash_algo: String::new(),
+        filetype: determine_file_type_fast(mime_str),
+        duplicate_paths: Vec::new(),
+        modified: format_timestamp(metadata.modified()),
+        created: format_timestamp(metadata.created()),
+        width: None,
+        height: None,
+        duration_secs: None,
+        codec: None,
+        camera_make: None,
+        camera_model: None,
+        taken_at: None,
+        latitude: None,
+        longitude: None,
+        chunks: Vec::new(),
+    })
+}
 
-    results
+/// Builds the scanner pool with exactly `thread_count` workers
+fn build_thread_pool(thread_count: usize) -> rayon::ThreadPool {
+    ThreadPoolBuilder::new()
+        .num_threads(thread_count)
+        .thread_name(|i| format!("scanner-{}", i))
+        .build()
+        .expect("Failed to create custom thread pool")
 }
 
 /// Determines optimal thread count based on system capabilities and workload
-fn determine_optimal_thread_count() -> usize {
-    let cpu_count = num_cpus::get();
-    let physical_cores = num_cpus::get_physical();
+fn determine_optimal_thread_count(
+    workload: Workload,
+    cpu_count: usize,
+    physical_cores: usize,
+) -> usize {
+    match workload {
+        // Hashing saturates the cores; extra threads only add contention
+        Workload::Cpu => physical_cores.max(1),
+        // For I/O intensive work like file scanning, we can use more threads than CPU cores
+        // But limit to avoid too much context switching
+        Workload::Io | Workload::Auto => {
+            let optimal = std::cmp::min(cpu_count * 2, physical_cores * 4);
+            std::cmp::max(optimal, 4) // Minimum 4 threads
+        }
+    }
+}
+
+/// Reads and hashes the start of a few files; if reading took longer than
+/// hashing the work is treated as I/O-bound
+fn sample_workload(dir_batches: &[DirBatch], algorithm: HashAlgorithm) -> Workload {
+    let mut read_time = std::time::Duration::ZERO;
+    let mut hash_time = std::time::Duration::ZERO;
+    let mut buffer = vec![0; WORKLOAD_SAMPLE_BYTES];
+
+    let samples = dir_batches
+        .iter()
+        .flat_map(|batch| batch.files.iter())
+        .take(WORKLOAD_SAMPLE_FILES);
+    for file in samples {
+        let started = Instant::now();
+        let bytes_read = match fs::File::open(&file.path)
+            .and_then(|mut f| std::io::Read::read(&mut f, &mut buffer))
+        {
+            Ok(bytes_read) => bytes_read,
+            Err(_) => continue,
+        };
+        read_time += started.elapsed();
+
+        let started = Instant::now();
+        algorithm.hash_bytes(&buffer[..bytes_read]);
+        hash_time += started.elapsed();
+    }
+
+    let workload = if read_time > hash_time {
+        Workload::Io
+    } else {
+        Workload::Cpu
+    };
+    info!(
+        "Workload auto-detected as {:?} (read {:?}, hash {:?})",
+        workload, read_time, hash_time
+    );
+    workload
+}
+
+/// Groups listed files into batches by parent directory, stat'ing and filtering
+/// each one the way a walk would
+fn group_listed_files(
+    paths: &[PathBuf],
+    filter: &FileFilter,
+    errors: &DashMap<PathBuf, String>,
+) -> Vec<DirBatch> {
+    let mut batches: std::collections::BTreeMap<String, DirBatch> = Default::default();
+    for (discovered, path) in paths.iter().enumerate() {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => {
+                errors.insert(path.clone(), "not a regular file".to_string());
+                continue;
+            }
+            Err(e) => {
+                errors.insert(path.clone(), e.to_string());
+                continue;
+            }
+        };
+        if !filter.is_included(path)
+            || filter.excludes_listed(path)
+            || !filter.size_in_range(metadata.len())
+        {
+            continue;
+        }
+
+        let parent = path
+            .parent()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        batches
+            .entry(parent.clone())
+            .or_insert_with(|| DirBatch {
+                depth: 0,
+                path: parent,
+                files: Vec::new(),
+                skipped: SkipCounts::default(),
+            })
+            .files
+            .push(DiscoveredFile {
+                path: path.clone(),
+                metadata: Ok(metadata),
+                discovered,
+            });
+    }
+
+    // Depth is measured from the directory all listed files share, like a walk's root
+    let mut batches: Vec<DirBatch> = batches.into_values().collect();
+    let common = batches
+        .iter()
+        .map(|batch| Path::new(&batch.path))
+        .reduce(common_ancestor)
+        .map(Path::to_path_buf);
+    if let Some(common) = common {
+        for batch in &mut batches {
+            batch.depth = Path::new(&batch.path)
+                .strip_prefix(&common)
+                .map_or(0, |relative| relative.components().count());
+        }
+    }
+    batches
+}
 
-    // For I/O intensive work like file scanning, we can use more threads than CPU cores
-    // But limit to avoid too much context switching
-    let optimal = std::cmp::min(cpu_count * 2, physical_cores * 4);
-    std::cmp::max(optimal, 4) // Minimum 4 threads
+/// Longest leading path both `a` and `b` start with
+fn common_ancestor<'a>(a: &'a Path, b: &Path) -> &'a Path {
+    a.ancestors()
+        .find(|ancestor| b.starts_with(ancestor))
+        .unwrap_or(Path::new(""))
 }
 
 /// Discovers and organizes files into hierarchical directory batches
-fn discover_nested_structure(folder: &str) -> Vec<DirBatch> {
-    println!("Stage 1: Discovering nested folder structure...");
+fn discover_nested_structure(
+    folder: &str,
+    options: &ScanOptions,
+    errors: &DashMap<PathBuf, String>,
+) -> Vec<DirBatch> {
+    let walk_options = options.walk;
+    let follow_symlinks = walk_options.follow_symlinks;
+    info!("Stage 1: Discovering nested folder structure...");
+    if walk_options.respect_gitignore {
+        return discover_gitignore_structure(folder, options, errors);
+    }
 
-    // Group files by their parent directory
-    let mut dir_file_map: HashMap<String, Vec<walkdir::DirEntry>> = HashMap::new();
-    let mut dir_depths: HashMap<String, usize> = HashMap::new();
+    // Files are grouped by parent directory from the walker's worker threads
+    let dir_file_map: Arc<DashMap<String, DirBatch>> = Arc::new(DashMap::new());
+    let visited_dirs: Arc<DashSet<DirKey>> = Arc::new(DashSet::new());
 
-    for entry in WalkDir::new(folder).into_iter().filter_map(Result::ok) {
-        if entry.file_type().is_file() && should_process_file(&entry) {
-            let parent_path = entry
-                .path()
-                .parent()
-                .map(|p| p.display().to_string())
-                .unwrap_or_else(|| folder.to_string());
+    // Discovery order needs each directory read in name order; files are then
+    // numbered as the walk yields them, which is depth-first
+    let track_order = options.process.order == ResultOrder::Discovery;
+    let mut walk_dir = WalkDir::new(folder)
+        .follow_links(follow_symlinks)
+        .skip_hidden(false)
+        .sort(track_order);
+    if let Some(max_depth) = walk_options.max_depth {
+        walk_dir = walk_dir.max_depth(max_depth);
+    }
 
-            let depth = entry.path().components().count();
+    // Each directory listing is filtered on the thread that read it, so the
+    // per-file stat for size filtering runs in parallel too
+    let root = folder.to_string();
+    let filter = Arc::new(options.filter.clone());
+    let file_map = Arc::clone(&dir_file_map);
+    let report_skipped = walk_options.report_skipped;
+    let walk_dir = walk_dir.process_read_dir(move |_depth, parent, _state, children| {
+        let mut files = Vec::new();
+        let mut skipped = SkipCounts::default();
+        let mut depth = 0;
+        for child in std::mem::take(children) {
+            match child {
+                Ok(entry) if entry.file_type().is_file() => {
+                    match accept_file(&entry, &root, &filter) {
+                        Ok(file) => files.push(file),
+                        Err(reason) => skipped.add(reason),
+                    }
+                    depth = entry.depth().saturating_sub(1);
+                    // Kept so the consuming loop sees files in walk order
+                    if track_order {
+                        children.push(Ok(entry));
+                    }
+                }
+                Ok(entry) if entry.file_type().is_dir() => {
+                    // Excluded directories are pruned here so their subtrees are never walked
+                    if is_excluded(&entry, &root, &filter) {
+                        continue;
+                    }
+                    // Never descend into the same directory twice through symlinks
+                    if follow_symlinks {
+                        if let Some(key) = dir_key(&entry) {
+                            if !visited_dirs.insert(key) {
+                                debug!(
+                                    "Skipping already visited directory: {}",
+                                    entry.path().display()
+                                );
+                                continue;
+                            }
+                        }
+                    }
+                    children.push(Ok(entry));
+                }
+                // Errors and other entry types are left for the consuming loop
+                other => children.push(other),
+            }
+        }
 
-            dir_file_map
+        // Directories with only filtered-out files are kept as empty batches for the report
+        if !files.is_empty() || (report_skipped && skipped.total() > 0) {
+            // `depth` is the parent directory's depth relative to the scan root
+            let parent_path = parent.display().to_string();
+            let mut batch = file_map
                 .entry(parent_path.clone())
-                .or_insert_with(Vec::new)
-                .push(entry);
+                .or_insert_with(|| DirBatch {
+                    path: parent_path,
+                    files: Vec::new(),
+                    depth,
+                    skipped: SkipCounts::default(),
+                });
+            batch.files.extend(files);
+            batch.skipped = skipped;
+        }
+    });
 
-            dir_depths.insert(parent_path, depth);
+    let mut walk_order: HashMap<PathBuf, usize> = HashMap::new();
+    for entry in walk_dir {
+        match entry {
+            Ok(entry) if track_order && entry.file_type().is_file() => {
+                let discovered = walk_order.len();
+                walk_order.insert(entry.path(), discovered);
+            }
+            Ok(_) => {}
+            Err(e) if e.loop_ancestor().is_some() => {
+                let path = e
+                    .path()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default();
+                debug!("Skipping symlink cycle: {}", path);
+            }
+            Err(e) => {
+                let path = e
+                    .path()
+                    .map(|p| p.to_path_buf())
+                    .unwrap_or_else(|| PathBuf::from(folder));
+                errors.insert(path, e.to_string());
+            }
         }
     }
 
-    // Convert to DirBatch and sort by depth (deeper directories first)
-    let mut batches: Vec<DirBatch> = dir_file_map
-        .into_iter()
-        .map(|(path, files)| DirBatch {
-            depth: dir_depths.get(&path).copied().unwrap_or(0),
-            path,
-            files,
+    let mut batches = into_sorted_batches(&dir_file_map, walk_options);
+    if track_order {
+        for file in batches.iter_mut().flat_map(|batch| &mut batch.files) {
+            file.discovered = walk_order.get(&file.path).copied().unwrap_or_default();
+        }
+    }
+    batches
+}
+
+/// Like [`discover_nested_structure`], but walks with the `ignore` crate so
+/// `.gitignore` files prune the paths they ignore, whole subtrees included
+fn discover_gitignore_structure(
+    folder: &str,
+    options: &ScanOptions,
+    errors: &DashMap<PathBuf, String>,
+) -> Vec<DirBatch> {
+    let walk_options = options.walk;
+    let dir_file_map: DashMap<String, DirBatch> = DashMap::new();
+
+    // Only gitignore rules apply; hidden files are skipped below as in the default walk
+    let mut builder = ignore::WalkBuilder::new(folder);
+    builder
+        .standard_filters(false)
+        .git_ignore(true)
+        .parents(true)
+        .require_git(false)
+        .follow_links(walk_options.follow_symlinks)
+        .max_depth(walk_options.max_depth);
+    let root = folder.to_string();
+    let filter = options.filter.clone();
+    builder.filter_entry(move |entry| {
+        let is_dir = entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_dir());
+        if !is_dir || entry.depth() == 0 || !filter.has_excludes() {
+            return true;
+        }
+        let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+        !filter.excludes_path(relative, true)
+    });
+
+    // Discovery order walks on one thread in name order and numbers files as they come
+    let track_order = options.process.order == ResultOrder::Discovery;
+    let next_index = AtomicUsize::new(0);
+    let visit = |entry: Result<ignore::DirEntry, ignore::Error>| {
+        match entry {
+            Ok(entry)
+                if entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_file()) =>
+            {
+                let relative = entry.path().strip_prefix(folder).unwrap_or(entry.path());
+                let accepted = accept_path(entry.path().to_path_buf(), &options.filter, || {
+                    options.filter.excludes_path(relative, false)
+                });
+                if accepted.is_err() && !walk_options.report_skipped {
+                    return;
+                }
+                let parent_path = entry
+                    .path()
+                    .parent()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default();
+                let mut batch =
+                    dir_file_map
+                        .entry(parent_path.clone())
+                        .or_insert_with(|| DirBatch {
+                            path: parent_path,
+                            files: Vec::new(),
+                            depth: entry.depth().saturating_sub(1),
+                            skipped: SkipCounts::default(),
+                        });
+                match accepted {
+                    Ok(mut file) => {
+                        file.discovered = next_index.fetch_add(1, Ordering::Relaxed);
+                        batch.files.push(file);
+                    }
+                    Err(reason) => batch.skipped.add(reason),
+                }
+            }
+            Ok(_) => {}
+            Err(ignore::Error::WithPath { path, err }) => {
+                errors.insert(path, err.to_string());
+            }
+            Err(e) if e.is_io() => {
+                errors.insert(PathBuf::from(folder), e.to_string());
+            }
+            // Symlink loops and unreadable .gitignore lines are not scan errors
+            Err(e) => debug!("Skipping during walk: {}", e),
+        }
+    };
+    if track_order {
+        builder.sort_by_file_name(|a, b| a.cmp(b));
+        builder.build().for_each(visit);
+    } else {
+        builder.build_parallel().run(|| {
+            Box::new(|entry| {
+                visit(entry);
+                ignore::WalkState::Continue
+            })
+        });
+    }
+
+    into_sorted_batches(&dir_file_map, walk_options)
+}
+
+/// Moves the batches out of a walk's directory map, deepest first. Batches with
+/// fewer than `--min-files` files are dropped, or emptied and kept for the
+/// skipped-directory report
+fn into_sorted_batches(
+    dir_file_map: &DashMap<String, DirBatch>,
+    walk_options: WalkOptions,
+) -> Vec<DirBatch> {
+    let min_files = walk_options.min_files;
+    // Worker threads may still hold the map briefly, so move batches out by key
+    let dir_paths: Vec<String> = dir_file_map
+        .iter()
+        .map(|entry| entry.key().clone())
+        .collect();
+    let mut batches: Vec<DirBatch> = dir_paths
+        .iter()
+        .filter_map(|path| dir_file_map.remove(path).map(|(_, batch)| batch))
+        .filter_map(|mut batch| {
+            // Empty batches only carry skip counts and are split off before processing
+            if batch.files.is_empty() || batch.files.len() >= min_files {
+                return Some(batch);
+            }
+            debug!(
+                "Skipping {} with {} files (--min-files {})",
+                batch.path,
+                batch.files.len(),
+                min_files
+            );
+            if !walk_options.report_skipped {
+                return None;
+            }
+            batch.skipped.below_min_files = batch.files.len();
+            batch.files.clear();
+            Some(batch)
         })
         .collect();
 
     // Sort by depth (process deeper folders first for better cache locality)
     batches.sort_by_key(|batch| std::cmp::Reverse(batch.depth));
 
-    println!("Organized into {} directory batches", batches.len());
+    debug!("Organized into {} directory batches", batches.len());
     batches
 }
 
 /// Process nested folders using Rayon scope for optimal thread management
 fn process_nested_folders_with_scope(
     dir_batches: &[DirBatch],
-    progress_bar: &ProgressBar,
-    seen_hashes: &Arc<DashMap<String, String>>,
+    context: &ScanContext,
 ) -> Vec<FileMeta> {
-    println!("Stage 2: Processing files with custom ThreadPool and scope...");
+    info!("Stage 2: Processing files with custom ThreadPool and scope...");
 
     let results = Arc::new(DashMap::new());
 
@@ -147,28 +1283,46 @@ fn process_nested_folders_with_scope(
     rayon::scope(|scope| {
         for (batch_idx, dir_batch) in dir_batches.iter().enumerate() {
             let results_ref = Arc::clone(&results);
-            let seen_hashes_ref = Arc::clone(seen_hashes);
-            let progress_bar_ref = progress_bar;
 
             // Spawn each directory batch in parallel scope
             scope.spawn(move |_nested_scope| {
-                process_directory_batch_scoped(
-                    dir_batch,
-                    batch_idx,
-                    results_ref,
-                    seen_hashes_ref,
-                    progress_bar_ref,
-                );
+                process_directory_batch_scoped(dir_batch, batch_idx, results_ref, context);
             });
         }
     });
 
     // Collect results from all batches
+    let mut keyed_results: Vec<(ResultKey, FileMeta)> = Arc::try_unwrap(results)
+        .map(|results| results.into_iter().collect())
+        .unwrap_or_else(|results| {
+            results
+                .iter()
+                .map(|entry| (*entry.key(), entry.value().clone()))
+                .collect()
+        });
+    if context.options.process.order == ResultOrder::Discovery {
+        // Archive members keep their order inside the file that holds them
+        keyed_results.sort_unstable_by_key(|&((batch_idx, file_idx, entry_idx), _)| {
+            (dir_batches[batch_idx].files[file_idx].discovered, entry_idx)
+        });
+    }
     let mut final_results: Vec<FileMeta> =
-        results.iter().map(|entry| entry.value().clone()).collect();
+        keyed_results.into_iter().map(|(_, file)| file).collect();
+
+    // Attach grouped duplicates to the file that was kept for each hash
+    if context.options.process.duplicates == DuplicateMode::Group {
+        for file_meta in &mut final_results {
+            if let Some((_, mut paths)) = context.duplicate_paths.remove(&file_meta.hash) {
+                paths.sort();
+                file_meta.duplicate_paths = paths;
+            }
+        }
+    }
 
     // Sort by filename for consistent output
-    final_results.sort_by(|a, b| a.filename.cmp(&b.filename));
+    if context.options.process.order == ResultOrder::Name {
+        final_results.sort_by(|a, b| a.filename.cmp(&b.filename));
+    }
 
     final_results
 }
@@ -177,13 +1331,26 @@ fn process_nested_folders_with_scope(
 fn process_directory_batch_scoped(
     dir_batch: &DirBatch,
     batch_idx: usize,
-    results: Arc<DashMap<String, FileMeta>>,
-    seen_hashes: Arc<DashMap<String, String>>,
-    progress_bar: &ProgressBar,
+    results: Arc<DashMap<ResultKey, FileMeta>>,
+    context: &ScanContext,
 ) {
+    // Whole batches are skipped once the cap is reached, without touching their files
+    if context.max_files_reached() {
+        return;
+    }
+
+    let progress = context.progress;
+    let seen_hashes = &context.seen_hashes;
+    // Empty hashes would all match each other
+    let duplicates = if context.options.hash.no_hash {
+        DuplicateMode::KeepAll
+    } else {
+        context.options.process.duplicates
+    };
+
     // Log directory processing (using the path field)
     if dir_batch.files.len() > 10 {
-        progress_bar.set_message(format!(
+        progress.set_message(format!(
             "Processing directory: {} ({} files)",
             dir_batch.path,
             dir_batch.files.len()
@@ -191,89 +1358,306 @@ fn process_directory_batch_scoped(
     }
 
     // ✅ Process files without individual progress updates
-    let batch_results: Vec<Option<FileMeta>> = dir_batch
+    type BatchResult = (usize, std::io::Result<FileMeta>, Vec<FileMeta>);
+    let batch_results: Vec<BatchResult> = dir_batch
         .files
         .par_iter()
-        .map(|entry| {
-            let file_meta = process_single_file_ultra_fast(entry)?;
+        .enumerate()
+        .filter_map(|(file_idx, file)| {
+            // Skip hashing entirely once `--limit` is satisfied
+            if context.limit_reached() || !context.claim_file() {
+                return None;
+            }
+            let file_meta =
+                process_single_file_ultra_fast(file, context.options, context.checkpoint);
             // ❌ ลบ progress_bar.inc(1) ออก - ไม่ให้ thread แย่งกัน
-            Some(file_meta)
+            let entries = match &file_meta {
+                Err(e) => {
+                    context.errors.insert(file.path.clone(), e.to_string());
+                    Vec::new()
+                }
+                Ok(file_meta)
+                    if context.options.process.expand_archives
+                        && archive::is_expandable(file_meta) =>
+                {
+                    let options = context.options;
+                    archive::expand_entries(file_meta, options.hash, &options.filter)
+                        .unwrap_or_else(|e| {
+                            warn!("Could not expand archive {}: {}", file_meta.filename, e);
+                            Vec::new()
+                        })
+                }
+                Ok(_) => Vec::new(),
+            };
+            Some((file_idx, file_meta, entries))
         })
         .collect();
 
     // ✅ Bulk update progress bar ครั้งเดียวหลังจบ directory batch
-    let processed_count = batch_results.iter().filter(|r| r.is_some()).count();
-    progress_bar.inc(processed_count as u64);
-
-    // Deduplicate and store results
-    for file_meta_opt in batch_results {
-        if let Some(file_meta) = file_meta_opt {
-            // Check for duplicates
-            if seen_hashes.contains_key(&file_meta.hash) {
-                continue; // Skip duplicate
-            }
+    let batch_bytes: u64 = batch_results
+        .iter()
+        .filter_map(|(_, file_meta, _)| file_meta.as_ref().ok())
+        .map(|file_meta| file_meta.size)
+        .sum();
+    progress.inc(batch_results.len() as u64, batch_bytes);
 
-            // Store unique file
-            seen_hashes.insert(file_meta.hash.clone(), file_meta.filename.clone());
-            let key = format!("{}_{}", batch_idx, file_meta.filename);
-            results.insert(key, file_meta);
+    // Deduplicate and store results; archive entries follow their archive
+    let expanded = batch_results
+        .into_iter()
+        .filter_map(|(file_idx, file_meta, entries)| Some((file_idx, file_meta.ok()?, entries)))
+        .flat_map(|(file_idx, file_meta, entries)| {
+            std::iter::once(file_meta)
+                .chain(entries)
+                .enumerate()
+                .map(move |(entry_idx, file_meta)| (file_idx, entry_idx, file_meta))
+        });
+    for (file_idx, entry_idx, mut file_meta) in expanded {
+        let hash_strategy = context.options.hash;
+        // Archive entries are always hashed in full and cannot be reopened by path
+        let mut claimed = false;
+        if hash_strategy.rehash_on_size_match
+            && entry_idx == 0
+            && hash_strategy.samples(file_meta.size)
+        {
+            // Claim the sampled hash under the entry lock so that every later
+            // match, from any batch, is confirmed against this file
+            let original = match seen_hashes.entry(file_meta.hash.clone()) {
+                dashmap::mapref::entry::Entry::Occupied(entry) => Some(entry.get().clone()),
+                dashmap::mapref::entry::Entry::Vacant(entry) => {
+                    entry.insert(file_meta.os_path());
+                    claimed = true;
+                    None
+                }
+            };
+            // Read outside the entry lock: hashing both files in full takes a while
+            if let Some(original) = original {
+                match confirm_sampled_match(&original, &file_meta, hash_strategy) {
+                    Ok(Some(full_hash)) => {
+                        debug!(
+                            "{} only matched {} on sampled regions; keeping its full hash",
+                            file_meta.filename,
+                            original.display()
+                        );
+                        file_meta.hash = full_hash;
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Could not confirm duplicate {}: {}", file_meta.filename, e),
+                }
+            }
         }
-    }
-}
+        if duplicates != DuplicateMode::KeepAll && !claimed {
+            // Check for duplicates; the entry lock makes check-and-insert atomic
+            match seen_hashes.entry(file_meta.hash.clone()) {
+                dashmap::mapref::entry::Entry::Occupied(_) => {
+                    context.duplicates_seen.fetch_add(1, Ordering::Relaxed);
+                    context
+                        .duplicate_bytes
+                        .fetch_add(file_meta.size, Ordering::Relaxed);
+                    if duplicates == DuplicateMode::Group {
+                        context
+                            .duplicate_paths
+                            .entry(file_meta.hash)
+                            .or_default()
+                            .push(file_meta.filename);
+                    }
+                    continue; // Skip duplicate
+                }
+                dashmap::mapref::entry::Entry::Vacant(entry) => {
+                    // Store unique file
+                    entry.insert(file_meta.os_path());
+                }
+            }
+        }
+
+        // Files hashed concurrently may overshoot the limit; drop the extras
+        if let Some(limit) = context.options.process.limit {
+            if context.collected.fetch_add(1, Ordering::Relaxed) >= limit {
+                break;
+            }
+        }
+
+        results.insert((batch_idx, file_idx, entry_idx), file_meta);
+    }
+}
+
+/// Hashes a file and the original it matched on a sampled hash in full;
+/// returns the file's full hash when the contents differ, `None` when they match
+fn confirm_sampled_match(
+    original: &Path,
+    file: &FileMeta,
+    hash_strategy: HashStrategy,
+) -> std::io::Result<Option<String>> {
+    let full = HashStrategy {
+        full_hash: true,
+        ..hash_strategy
+    };
+    let file_hash = hash_file(&file.os_path(), file.size, full)?;
+    let original_hash = hash_file(original, file.size, full)?;
+    Ok((file_hash != original_hash).then_some(file_hash))
+}
+
+/// Returns a stable identity for a directory so symlinked revisits can be detected
+#[cfg(unix)]
+fn dir_key(entry: &WalkEntry) -> Option<DirKey> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = entry.metadata().ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_key(entry: &WalkEntry) -> Option<DirKey> {
+    fs::canonicalize(entry.path()).ok()
+}
+
+/// Checks an entry against the exclude set using its path relative to the scan root
+fn is_excluded(entry: &WalkEntry, root: &str, filter: &FileFilter) -> bool {
+    if !filter.has_excludes() || entry.depth() == 0 {
+        return false;
+    }
 
-/// Early filtering to skip files we don't want to process
-fn should_process_file(entry: &walkdir::DirEntry) -> bool {
     let path = entry.path();
+    let relative = path.strip_prefix(root).unwrap_or(&path);
+    filter.excludes_path(relative, entry.file_type().is_dir())
+}
 
+/// Early filtering to skip files we don't want to process; the single stat taken
+/// here is carried into processing so the file is never stat'ed twice
+fn accept_file(
+    entry: &WalkEntry,
+    root: &str,
+    filter: &FileFilter,
+) -> Result<DiscoveredFile, SkipReason> {
+    accept_path(entry.path(), filter, || is_excluded(entry, root, filter))
+}
+
+/// The checks of [`accept_file`] in order: hidden names, the include whitelist,
+/// then `excluded` (which depends on the walker), and finally the size range
+fn accept_path(
+    path: PathBuf,
+    filter: &FileFilter,
+    excluded: impl FnOnce() -> bool,
+) -> Result<DiscoveredFile, SkipReason> {
     // Skip hidden files and system files
     if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
         if name.starts_with('.') {
-            return false;
+            return Err(SkipReason::Hidden);
         }
     }
 
-    // Skip very small files (likely not media)
-    if let Ok(metadata) = entry.metadata() {
-        if metadata.len() < 1024 {
-            // Skip files smaller than 1KB
-            return false;
+    if !filter.is_included(&path) {
+        return Err(SkipReason::NotIncluded);
+    }
+
+    if excluded() {
+        return Err(SkipReason::Excluded);
+    }
+
+    // Skip files outside the configured size range
+    let metadata = fs::metadata(&path);
+    if let Ok(metadata) = &metadata {
+        if !filter.size_in_range(metadata.len()) {
+            return Err(if metadata.len() < filter.min_size {
+                SkipReason::BelowMinSize
+            } else {
+                SkipReason::AboveMaxSize
+            });
         }
     }
 
-    true
+    Ok(DiscoveredFile {
+        path,
+        metadata,
+        discovered: 0,
+    })
 }
 
 /// Ultra-optimized single file processing with memory mapping and reduced allocations
-fn process_single_file_ultra_fast(entry: &walkdir::DirEntry) -> Option<FileMeta> {
-    let path = entry.path();
+fn process_single_file_ultra_fast(
+    file: &DiscoveredFile,
+    options: &ScanOptions,
+    checkpoint: Option<&Checkpoint>,
+) -> std::io::Result<FileMeta> {
+    let path = &file.path;
+    let hash_strategy = options.hash;
+    let extract = options.process.extract;
 
-    // Get metadata once - batch system calls
-    let metadata = path.metadata().ok()?;
+    // Metadata was captured during discovery - no second stat
+    let metadata = file.metadata()?;
     let file_size = metadata.len();
 
     // Fast MIME type detection using file extension first
     let mime_type = from_path(path).first_or_octet_stream();
-    let mime_str = mime_type.essence_str(); // More efficient than to_string()
 
-    // Ultra-fast hash computation strategy based on file size
-    let file_hash = if file_size > VERY_LARGE_FILE_THRESHOLD {
-        // For very large files, use sampling hash (much faster)
-        compute_sample_hash(path, file_size).ok()?
-    } else if file_size > LARGE_FILE_THRESHOLD {
-        // Memory map for large files
-        compute_hash_mmap(path).ok()?
+    let hash_algo = hash_strategy.algorithm;
+    let filename = path.display().to_string();
+
+    let checkpoint_algo = hash_strategy.checkpoint_key(file_size);
+
+    // Reuse the checkpointed hash when the file is unchanged since the last run
+    let cached_hash = checkpoint.and_then(|cp| cp.lookup(&filename, metadata, &checkpoint_algo));
+    let reused_hash = cached_hash.is_some();
+
+    let mut head = None;
+    let file_hash = match cached_hash {
+        Some(cached_hash) => cached_hash,
+        None if hash_strategy.no_hash => String::new(),
+        None => {
+            let (file_hash, content) =
+                hash_contents(path, file_size, hash_strategy, extract.sniff_content)?;
+            head = content;
+            file_hash
+        }
+    };
+
+    if let Some(checkpoint) = checkpoint {
+        if !reused_hash && !hash_strategy.no_hash {
+            checkpoint.record(&filename, metadata, &file_hash, &checkpoint_algo);
+        }
+    }
+
+    // Magic bytes win over the extension when sniffing finds a match
+    let sniffed_mime = if extract.sniff_content {
+        media::sniff_mime(path, head.as_deref())
     } else {
-        // Direct read for small files
-        let file_content = fs::read(path).ok()?;
-        hash(&file_content).to_hex().to_string()
+        None
     };
+    let mime_str = sniffed_mime.unwrap_or_else(|| mime_type.essence_str());
 
     // Efficient file type determination
     let file_type = determine_file_type_fast(mime_str);
 
+    // Header-only dimension read, skipped unless requested
+    let dimensions = if extract.dimensions && mime_str.starts_with("image/") {
+        media::image_dimensions(path)
+    } else {
+        None
+    };
+
+    // ffprobe is slow and optional, so only run it for videos when asked
+    let media_info = if extract.media_info && file_type == "video" {
+        media::probe_media(path).unwrap_or_default()
+    } else {
+        media::MediaInfo::default()
+    };
+
+    let exif_info = if extract.exif && media::has_exif(mime_str) {
+        media::read_exif(path).unwrap_or_default()
+    } else {
+        media::ExifInfo::default()
+    };
+
+    // Chunking reads the whole file, so it is never served from the checkpoint
+    let chunks = if hash_strategy.chunk_hashes {
+        chunk_hashes(path)?
+    } else {
+        Vec::new()
+    };
+
     // Minimize allocations
-    Some(FileMeta {
-        filename: path.display().to_string(),
+    Ok(FileMeta {
+        filename,
+        path: None,
+        path_bytes: encode_path_bytes(path),
         folder: path
             .parent()
             .map(|p| p.display().to_string())
@@ -281,40 +1665,1330 @@ fn process_single_file_ultra_fast(entry: &walkdir::DirEntry) -> Option<FileMeta>
         size: file_size,
         mime: mime_str.to_string(),
         hash: file_hash,
+        hash_algo: if hash_strategy.no_hash {
+            String::new()
+        } else {
+            hash_algo.name().to_string()
+        },
         filetype: file_type,
+        duplicate_paths: Vec::new(),
+        modified: format_timestamp(metadata.modified()),
+        created: format_timestamp(metadata.created()),
+        width: dimensions.map(|(width, _)| width),
+        height: dimensions.map(|(_, height)| height),
+        duration_secs: media_info.duration_secs,
+        codec: media_info.codec,
+        camera_make: exif_info.camera_make,
+        camera_model: exif_info.camera_model,
+        taken_at: exif_info.taken_at,
+        latitude: exif_info.latitude,
+        longitude: exif_info.longitude,
+        chunks,
     })
 }
 
+/// Hashes a file with the strategy's algorithm, sampling or memory-mapping by size
+pub fn hash_file(
+    path: &std::path::Path,
+    file_size: u64,
+    hash_strategy: HashStrategy,
+) -> std::io::Result<String> {
+    hash_contents(path, file_size, hash_strategy, false).map(|(file_hash, _)| file_hash)
+}
+
+/// Ultra-fast hash computation strategy based on file size; small files' contents
+/// are returned when `keep_content` is set so they can be reused
+fn hash_contents(
+    path: &std::path::Path,
+    file_size: u64,
+    hash_strategy: HashStrategy,
+    keep_content: bool,
+) -> std::io::Result<(String, Option<Vec<u8>>)> {
+    let hash_algo = hash_strategy.algorithm;
+    let encoding = hash_strategy.encoding;
+    let file_hash = if file_size == 0 {
+        // Empty files get the digest of empty input without opening them
+        encoding.encode(&hash_algo.digest_bytes(&[]))
+    } else if hash_strategy.samples(file_size) {
+        // For very large files, use sampling hash (much faster)
+        let sample_points = hash_strategy
+            .sample_points
+            .unwrap_or_else(|| auto_sample_points(file_size));
+        compute_sample_hash(
+            path,
+            file_size,
+            hash_algo,
+            hash_strategy.sample_size,
+            sample_points,
+            encoding,
+        )?
+    } else if file_size > hash_strategy.large_threshold {
+        // Memory map for large files
+        encoding.encode(&compute_digest_mmap(path, hash_algo)?)
+    } else {
+        // Direct read for small files
+        let file_content = fs::read(path)?;
+        let file_hash = encoding.encode(&hash_algo.digest_bytes(&file_content));
+        return Ok((file_hash, keep_content.then_some(file_content)));
+    };
+    Ok((file_hash, None))
+}
+
 /// Memory-mapped hash computation for large files
-fn compute_hash_mmap(path: &std::path::Path) -> Result<String, Box<dyn std::error::Error>> {
+fn compute_digest_mmap(
+    path: &std::path::Path,
+    hash_algo: HashAlgorithm,
+) -> std::io::Result<Vec<u8>> {
     let file = fs::File::open(path)?;
-    let mmap = unsafe { Mmap::map(&file)? };
-    let hash_result = hash(&mmap);
-    Ok(hash_result.to_hex().to_string())
+    // Some filesystems and special files cannot be mapped; stream those instead
+    match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => Ok(hash_algo.digest_bytes(&mmap)),
+        Err(_) => hash_algo.digest_reader(file),
+    }
 }
 
 /// Fast file type determination without string allocation
-fn determine_file_type_fast(mime_str: &str) -> String {
-    match mime_str.as_bytes()[0] {
+pub fn determine_file_type_fast(mime_str: &str) -> String {
+    // Empty MIME strings have no first byte and fall through to "other"
+    match mime_str.as_bytes().first().copied().unwrap_or_default() {
         b'i' if mime_str.starts_with("image/") => "image".to_string(),
         b'v' if mime_str.starts_with("video/") => "video".to_string(),
+        b'a' if mime_str.starts_with("audio/") => "audio".to_string(),
+        b'a' if is_document_mime(mime_str) => "document".to_string(),
+        b'a' if is_archive_mime(mime_str) => "archive".to_string(),
         _ => "other".to_string(),
     }
 }
 
+/// Common office and PDF document types
+fn is_document_mime(mime_str: &str) -> bool {
+    matches!(
+        mime_str,
+        "application/pdf"
+            | "application/msword"
+            | "application/rtf"
+            | "application/vnd.ms-excel"
+            | "application/vnd.ms-powerpoint"
+            | "application/vnd.oasis.opendocument.text"
+            | "application/vnd.oasis.opendocument.spreadsheet"
+            | "application/vnd.oasis.opendocument.presentation"
+    ) || mime_str.starts_with("application/vnd.openxmlformats-officedocument.")
+}
+
+/// Compressed and bundled archive types
+fn is_archive_mime(mime_str: &str) -> bool {
+    matches!(
+        mime_str,
+        "application/zip"
+            | "application/gzip"
+            | "application/x-gzip"
+            | "application/x-tar"
+            | "application/x-bzip2"
+            | "application/x-xz"
+            | "application/x-7z-compressed"
+            | "application/vnd.rar"
+            | "application/x-rar-compressed"
+            | "application/zstd"
+    )
+}
+
 /// Prints system information about thread pool and CPU cores
 fn print_system_info() {
     let num_threads = rayon::current_num_threads();
-    println!("System Info:");
-    println!("  Rayon threads: {}", num_threads);
-    println!("  CPU cores: {}", num_cpus::get());
+    debug!(
+        "System info: {} rayon threads, {} CPU cores",
+        num_threads,
+        num_cpus::get()
+    );
+}
+
+/// Lists the directories in `ScanReport::skipped_dirs` with their skip reasons
+pub fn print_skipped_dirs(skipped_dirs: &[SkippedDir]) {
+    let mut output = format!(
+        "🙈 {} directories had no processable files\n",
+        skipped_dirs.len()
+    );
+    for dir in skipped_dirs {
+        output.push_str(&format!("   {}: {}\n", dir.path, dir.skipped.describe()));
+    }
+    eprint!("{}", status::decorate(&output));
 }
 
 /// Prints completion statistics
-fn print_completion_stats(start_time: &Instant, file_count: usize) {
-    let elapsed = start_time.elapsed();
-    println!(
+fn print_completion_stats(stats: &ScanStats) {
+    status!(
         "Scanning completed in {:.2?} - processed {} unique files",
-        elapsed, file_count
+        std::time::Duration::from_secs_f64(stats.elapsed_secs),
+        stats.unique_files
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `len` bytes of `fill` to `relative` under `root`, creating parents
+    fn write_file(root: &Path, relative: &str, len: usize, fill: u8) -> PathBuf {
+        let path = root.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, vec![fill; len]).unwrap();
+        path
+    }
+
+    fn options_with(filter: FileFilter) -> ScanOptions {
+        ScanOptions {
+            filter,
+            ..ScanOptions::default()
+        }
+    }
+
+    /// Paths a walk accepts, relative to `root` and sorted
+    fn discovered(root: &Path, options: &ScanOptions) -> Vec<String> {
+        let errors = DashMap::new();
+        let mut paths: Vec<String> =
+            discover_nested_structure(&root.display().to_string(), options, &errors)
+                .into_iter()
+                .flat_map(|batch| batch.files)
+                .map(|file| file.path.strip_prefix(root).unwrap().display().to_string())
+                .collect();
+        paths.sort();
+        paths
+    }
+
+    fn excludes(patterns: &[&str]) -> GlobSet {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        build_exclude_set(&patterns).unwrap()
+    }
+
+    #[test]
+    fn exclude_matches_nested_paths_relative_to_root() {
+        let set = excludes(&["**/cache/**", "*.part"]);
+        assert!(set.is_match("a/b/cache/x.jpg"));
+        assert!(set.is_match("video.part"));
+        assert!(!set.is_match("a/b/photo.jpg"));
+
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "keep.jpg", 2048, 1);
+        write_file(dir.path(), "a/b/cache/thumb.jpg", 2048, 2);
+        write_file(dir.path(), "a/b/photo.jpg", 2048, 3);
+        write_file(dir.path(), "a/download.part", 2048, 4);
+
+        let options = options_with(FileFilter::new(&[], set, 0, None));
+        assert_eq!(
+            discovered(dir.path(), &options),
+            ["a/b/photo.jpg", "keep.jpg"]
+        );
+    }
+
+    #[test]
+    fn trailing_slash_pattern_prunes_directory_at_any_depth() {
+        let set = excludes(&["node_modules/"]);
+        assert!(set.is_match("node_modules"));
+        assert!(set.is_match("web/node_modules/pkg/index.js"));
+        // Only directories named exactly like the pattern are excluded
+        assert!(!set.is_match("node_modules.jpg"));
+
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "node_modules/a.js", 2048, 1);
+        write_file(dir.path(), "web/node_modules/pkg/index.js", 2048, 2);
+        write_file(dir.path(), "web/app.js", 2048, 3);
+
+        let options = options_with(FileFilter::new(&[], set, 0, None));
+        assert_eq!(discovered(dir.path(), &options), ["web/app.js"]);
+    }
+
+    #[test]
+    fn include_skips_other_extensions_and_keeps_min_size() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "photo.jpg", 2048, 1);
+        write_file(dir.path(), "upper.JPG", 2048, 2);
+        write_file(dir.path(), "tiny.jpg", 100, 3);
+        write_file(dir.path(), "graphic.png", 2048, 4);
+
+        let includes = [".jpg".to_string()];
+        let options = options_with(FileFilter::new(&includes, GlobSet::empty(), 1024, None));
+        assert_eq!(discovered(dir.path(), &options), ["photo.jpg", "upper.JPG"]);
+    }
+
+    #[test]
+    fn include_is_checked_before_exclude() {
+        let includes = ["jpg".to_string()];
+        let filter = FileFilter::new(&includes, excludes(&["*.png", "skip.jpg"]), 0, None);
+        let dir = tempfile::tempdir().unwrap();
+        let png = write_file(dir.path(), "graphic.png", 2048, 1);
+        let jpg = write_file(dir.path(), "skip.jpg", 2048, 2);
+
+        // A file outside the whitelist is reported as such even when an exclude matches too
+        let excluded = || true;
+        assert_eq!(
+            accept_path(png, &filter, excluded).unwrap_err(),
+            SkipReason::NotIncluded
+        );
+        assert_eq!(
+            accept_path(jpg, &filter, excluded).unwrap_err(),
+            SkipReason::Excluded
+        );
+    }
+
+    #[test]
+    fn min_size_keeps_files_exactly_at_the_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "below.bin", 1023, 1);
+        write_file(dir.path(), "exact.bin", 1024, 2);
+        write_file(dir.path(), "empty.bin", 0, 3);
+
+        let options = options_with(FileFilter::new(&[], GlobSet::empty(), 1024, None));
+        assert_eq!(discovered(dir.path(), &options), ["exact.bin"]);
+
+        // Zero disables the filter, so even empty files are kept
+        let options = options_with(FileFilter::new(&[], GlobSet::empty(), 0, None));
+        assert_eq!(
+            discovered(dir.path(), &options),
+            ["below.bin", "empty.bin", "exact.bin"]
+        );
+    }
+
+    #[test]
+    fn max_size_drops_larger_files_from_the_results() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "small.bin", 2000, 1);
+        write_file(dir.path(), "exact.bin", 4096, 2);
+        write_file(dir.path(), "large.bin", 5000, 3);
+
+        let options = options_with(FileFilter::new(&[], GlobSet::empty(), 0, Some(4096)));
+        let report = scan_folder(&[dir.path().display().to_string()], &options, None);
+        let names: Vec<&str> = report
+            .files
+            .iter()
+            .map(|file| file.filename.rsplit('/').next().unwrap())
+            .collect();
+        assert_eq!(names, ["exact.bin", "small.bin"]);
+    }
+
+    #[test]
+    fn duplicates_are_detected_across_roots() {
+        let photos = tempfile::tempdir().unwrap();
+        let videos = tempfile::tempdir().unwrap();
+        write_file(photos.path(), "beach.jpg", 3000, 7);
+        write_file(photos.path(), "only-here.jpg", 3000, 8);
+        write_file(videos.path(), "copy-of-beach.jpg", 3000, 7);
+
+        let roots = [
+            photos.path().display().to_string(),
+            videos.path().display().to_string(),
+        ];
+        let report = scan_folder(&roots, &ScanOptions::default(), None);
+        assert_eq!(report.stats.files_found, 3);
+        assert_eq!(report.files.len(), 2);
+        assert_eq!(report.stats.unique_files, 2);
+        assert_eq!(report.stats.duplicate_files, 1);
+        assert_eq!(report.stats.duplicate_bytes, 3000);
+    }
+
+    #[test]
+    fn dry_run_plans_files_without_opening_them() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "a.jpg", 2048, 1);
+        write_file(dir.path(), "nested/b.jpg", 4096, 2);
+
+        let mut options = ScanOptions::default();
+        options.process.dry_run = true;
+        let errors = DashMap::new();
+        let batches =
+            discover_nested_structure(&dir.path().display().to_string(), &options, &errors);
+        // Any read after discovery would now fail, so a clean plan proves none happened
+        fs::remove_dir_all(dir.path()).unwrap();
+
+        let report = process_batches(
+            batches,
+            errors,
+            &options,
+            None,
+            Instant::now(),
+            chrono::Utc::now(),
+        );
+        assert!(report.errors.is_empty());
+        let planned: Vec<(u64, &str)> = report
+            .files
+            .iter()
+            .map(|file| (file.size, file.hash.as_str()))
+            .collect();
+        assert_eq!(planned, [(2048, ""), (4096, "")]);
+    }
+
+    #[test]
+    fn hash_algo_is_used_for_direct_mmap_and_sampled_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let content: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, &content).unwrap();
+        let size = content.len() as u64;
+        let sha256 = HashAlgorithm::Sha256.hash_bytes(&content);
+
+        let direct = HashStrategy {
+            algorithm: HashAlgorithm::Sha256,
+            ..HashStrategy::default()
+        };
+        assert_eq!(hash_file(&path, size, direct).unwrap(), sha256);
+
+        let mmap = HashStrategy {
+            large_threshold: 0,
+            ..direct
+        };
+        assert_eq!(hash_file(&path, size, mmap).unwrap(), sha256);
+
+        // Sampling digests each region's offset and bytes, then the file size
+        let sampled = HashStrategy {
+            sample_threshold: 4096,
+            sample_size: 1024,
+            sample_points: Some(3),
+            ..direct
+        };
+        let mut expected = Vec::new();
+        for offset in [0usize, 4488, 8976] {
+            expected.extend_from_slice(&(offset as u64).to_le_bytes());
+            expected.extend_from_slice(&content[offset..offset + 1024]);
+        }
+        expected.extend_from_slice(&size.to_le_bytes());
+        let sampled_hash = hash_file(&path, size, sampled).unwrap();
+        assert_eq!(sampled_hash, HashAlgorithm::Sha256.hash_bytes(&expected));
+
+        let blake3 = HashStrategy {
+            algorithm: HashAlgorithm::Blake3,
+            ..sampled
+        };
+        assert_eq!(
+            hash_file(&path, size, blake3).unwrap(),
+            HashAlgorithm::Blake3.hash_bytes(&expected)
+        );
+    }
+
+    /// Two sparse files of `size` bytes that differ only at `offset`
+    fn files_differing_at(root: &Path, size: u64, offset: u64) -> (PathBuf, PathBuf) {
+        use std::io::{Seek, SeekFrom, Write};
+        let first = root.join("first.bin");
+        let second = root.join("second.bin");
+        fs::File::create(&first).unwrap().set_len(size).unwrap();
+        let mut file = fs::File::create(&second).unwrap();
+        file.set_len(size).unwrap();
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.write_all(b"interior change").unwrap();
+        (first, second)
+    }
+
+    #[test]
+    fn full_hash_tells_apart_files_sharing_sampled_regions() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = VERY_LARGE_FILE_THRESHOLD + 1024 * 1024;
+        let (first, second) = files_differing_at(dir.path(), size, 10 * 1024 * 1024);
+
+        let sampled = HashStrategy::default();
+        assert!(sampled.samples(size));
+        assert_eq!(
+            hash_file(&first, size, sampled).unwrap(),
+            hash_file(&second, size, sampled).unwrap()
+        );
+
+        let full = HashStrategy {
+            full_hash: true,
+            ..sampled
+        };
+        assert_ne!(
+            hash_file(&first, size, full).unwrap(),
+            hash_file(&second, size, full).unwrap()
+        );
+    }
+
+    #[test]
+    fn file_just_above_custom_sample_threshold_is_sampled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(dir.path(), "edge.bin", 8193, 5);
+        let strategy = HashStrategy {
+            large_threshold: 4096,
+            sample_threshold: 8192,
+            sample_size: 1024,
+            ..HashStrategy::default()
+        };
+        assert!(strategy.samples(8193));
+        assert!(!strategy.samples(8192));
+
+        let full = HashAlgorithm::Blake3.hash_bytes(&fs::read(&path).unwrap());
+        assert_ne!(hash_file(&path, 8193, strategy).unwrap(), full);
+        let at_threshold = HashStrategy {
+            sample_threshold: 8193,
+            ..strategy
+        };
+        assert_eq!(hash_file(&path, 8193, at_threshold).unwrap(), full);
+    }
+
+    #[test]
+    fn unreadable_files_are_reported_as_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "kept.bin", 2048, 1);
+        let gone = write_file(dir.path(), "gone.bin", 2048, 2);
+
+        let options = ScanOptions::default();
+        let errors = DashMap::new();
+        let batches =
+            discover_nested_structure(&dir.path().display().to_string(), &options, &errors);
+        // Vanishing between discovery and hashing fails the read even when running as root
+        fs::remove_file(&gone).unwrap();
+
+        let report = process_batches(
+            batches,
+            errors,
+            &options,
+            None,
+            Instant::now(),
+            chrono::Utc::now(),
+        );
+        assert_eq!(report.files.len(), 1);
+        assert!(report.files[0].filename.ends_with("kept.bin"));
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0, gone);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn following_a_symlink_to_an_ancestor_terminates() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "album/photo.jpg", 2048, 1);
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("album/loop")).unwrap();
+
+        let mut options = ScanOptions::default();
+        options.walk.follow_symlinks = true;
+        assert_eq!(discovered(dir.path(), &options), ["album/photo.jpg"]);
+    }
+
+    #[test]
+    fn max_depth_one_returns_only_first_level_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "top.bin", 2048, 1);
+        write_file(dir.path(), "one/mid.bin", 2048, 2);
+        write_file(dir.path(), "one/two/deep.bin", 2048, 3);
+
+        let mut options = ScanOptions::default();
+        options.walk.max_depth = Some(1);
+        assert_eq!(discovered(dir.path(), &options), ["top.bin"]);
+    }
+
+    #[test]
+    fn listed_file_depth_is_relative_to_their_common_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = [
+            write_file(dir.path(), "top.bin", 2048, 1),
+            write_file(dir.path(), "one/mid.bin", 2048, 2),
+            write_file(dir.path(), "one/two/deep.bin", 2048, 3),
+        ];
+        let errors = DashMap::new();
+        let mut depths: Vec<(String, usize)> =
+            group_listed_files(&paths, &FileFilter::default(), &errors)
+                .into_iter()
+                .map(|batch| {
+                    let relative = Path::new(&batch.path).strip_prefix(dir.path()).unwrap();
+                    (relative.display().to_string(), batch.depth)
+                })
+                .collect();
+        depths.sort();
+        assert_eq!(
+            depths,
+            [
+                (String::new(), 0),
+                ("one".to_string(), 1),
+                ("one/two".to_string(), 2)
+            ]
+        );
+    }
+
+    /// Scans three identical files under different names with `duplicates`
+    fn scan_identical_files(duplicates: DuplicateMode) -> Vec<FileMeta> {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.jpg", "b.jpg", "c.jpg"] {
+            write_file(dir.path(), name, 3000, 4);
+        }
+        let mut options = ScanOptions::default();
+        options.process.duplicates = duplicates;
+        scan_folder(&[dir.path().display().to_string()], &options, None).files
+    }
+
+    fn base_name(path: &str) -> &str {
+        path.rsplit('/').next().unwrap()
+    }
+
+    #[test]
+    fn skip_mode_emits_one_file_per_hash() {
+        let files = scan_identical_files(DuplicateMode::Skip);
+        assert_eq!(files.len(), 1);
+        assert!(files[0].duplicate_paths.is_empty());
+    }
+
+    #[test]
+    fn keep_all_mode_emits_every_duplicate() {
+        let files = scan_identical_files(DuplicateMode::KeepAll);
+        let names: Vec<&str> = files.iter().map(|f| base_name(&f.filename)).collect();
+        assert_eq!(names, ["a.jpg", "b.jpg", "c.jpg"]);
+        assert!(files.iter().all(|f| f.hash == files[0].hash));
+    }
+
+    #[test]
+    fn group_mode_attaches_the_other_paths() {
+        let files = scan_identical_files(DuplicateMode::Group);
+        assert_eq!(files.len(), 1);
+        let mut names: Vec<&str> = std::iter::once(files[0].filename.as_str())
+            .chain(files[0].duplicate_paths.iter().map(String::as_str))
+            .map(base_name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a.jpg", "b.jpg", "c.jpg"]);
+    }
+
+    #[test]
+    fn fresh_file_reports_a_recent_modified_time() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "new.bin", 2048, 1);
+        let report = scan_folder(
+            &[dir.path().display().to_string()],
+            &ScanOptions::default(),
+            None,
+        );
+
+        let modified = report.files[0].modified.as_deref().unwrap();
+        let modified = chrono::DateTime::parse_from_rfc3339(modified).unwrap();
+        let age = chrono::Utc::now().signed_duration_since(modified);
+        assert!(age.num_seconds().abs() < 60, "modified {} ago", age);
+    }
+
+    #[test]
+    fn extract_dimensions_fills_width_and_height_for_images() {
+        let dir = tempfile::tempdir().unwrap();
+        image::RgbImage::new(7, 3)
+            .save(dir.path().join("small.png"))
+            .unwrap();
+        let mut options = ScanOptions::default();
+        options.process.extract.dimensions = true;
+        let report = scan_folder(&[dir.path().display().to_string()], &options, None);
+
+        let file = &report.files[0];
+        assert_eq!((file.width, file.height), (Some(7), Some(3)));
+    }
+
+    #[test]
+    fn file_types_cover_audio_documents_and_archives() {
+        assert_eq!(determine_file_type_fast("image/png"), "image");
+        assert_eq!(determine_file_type_fast("video/mp4"), "video");
+        assert_eq!(determine_file_type_fast("audio/mpeg"), "audio");
+        assert_eq!(determine_file_type_fast("application/pdf"), "document");
+        assert_eq!(
+            determine_file_type_fast(
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            ),
+            "document"
+        );
+        assert_eq!(determine_file_type_fast("application/zip"), "archive");
+        assert_eq!(determine_file_type_fast("application/x-tar"), "archive");
+        assert_eq!(determine_file_type_fast("application/x-unknown"), "other");
+        assert_eq!(determine_file_type_fast("text/plain"), "other");
+    }
+
+    #[test]
+    fn empty_mime_is_other_instead_of_panicking() {
+        assert_eq!(determine_file_type_fast(""), "other");
+    }
+
+    #[test]
+    fn sniffing_classifies_a_png_named_jpg_by_content() {
+        let dir = tempfile::tempdir().unwrap();
+        image::RgbImage::new(4, 4)
+            .save_with_format(dir.path().join("photo.jpg"), image::ImageFormat::Png)
+            .unwrap();
+        let folders = [dir.path().display().to_string()];
+
+        let report = scan_folder(&folders, &ScanOptions::default(), None);
+        assert_eq!(report.files[0].mime, "image/jpeg");
+
+        let mut options = ScanOptions::default();
+        options.process.extract.sniff_content = true;
+        let report = scan_folder(&folders, &options, None);
+        assert_eq!(report.files[0].mime, "image/png");
+        assert_eq!(report.files[0].filetype, "image");
+    }
+
+    #[test]
+    fn scan_time_limit_returns_exactly_n_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..10u8 {
+            write_file(dir.path(), &format!("{}.bin", i), 2048, i);
+        }
+        let mut options = ScanOptions::default();
+        options.process.limit = Some(3);
+        options.process.order = ResultOrder::Unordered;
+        let report = scan_folder(&[dir.path().display().to_string()], &options, None);
+        assert_eq!(report.files.len(), 3);
+    }
+
+    /// Files per directory found by a plain serial `read_dir` recursion
+    fn serial_file_counts(dir: &Path, counts: &mut BTreeMap<String, usize>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                serial_file_counts(&entry.path(), counts);
+            } else {
+                *counts.entry(dir.display().to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    /// Writes a tree of `dirs` directories nested up to four deep, `files_per_dir` each
+    fn write_tree(root: &Path, dirs: usize, files_per_dir: usize) {
+        for d in 0..dirs {
+            let dir = format!("a{}/b{}/c{}/d{}", d % 3, d % 5, d % 7, d);
+            for f in 0..files_per_dir {
+                write_file(root, &format!("{}/f{}.bin", dir, f), 16, f as u8);
+            }
+            write_file(root, &format!("a{}/top{}.bin", d % 3, d), 16, 0);
+        }
+    }
+
+    #[test]
+    fn parallel_discovery_matches_a_serial_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        write_tree(dir.path(), 40, 7);
+
+        let mut expected = BTreeMap::new();
+        serial_file_counts(dir.path(), &mut expected);
+        let errors = DashMap::new();
+        let discovered: BTreeMap<String, usize> = discover_nested_structure(
+            &dir.path().display().to_string(),
+            &ScanOptions::default(),
+            &errors,
+        )
+        .into_iter()
+        .filter(|batch| !batch.files.is_empty())
+        .map(|batch| (batch.path, batch.files.len()))
+        .collect();
+        assert_eq!(discovered, expected);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    #[ignore = "benchmark; run with --ignored --nocapture"]
+    fn bench_discovery_against_a_serial_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        write_tree(dir.path(), 2000, 25);
+        let root = dir.path().display().to_string();
+
+        let started = Instant::now();
+        let mut serial = BTreeMap::new();
+        serial_file_counts(dir.path(), &mut serial);
+        let serial_time = started.elapsed();
+
+        let started = Instant::now();
+        let errors = DashMap::new();
+        let batches = discover_nested_structure(&root, &ScanOptions::default(), &errors);
+        let parallel_time = started.elapsed();
+
+        let found: usize = batches.iter().map(|batch| batch.files.len()).sum();
+        assert_eq!(found, serial.values().sum::<usize>());
+        eprintln!(
+            "{} files: serial read_dir {:?}, discover_nested_structure {:?}",
+            found, serial_time, parallel_time
+        );
+    }
+
+    #[test]
+    fn file_size_is_taken_from_discovery_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(dir.path(), "growing.bin", 2048, 1);
+        let options = options_with(FileFilter::new(&[], GlobSet::empty(), 1024, Some(4096)));
+        let errors = DashMap::new();
+        let batches =
+            discover_nested_structure(&dir.path().display().to_string(), &options, &errors);
+        let discovered = batches[0].files[0].metadata().unwrap().len();
+        assert_eq!(discovered, 2048);
+
+        // A re-stat would now see 5000 bytes, above --max-size
+        fs::write(&path, vec![1; 5000]).unwrap();
+        let report = process_batches(
+            batches,
+            errors,
+            &options,
+            None,
+            Instant::now(),
+            chrono::Utc::now(),
+        );
+        assert_eq!(report.files[0].size, discovered);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn procfs_files_fall_back_to_a_full_streamed_hash() {
+        // procfs cannot be memory-mapped and reports a size of 0
+        let path = Path::new("/proc/self/cmdline");
+        assert!(unsafe { Mmap::map(&fs::File::open(path).unwrap()) }.is_err());
+        let expected = HashAlgorithm::Sha256.digest_bytes(&fs::read(path).unwrap());
+        assert!(!expected.is_empty());
+        assert_eq!(
+            compute_digest_mmap(path, HashAlgorithm::Sha256).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn empty_files_get_a_stable_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "empty.bin", 0, 0);
+        let folders = [dir.path().display().to_string()];
+        let options = options_with(FileFilter::new(&[], GlobSet::empty(), 0, None));
+
+        let first = scan_folder(&folders, &options, None);
+        let second = scan_folder(&folders, &options, None);
+        assert_eq!(first.files.len(), 1);
+        assert_eq!(first.files[0].size, 0);
+        assert_eq!(
+            first.files[0].hash,
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        assert_eq!(second.files[0].hash, first.files[0].hash);
+    }
+
+    #[test]
+    fn more_sample_points_catch_interior_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = 10 * 1024 * 1024;
+        let sample_size = 4096;
+        // Hit by the second of five samples, but none of the default three
+        let changed_at = (size - sample_size as u64) / 4;
+        let (first, second) = files_differing_at(dir.path(), size, changed_at);
+
+        let three = HashStrategy {
+            sample_threshold: 1024 * 1024,
+            sample_size,
+            sample_points: Some(3),
+            ..HashStrategy::default()
+        };
+        assert_eq!(
+            hash_file(&first, size, three).unwrap(),
+            hash_file(&second, size, three).unwrap()
+        );
+
+        let five = HashStrategy {
+            sample_points: Some(5),
+            ..three
+        };
+        assert_ne!(
+            hash_file(&first, size, five).unwrap(),
+            hash_file(&second, size, five).unwrap()
+        );
+    }
+
+    #[test]
+    fn thread_pool_has_the_requested_size() {
+        let pool = build_thread_pool(3);
+        assert_eq!(pool.install(rayon::current_num_threads), 3);
+    }
+
+    #[test]
+    fn cpu_workload_uses_one_thread_per_physical_core() {
+        assert_eq!(determine_optimal_thread_count(Workload::Cpu, 16, 8), 8);
+        assert_eq!(determine_optimal_thread_count(Workload::Cpu, 2, 1), 1);
+        assert_eq!(determine_optimal_thread_count(Workload::Cpu, 1, 0), 1);
+    }
+
+    #[test]
+    fn io_workload_oversubscribes_with_a_floor_of_four() {
+        // min(cpu * 2, physical * 4)
+        assert_eq!(determine_optimal_thread_count(Workload::Io, 16, 8), 32);
+        assert_eq!(determine_optimal_thread_count(Workload::Io, 16, 2), 8);
+        assert_eq!(determine_optimal_thread_count(Workload::Io, 1, 1), 4);
+        assert_eq!(determine_optimal_thread_count(Workload::Auto, 16, 8), 32);
+    }
+
+    #[test]
+    fn same_file_in_two_batches_is_kept_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(dir.path(), "same.jpg", 2048, 3);
+        // Two batches holding the same path shared a composite batch/filename key
+        let batch = || DirBatch {
+            depth: 1,
+            path: dir.path().display().to_string(),
+            files: vec![DiscoveredFile {
+                path: path.clone(),
+                metadata: fs::metadata(&path),
+                discovered: 0,
+            }],
+            skipped: SkipCounts::default(),
+        };
+
+        let mut options = ScanOptions::default();
+        options.process.duplicates = DuplicateMode::KeepAll;
+        let report = process_batches(
+            vec![batch(), batch()],
+            DashMap::new(),
+            &options,
+            None,
+            Instant::now(),
+            chrono::Utc::now(),
+        );
+        assert_eq!(report.files.len(), 2);
+        assert!(report
+            .files
+            .iter()
+            .all(|file| file.full_path() == path.display().to_string()));
+    }
+
+    #[test]
+    fn discovery_order_is_depth_first_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "c/x.jpg", 2048, 1);
+        write_file(dir.path(), "b.jpg", 2048, 2);
+        write_file(dir.path(), "a/y.jpg", 2048, 3);
+        write_file(dir.path(), "a/deep/d.jpg", 2048, 4);
+        let expected: Vec<String> = ["a/deep/d.jpg", "a/y.jpg", "b.jpg", "c/x.jpg"]
+            .iter()
+            .map(|relative| dir.path().join(relative).display().to_string())
+            .collect();
+
+        for respect_gitignore in [false, true] {
+            let mut options = ScanOptions::default();
+            options.process.order = ResultOrder::Discovery;
+            options.walk.respect_gitignore = respect_gitignore;
+            // Repeated walks must agree, whatever order the threads read directories in
+            for _ in 0..5 {
+                let errors = DashMap::new();
+                let batches =
+                    discover_nested_structure(&dir.path().display().to_string(), &options, &errors);
+                let report = process_batches(
+                    batches,
+                    errors,
+                    &options,
+                    None,
+                    Instant::now(),
+                    chrono::Utc::now(),
+                );
+                let order: Vec<String> = report
+                    .files
+                    .iter()
+                    .map(|f| f.full_path().to_string())
+                    .collect();
+                assert_eq!(order, expected, "respect_gitignore: {}", respect_gitignore);
+            }
+        }
+    }
+
+    /// Walks `root` and processes it, returning the files and the bytes the
+    /// progress counter saw
+    fn process_counting_bytes(root: &Path, options: &ScanOptions) -> (Vec<FileMeta>, u64) {
+        let errors = DashMap::new();
+        let batches = discover_nested_structure(&root.display().to_string(), options, &errors);
+        let progress = Progress::new(ProgressMode::None, 0);
+        let context = ScanContext {
+            progress: &progress,
+            seen_hashes: Arc::new(DashMap::new()),
+            duplicate_paths: DashMap::new(),
+            errors: &errors,
+            options,
+            checkpoint: None,
+            collected: AtomicUsize::new(0),
+            processed: AtomicUsize::new(0),
+            duplicates_seen: AtomicUsize::new(0),
+            duplicate_bytes: AtomicU64::new(0),
+        };
+        let files = process_nested_folders_with_scope(&batches, &context);
+        assert!(errors.is_empty());
+        (files, progress.bytes_processed())
+    }
+
+    #[test]
+    fn byte_counter_sums_every_processed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "a.jpg", 2048, 1);
+        write_file(dir.path(), "copy/a.jpg", 2048, 1);
+        write_file(dir.path(), "nested/b.mp4", 5000, 2);
+        write_file(dir.path(), "nested/deeper/c.txt", 100, 3);
+
+        let (files, bytes) = process_counting_bytes(dir.path(), &ScanOptions::default());
+        // The duplicate is hashed before it is dropped, so its bytes count too
+        assert_eq!(files.len(), 3);
+        assert_eq!(bytes, 2048 + 2048 + 5000 + 100);
+    }
+
+    #[test]
+    fn xxh3_still_deduplicates_identical_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "a.jpg", 3000, 4);
+        write_file(dir.path(), "b.jpg", 3000, 4);
+        write_file(dir.path(), "c.jpg", 3000, 5);
+
+        let mut options = ScanOptions::default();
+        options.hash.algorithm = HashAlgorithm::Xxh3;
+        let report = scan_folder(&[dir.path().display().to_string()], &options, None);
+        assert_eq!(report.files.len(), 2);
+        assert_eq!(report.stats.duplicate_files, 1);
+        assert!(report.files.iter().all(|file| file.hash_algo == "xxh3"));
+    }
+
+    #[test]
+    fn filetype_breakdown_counts_and_sums_each_category() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "a.jpg", 1000, 1);
+        write_file(dir.path(), "b.png", 2000, 2);
+        write_file(dir.path(), "clip.mp4", 5000, 3);
+        write_file(dir.path(), "notes.txt", 300, 4);
+        write_file(dir.path(), "report.pdf", 700, 5);
+
+        let report = scan_folder(
+            &[dir.path().display().to_string()],
+            &ScanOptions::default(),
+            None,
+        );
+        let breakdown: Vec<(&str, usize, u64)> = report
+            .stats
+            .by_filetype
+            .iter()
+            .map(|(filetype, totals)| (filetype.as_str(), totals.count, totals.total_size))
+            .collect();
+        assert_eq!(
+            breakdown,
+            [
+                ("document", 1, 700),
+                ("image", 2, 3000),
+                ("other", 1, 300),
+                ("video", 1, 5000),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_names_are_kept_apart_and_reopenable() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        // Both display as `photo\u{FFFD}.jpg`
+        let first = dir.path().join(OsStr::from_bytes(b"photo\xff.jpg"));
+        let second = dir.path().join(OsStr::from_bytes(b"photo\xfe.jpg"));
+        fs::write(&first, [1; 2048]).unwrap();
+        fs::write(&second, [2; 2048]).unwrap();
+
+        let report = scan_folder(
+            &[dir.path().display().to_string()],
+            &ScanOptions::default(),
+            None,
+        );
+        assert!(report.errors.is_empty());
+        assert_eq!(report.files.len(), 2);
+
+        // The original bytes survive a trip through the JSON output
+        let json = serde_json::to_string(&report.files).unwrap();
+        let parsed: Vec<FileMeta> = serde_json::from_str(&json).unwrap();
+        let mut paths: Vec<PathBuf> = parsed.iter().map(FileMeta::os_path).collect();
+        paths.sort();
+        let mut expected = vec![first.clone(), second.clone()];
+        expected.sort();
+        assert_eq!(paths, expected);
+        for file in &parsed {
+            let contents = fs::read(file.os_path()).unwrap();
+            assert_eq!(HashAlgorithm::Blake3.hash_bytes(&contents), file.hash);
+        }
+    }
+
+    #[test]
+    fn rehash_on_size_match_keeps_sampling_collisions_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = 4 * 1024 * 1024;
+        let (first, second) = files_differing_at(dir.path(), size, size / 4);
+        let sampled = HashStrategy {
+            sample_threshold: 1024 * 1024,
+            sample_points: Some(3),
+            ..HashStrategy::default()
+        };
+        let scan = |hash: HashStrategy| {
+            let options = ScanOptions {
+                hash,
+                ..ScanOptions::default()
+            };
+            scan_folder(&[dir.path().display().to_string()], &options, None)
+        };
+
+        // Without confirmation the collision passes as a duplicate
+        assert_eq!(scan(sampled).files.len(), 1);
+
+        let confirmed = scan(HashStrategy {
+            rehash_on_size_match: true,
+            ..sampled
+        });
+        let mut hashes: Vec<(String, String)> = confirmed
+            .files
+            .iter()
+            .map(|file| (file.full_path().to_string(), file.hash.clone()))
+            .collect();
+        hashes.sort();
+        assert_eq!(hashes.len(), 2);
+        assert_ne!(hashes[0].1, hashes[1].1);
+        assert_eq!(hashes[0].0, first.display().to_string());
+        assert_eq!(hashes[1].0, second.display().to_string());
+        assert_eq!(confirmed.stats.duplicate_files, 0);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn sampled_collisions_across_directories_are_all_confirmed() {
+        use std::io::{Seek, SeekFrom, Write};
+        use std::os::unix::ffi::OsStrExt;
+        let dir = tempfile::tempdir().unwrap();
+        let size = 4 * 1024 * 1024;
+        // One file per directory so they are hashed in different batches; the
+        // non-UTF-8 names can only be reopened from their raw bytes
+        for i in 0..8u8 {
+            let folder = dir.path().join(format!("d{}", i));
+            fs::create_dir(&folder).unwrap();
+            let name = [b'f', 0xff, b'0' + i];
+            let mut file =
+                fs::File::create(folder.join(std::ffi::OsStr::from_bytes(&name))).unwrap();
+            file.set_len(size).unwrap();
+            file.seek(SeekFrom::Start(size / 4)).unwrap();
+            file.write_all(&[i + 1]).unwrap();
+        }
+        let options = ScanOptions {
+            hash: HashStrategy {
+                sample_threshold: 1024 * 1024,
+                sample_points: Some(3),
+                rehash_on_size_match: true,
+                ..HashStrategy::default()
+            },
+            ..ScanOptions::default()
+        };
+
+        let report = scan_folder(&[dir.path().display().to_string()], &options, None);
+        let hashes: std::collections::HashSet<&str> =
+            report.files.iter().map(|file| file.hash.as_str()).collect();
+        assert_eq!(report.files.len(), 8);
+        assert_eq!(hashes.len(), 8);
+        assert_eq!(report.stats.duplicate_files, 0);
+    }
+
+    #[test]
+    fn exclude_from_file_applies_patterns_negations_and_inline_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        for name in [
+            "a.jpg",
+            "draft.tmp",
+            "keep.tmp",
+            "old.bak",
+            "cache/thumb.jpg",
+            "nested/b.tmp",
+        ] {
+            write_file(&root, name, 10, 1);
+        }
+        let ignore_file = dir.path().join("shared.ignore");
+        fs::write(&ignore_file, "# shared list\n*.tmp\ncache/\n!keep.tmp\n").unwrap();
+
+        let filter = FileFilter::new(&[], excludes(&["*.bak"]), 0, None)
+            .with_ignore(build_ignore_file(&ignore_file).unwrap());
+        assert_eq!(
+            discovered(&root, &options_with(filter)),
+            ["a.jpg", "keep.tmp"]
+        );
+    }
+
+    #[test]
+    fn respect_gitignore_prunes_ignored_subdirectories() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "a.jpg", 10, 1);
+        write_file(dir.path(), "build/out.jpg", 10, 2);
+        write_file(dir.path(), "nested/b.jpg", 10, 3);
+        write_file(dir.path(), "nested/scratch.jpg", 10, 4);
+        fs::write(dir.path().join(".gitignore"), "build/\n").unwrap();
+        fs::write(dir.path().join("nested/.gitignore"), "scratch.jpg\n").unwrap();
+
+        let mut options = ScanOptions::default();
+        assert_eq!(
+            discovered(dir.path(), &options),
+            [
+                "a.jpg",
+                "build/out.jpg",
+                "nested/b.jpg",
+                "nested/scratch.jpg"
+            ]
+        );
+        options.walk.respect_gitignore = true;
+        assert_eq!(discovered(dir.path(), &options), ["a.jpg", "nested/b.jpg"]);
+    }
+
+    #[test]
+    fn no_hash_reads_no_file_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "a.jpg", 2048, 1);
+        write_file(dir.path(), "copy-of-a.jpg", 2048, 1);
+        write_file(dir.path(), "nested/b.mp4", 4096, 2);
+
+        let mut options = ScanOptions::default();
+        options.hash.no_hash = true;
+        let errors = DashMap::new();
+        let batches =
+            discover_nested_structure(&dir.path().display().to_string(), &options, &errors);
+        // Any read after discovery would now fail, so a clean scan proves none happened
+        fs::remove_dir_all(dir.path()).unwrap();
+
+        let report = process_batches(
+            batches,
+            errors,
+            &options,
+            None,
+            Instant::now(),
+            chrono::Utc::now(),
+        );
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        // Identical contents cannot be told apart, so nothing is deduplicated
+        let files: Vec<(&str, u64, &str, &str)> = report
+            .files
+            .iter()
+            .map(|f| {
+                (
+                    base_name(&f.filename),
+                    f.size,
+                    f.hash.as_str(),
+                    f.mime.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("a.jpg", 2048, "", "image/jpeg"),
+                ("copy-of-a.jpg", 2048, "", "image/jpeg"),
+                ("b.mp4", 4096, "", "video/mp4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn min_files_drops_directories_with_too_few_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..5 {
+            write_file(dir.path(), &format!("album/{}.jpg", i), 10, i);
+        }
+        write_file(dir.path(), "stray/only.jpg", 10, 9);
+        // Counted per directory, so a sparse subfolder of the album is dropped too
+        write_file(dir.path(), "album/extra/one.jpg", 10, 8);
+
+        let mut options = ScanOptions::default();
+        options.walk.min_files = 2;
+        assert_eq!(
+            discovered(dir.path(), &options),
+            [
+                "album/0.jpg",
+                "album/1.jpg",
+                "album/2.jpg",
+                "album/3.jpg",
+                "album/4.jpg"
+            ]
+        );
+    }
+
+    #[test]
+    fn hash_encoding_is_consistent_across_hashing_paths() {
+        use base64::Engine;
+
+        let dir = tempfile::tempdir().unwrap();
+        let hex = HashStrategy {
+            large_threshold: 4096,
+            sample_threshold: 64 * 1024,
+            ..HashStrategy::default()
+        };
+        let base64 = HashStrategy {
+            encoding: HashEncoding::Base64,
+            ..hex
+        };
+        // Read directly, memory-mapped, and sampled
+        for (name, size) in [
+            ("small.bin", 1000),
+            ("mapped.bin", 8000),
+            ("sampled.bin", 100_000),
+        ] {
+            let path = write_file(dir.path(), name, size, 7);
+            let size = size as u64;
+            let hex_hash = hash_file(&path, size, hex).unwrap();
+            let base64_hash = hash_file(&path, size, base64).unwrap();
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(&base64_hash)
+                .unwrap();
+            assert_eq!(HashEncoding::Hex.encode(&decoded), hex_hash, "{}", name);
+        }
+    }
+
+    #[test]
+    fn max_files_stops_hashing_after_n_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..200 {
+            write_file(dir.path(), &format!("d{}/f{}.bin", i % 20, i), 100, i as u8);
+        }
+
+        let mut options = ScanOptions::default();
+        options.process.max_files = Some(15);
+        let (files, bytes) = process_counting_bytes(dir.path(), &options);
+        // Every file is unique, so none of the 15 was dropped as a duplicate
+        assert_eq!(files.len(), 15);
+        // The other 185 were never hashed
+        assert_eq!(bytes, 15 * 100);
+    }
+
+    #[test]
+    fn directory_of_small_files_is_reported_as_skipped_for_size() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..4 {
+            write_file(dir.path(), &format!("thumbs/{}.jpg", i), 500, 1);
+        }
+        write_file(dir.path(), "thumbs/.hidden.jpg", 5000, 1);
+        write_file(dir.path(), "photos/big.jpg", 5000, 2);
+
+        let mut options = options_with(FileFilter::new(&[], GlobSet::empty(), 1024, None));
+        options.walk.report_skipped = true;
+        let report = scan_folder(&[dir.path().display().to_string()], &options, None);
+
+        assert_eq!(report.files.len(), 1);
+        let skipped: Vec<(String, String)> = report
+            .skipped_dirs
+            .iter()
+            .map(|skipped| (skipped.path.clone(), skipped.skipped.describe()))
+            .collect();
+        assert_eq!(
+            skipped,
+            [(
+                dir.path().join("thumbs").display().to_string(),
+                "4 below --min-size, 1 hidden".to_string()
+            )]
+        );
+        assert_eq!(report.skipped_dirs[0].skipped.below_min_size, 4);
+    }
+
+    #[test]
+    fn directories_below_min_files_are_reported_as_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..3 {
+            write_file(dir.path(), &format!("album/{}.jpg", i), 2048, i);
+        }
+        write_file(dir.path(), "stray/only.jpg", 2048, 9);
+
+        let mut options = ScanOptions::default();
+        options.walk.min_files = 2;
+        options.walk.report_skipped = true;
+        let report = scan_folder(&[dir.path().display().to_string()], &options, None);
+
+        assert_eq!(report.files.len(), 3);
+        let skipped: Vec<(String, String)> = report
+            .skipped_dirs
+            .iter()
+            .map(|skipped| (skipped.path.clone(), skipped.skipped.describe()))
+            .collect();
+        assert_eq!(
+            skipped,
+            [(
+                dir.path().join("stray").display().to_string(),
+                "1 in a directory with fewer than --min-files files".to_string()
+            )]
+        );
+    }
+}
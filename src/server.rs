@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Deserialize;
+
+use crate::checkpoint::Checkpoint;
+use crate::progress::ProgressMode;
+use crate::scanner::{scan_folder, ScanOptions};
+use crate::status;
+
+/// Most hashes kept between requests; past it, arbitrary entries are evicted
+const CACHE_CAPACITY: usize = 250_000;
+
+struct ServerState {
+    options: ScanOptions,
+    /// Canonical folders given at startup; only paths inside them are scanned
+    roots: Vec<PathBuf>,
+    /// Hashes reused by later scans while a file's size and mtime are unchanged
+    cache: Checkpoint,
+}
+
+#[derive(Deserialize)]
+struct ScanQuery {
+    path: Option<String>,
+}
+
+/// Serves `GET /scan?path=<folder>` on `addr` until the process is stopped
+///
+/// Each request walks the folder with `options` and answers with the scan
+/// report as JSON (`files`, `stats`, `errors`, as written by `--report`).
+/// Only `roots` and the folders inside them can be scanned; any other path is
+/// refused with 403.
+pub fn serve(addr: &str, roots: &[String], options: ScanOptions) -> std::io::Result<()> {
+    let mut options = options;
+    // Progress bars from concurrent requests would interleave on the server's stderr
+    options.process.progress = ProgressMode::None;
+    let roots = roots
+        .iter()
+        .map(|root| {
+            Path::new(root)
+                .canonicalize()
+                .map_err(|e| std::io::Error::new(e.kind(), format!("cannot serve {}: {}", root, e)))
+        })
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    let state = Arc::new(ServerState {
+        options,
+        roots,
+        cache: Checkpoint::in_memory(CACHE_CAPACITY),
+    });
+    let app = Router::new().route("/scan", get(scan)).with_state(state);
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        status!(
+            "🌐 Serving scans on http://{}/scan?path=...",
+            listener.local_addr()?
+        );
+        axum::serve(listener, app).await
+    })
+}
+
+async fn scan(State(state): State<Arc<ServerState>>, Query(query): Query<ScanQuery>) -> Response {
+    let Some(path) = query.path else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "missing `path` query parameter".to_string(),
+        );
+    };
+    if !Path::new(&path).is_dir() {
+        return error_response(StatusCode::NOT_FOUND, format!("not a directory: {}", path));
+    }
+    // Canonical, so `..` and symlinks cannot step outside the served folders
+    let inside_roots = Path::new(&path)
+        .canonicalize()
+        .is_ok_and(|path| state.roots.iter().any(|root| path.starts_with(root)));
+    if !inside_roots {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            format!("not inside a served folder: {}", path),
+        );
+    }
+
+    // Hashing is blocking work; keep it off the async worker threads
+    let result = tokio::task::spawn_blocking(move || {
+        scan_folder(&[path], &state.options, Some(&state.cache))
+    })
+    .await;
+    match result {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("scan failed: {}", e),
+        ),
+    }
+}
+
+fn error_response(status: StatusCode, message: String) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+
+use owo_colors::AnsiColors;
+use serde::Serialize;
+
+use crate::output::{self, ConsoleStyle, OutputOptions};
+use crate::uploader::FileMeta;
+
+/// Two files sharing content-defined chunks
+#[derive(Serialize)]
+pub struct SimilarPair {
+    pub paths: [String; 2],
+    /// Distinct chunks present in both files
+    pub shared_chunks: usize,
+    /// Shared chunks over the distinct chunks of the file with fewer of them,
+    /// so a file and an appended-to copy of it score close to 1
+    pub ratio: f64,
+}
+
+/// Shared distinct chunks of two chunk lists and their overlap ratio (see
+/// [`SimilarPair::ratio`]); 0 when either list is empty
+pub fn chunk_overlap(a: &[String], b: &[String]) -> (usize, f64) {
+    let a: HashSet<&str> = a.iter().map(String::as_str).collect();
+    let b: HashSet<&str> = b.iter().map(String::as_str).collect();
+    let shared = a.intersection(&b).count();
+    (shared, overlap_ratio(shared, a.len(), b.len()))
+}
+
+fn overlap_ratio(shared: usize, a: usize, b: usize) -> f64 {
+    match a.min(b) {
+        0 => 0.0,
+        smaller => shared as f64 / smaller as f64,
+    }
+}
+
+/// Pairs of files whose chunk overlap is at least `min_ratio`, most similar first
+///
+/// Only files that share a chunk are compared, so unrelated files cost nothing
+/// beyond indexing their chunks.
+pub fn find_similar_pairs(files: &[FileMeta], min_ratio: f64) -> Vec<SimilarPair> {
+    let chunk_sets: Vec<HashSet<&str>> = files
+        .iter()
+        .map(|file| file.chunks.iter().map(String::as_str).collect())
+        .collect();
+    let mut by_chunk: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, chunks) in chunk_sets.iter().enumerate() {
+        for chunk in chunks {
+            by_chunk.entry(chunk).or_default().push(index);
+        }
+    }
+
+    let mut shared: HashMap<(usize, usize), usize> = HashMap::new();
+    for indices in by_chunk.values() {
+        for (i, &a) in indices.iter().enumerate() {
+            for &b in &indices[i + 1..] {
+                *shared.entry((a, b)).or_default() += 1;
+            }
+        }
+    }
+
+    let mut pairs: Vec<SimilarPair> = shared
+        .into_iter()
+        .filter_map(|((a, b), shared_chunks)| {
+            let ratio = overlap_ratio(shared_chunks, chunk_sets[a].len(), chunk_sets[b].len());
+            let mut paths = [
+                files[a].full_path().to_string(),
+                files[b].full_path().to_string(),
+            ];
+            paths.sort();
+            (ratio >= min_ratio).then_some(SimilarPair {
+                paths,
+                shared_chunks,
+                ratio,
+            })
+        })
+        .collect();
+
+    pairs.sort_by(|a, b| {
+        b.ratio
+            .total_cmp(&a.ratio)
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+    pairs
+}
+
+/// Renders the report as human-readable console lines
+pub fn generate_console(pairs: &[SimilarPair], style: ConsoleStyle) -> String {
+    let mut output = String::new();
+    for pair in pairs {
+        output.push_str(&format!(
+            "{}{} similar ({} shared chunks)\n",
+            style.icon("🧩"),
+            style.paint(format!("{:.1}%", pair.ratio * 100.0), AnsiColors::Yellow),
+            pair.shared_chunks
+        ));
+        for path in &pair.paths {
+            output.push_str(&format!("   {}\n", path));
+        }
+    }
+    output
+}
+
+/// Renders the report as TOML with one `[[pairs]]` table per pair
+pub fn generate_toml(pairs: &[SimilarPair]) -> String {
+    #[derive(Serialize)]
+    struct Document<'a> {
+        pairs: &'a [SimilarPair],
+    }
+    toml::to_string(&Document { pairs }).expect("Failed to serialize to TOML")
+}
+
+/// Renders the report as CSV with one row per pair
+pub fn generate_csv(pairs: &[SimilarPair], options: &OutputOptions) -> String {
+    let mut writer = output::csv_writer(options);
+    if options.csv_header {
+        writer
+            .write_record(["ratio", "shared_chunks", "path_a", "path_b"])
+            .expect("Failed to serialize to CSV");
+    }
+    for pair in pairs {
+        writer
+            .write_record([
+                pair.ratio.to_string().as_str(),
+                &pair.shared_chunks.to_string(),
+                &pair.paths[0],
+                &pair.paths[1],
+            ])
+            .expect("Failed to serialize to CSV");
+    }
+    output::finish_csv(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{scan_folder, ScanOptions};
+
+    /// Deterministic incompressible bytes, so FastCDC finds varied boundaries
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn appended_file_shares_most_chunks_with_the_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = noise(2 * 1024 * 1024, 1);
+        let mut appended = original.clone();
+        appended.extend(noise(300 * 1024, 2));
+        std::fs::write(dir.path().join("original.log"), &original).unwrap();
+        std::fs::write(dir.path().join("appended.log"), &appended).unwrap();
+        std::fs::write(dir.path().join("unrelated.log"), noise(2 * 1024 * 1024, 3)).unwrap();
+
+        let mut options = ScanOptions::default();
+        options.hash.chunk_hashes = true;
+        let report = scan_folder(&[dir.path().display().to_string()], &options, None);
+        assert!(report.files.iter().all(|file| file.chunks.len() > 10));
+
+        let pairs = find_similar_pairs(&report.files, 0.5);
+        assert_eq!(pairs.len(), 1);
+        let names = pairs[0]
+            .paths
+            .clone()
+            .map(|path| path.rsplit('/').next().unwrap().to_string());
+        assert_eq!(names, ["appended.log", "original.log"]);
+        assert!(pairs[0].ratio > 0.9, "ratio {}", pairs[0].ratio);
+    }
+
+    #[test]
+    fn overlap_ratio_is_relative_to_the_smaller_file() {
+        let chunks = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            chunk_overlap(&chunks(&["a", "b"]), &chunks(&["a", "b", "c", "d"])),
+            (2, 1.0)
+        );
+        assert_eq!(
+            chunk_overlap(&chunks(&["a", "b"]), &chunks(&["b", "c"])),
+            (1, 0.5)
+        );
+        assert_eq!(chunk_overlap(&[], &chunks(&["a"])), (0, 0.0));
+    }
+}
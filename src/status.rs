@@ -0,0 +1,89 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static EMOJI: AtomicBool = AtomicBool::new(true);
+
+/// Silences `status!` messages for the rest of the run (`--quiet`)
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Strips emoji from `status!` messages for the rest of the run (`--no-emoji`)
+pub fn set_emoji(emoji: bool) {
+    EMOJI.store(emoji, Ordering::Relaxed);
+}
+
+pub fn emoji_enabled() -> bool {
+    EMOJI.load(Ordering::Relaxed)
+}
+
+/// Whether the `NO_COLOR` convention (any non-empty value) asks for plain output
+pub fn no_color_env() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// Removes emoji, along with the spaces that separated them from the text
+pub fn strip_emoji(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut after_emoji = false;
+    for c in text.chars() {
+        if is_emoji(c) {
+            after_emoji = true;
+        } else if !(after_emoji && c == ' ') {
+            after_emoji = false;
+            output.push(c);
+        }
+    }
+    output
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c,
+        '\u{2300}'..='\u{23FF}'
+            | '\u{2600}'..='\u{27BF}'
+            | '\u{2B00}'..='\u{2BFF}'
+            | '\u{1F000}'..='\u{1FAFF}'
+            | '\u{200D}'
+            | '\u{FE0F}'
+    )
+}
+
+/// `text` as is, or without emoji under `--no-emoji`
+pub fn decorate(text: &str) -> Cow<'_, str> {
+    if emoji_enabled() {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(strip_emoji(text))
+    }
+}
+
+#[doc(hidden)]
+pub fn print(args: fmt::Arguments) {
+    eprintln!("{}", decorate(&args.to_string()));
+}
+
+/// Prints an informational message to stderr unless `--quiet` is set; stdout
+/// is reserved for the output payload so it can be piped
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::status::is_quiet() {
+            $crate::status::print(format_args!($($arg)*));
+        }
+    };
+}
+
+/// Prints a warning to stderr even under `--quiet`
+#[macro_export]
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::status::print(format_args!($($arg)*))
+    };
+}
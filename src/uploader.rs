@@ -1,89 +1,1585 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use indicatif::{ProgressBar, ProgressStyle};
+use log::{error, warn};
 use reqwest::blocking::Client;
-use serde::Serialize;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::Semaphore;
 
-#[derive(Serialize, Clone)]
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use crate::hash::{HashAlgorithm, HashEncoding};
+use crate::manifest::{self, ManifestEntry};
+use crate::status;
+
+/// Integrity header carrying a digest of the request body
+const CONTENT_HASH: HeaderName = HeaderName::from_static("x-content-hash");
+
+/// Key derived from the request body so the server can drop retried batches
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct FileMeta {
     pub filename: String,
+    /// Full path when `filename` holds only the base name (`--path-style split`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Base64 of the full path's raw bytes, only when the name is not valid
+    /// UTF-8 and `filename` had to replace some of them (Unix only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_bytes: Option<String>,
     pub folder: String,
     pub size: u64,
     pub mime: String,
     pub hash: String,
-    pub filetype: String, // image / video / other
+    #[serde(default)]
+    pub hash_algo: String,
+    pub filetype: String, // image / video / audio / document / archive / other
+    /// Other paths with the same hash (only filled by `--duplicates group`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicate_paths: Vec<String>,
+    /// Last modification time (RFC 3339), when the platform reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    /// Creation time (RFC 3339), when the platform reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// Image width in pixels (only with `--extract-dimensions`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    /// Image height in pixels (only with `--extract-dimensions`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Video duration in seconds (only with `--extract-media-info`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<f64>,
+    /// Video codec name (only with `--extract-media-info`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+    /// Camera manufacturer from EXIF (only with `--exif`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera_make: Option<String>,
+    /// Camera model from EXIF (only with `--exif`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera_model: Option<String>,
+    /// Capture time from EXIF, e.g. `2024-05-04T13:02:01+02:00` (only with `--exif`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub taken_at: Option<String>,
+    /// GPS latitude in decimal degrees, negative for south (only with `--exif`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    /// GPS longitude in decimal degrees, negative for west (only with `--exif`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// Content-defined chunk hashes in file order (only with `--chunk-hashes`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<String>,
+}
+
+impl FileMeta {
+    /// Full path of the file regardless of the path style
+    pub fn full_path(&self) -> &str {
+        self.path.as_deref().unwrap_or(&self.filename)
+    }
+
+    /// Path that reopens the file, restoring non-UTF-8 names from `path_bytes`
+    pub fn os_path(&self) -> PathBuf {
+        #[cfg(unix)]
+        if let Some(bytes) = self
+            .path_bytes
+            .as_deref()
+            .and_then(|encoded| BASE64.decode(encoded).ok())
+        {
+            use std::os::unix::ffi::OsStringExt;
+            return PathBuf::from(std::ffi::OsString::from_vec(bytes));
+        }
+        PathBuf::from(self.full_path())
+    }
+}
+
+#[cfg(test)]
+impl FileMeta {
+    /// A 1234-byte JPEG at `path` in `/photos`, with a BLAKE3 hash of the path
+    /// so distinct paths never look like duplicates
+    pub(crate) fn for_test(path: &str) -> Self {
+        Self {
+            filename: path.to_string(),
+            path: None,
+            path_bytes: None,
+            folder: "/photos".to_string(),
+            size: 1234,
+            mime: "image/jpeg".to_string(),
+            hash: HashAlgorithm::Blake3.hash_bytes(path.as_bytes()),
+            hash_algo: "blake3".to_string(),
+            filetype: "image".to_string(),
+            duplicate_paths: Vec::new(),
+            modified: None,
+            created: None,
+            width: None,
+            height: None,
+            duration_secs: None,
+            codec: None,
+            camera_make: None,
+            camera_model: None,
+            taken_at: None,
+            latitude: None,
+            longitude: None,
+            chunks: Vec::new(),
+        }
+    }
+
+    /// `count` test files named `/photos/0.jpg`, `/photos/1.jpg`, and so on
+    pub(crate) fn test_files(count: usize) -> Vec<Self> {
+        (0..count)
+            .map(|i| Self::for_test(&format!("/photos/{}.jpg", i)))
+            .collect()
+    }
+
+    pub(crate) fn with_size(mut self, size: u64) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub(crate) fn with_hash(mut self, hash: &str) -> Self {
+        self.hash = hash.to_string();
+        self
+    }
+}
+
+/// Encodes `path` for `FileMeta::path_bytes` when its display form would be lossy
+pub fn encode_path_bytes(path: &Path) -> Option<String> {
+    if path.to_str().is_some() {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Some(BASE64.encode(path.as_os_str().as_bytes()))
+    }
+    // Other platforms have no byte form of a path to preserve
+    #[cfg(not(unix))]
+    {
+        None
+    }
 }
 
 #[derive(Serialize)]
 pub struct UploadRequest {
     pub files: Vec<FileMeta>,
+    /// When the scan began (RFC 3339)
     pub scan_timestamp: String,
+    /// When this upload began (RFC 3339)
+    pub upload_timestamp: String,
+    /// Files and bytes across every batch of the upload
     pub total_files: usize,
     pub total_size: u64,
+    /// Files and bytes in this batch alone
+    pub batch_files: usize,
+    pub batch_size: u64,
+    pub batch_index: usize,
+    pub batch_total: usize,
+}
+
+/// Layout of each request body
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UploadFormat {
+    /// `UploadRequest` object with the files plus scan and batch totals
+    #[default]
+    Wrapped,
+    /// Bare JSON array of the batch's files
+    Array,
+    /// One JSON object per file per line, for streaming ingest
+    Ndjson,
+}
+
+/// How `ndjson` records are delimited within a request body
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Framing {
+    /// Each record is followed by `\n`
+    #[default]
+    Newline,
+    /// Each record is preceded by its byte length as a 4-byte big-endian integer,
+    /// and the frames are streamed with chunked transfer encoding
+    LengthPrefixed,
 }
 
+impl UploadFormat {
+    fn content_type(self, framing: Framing) -> &'static str {
+        match (self, framing) {
+            (UploadFormat::Wrapped | UploadFormat::Array, _) => "application/json",
+            (UploadFormat::Ndjson, Framing::Newline) => "application/x-ndjson",
+            (UploadFormat::Ndjson, Framing::LengthPrefixed) => "application/octet-stream",
+        }
+    }
+}
+
+/// Structured API reply, e.g. `{"accepted": 10, "rejected": [...]}`
+#[derive(Deserialize, Debug, Default)]
+pub struct UploadResponse {
+    #[serde(default)]
+    pub accepted: usize,
+    #[serde(default)]
+    pub rejected: Vec<RejectedFile>,
+}
+
+/// A file the API refused, given either as a bare path or with a reason
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum RejectedFile {
+    Path(String),
+    Detailed {
+        #[serde(alias = "filename")]
+        path: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+}
+
+impl std::fmt::Display for RejectedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RejectedFile::Path(path) => write!(f, "{}", path),
+            RejectedFile::Detailed { path, reason: None } => write!(f, "{}", path),
+            RejectedFile::Detailed {
+                path,
+                reason: Some(reason),
+            } => write!(f, "{} ({})", path, reason),
+        }
+    }
+}
+
+/// A batch that reached the API and got a success status
+struct Delivered {
+    status: StatusCode,
+    response: Option<UploadResponse>,
+}
+
+/// A batch that could not be delivered, with the final HTTP status when one was received
+#[derive(Debug)]
+struct BatchError {
+    status: Option<StatusCode>,
+    message: String,
+}
+
+impl BatchError {
+    /// Failure before or without any HTTP response
+    fn other(error: impl std::fmt::Display) -> Self {
+        Self {
+            status: None,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+type BatchResult = Result<Delivered, BatchError>;
+
+/// Parses a success body; non-JSON or differently shaped bodies yield `None`
+fn parse_response(text: &str) -> Option<UploadResponse> {
+    serde_json::from_str(text).ok()
+}
+
+/// Settings for how metadata is sent to the API
+#[derive(Clone, Debug)]
+pub struct UploadOptions {
+    /// Extra attempts after the first for connection errors and 5xx responses
+    pub retries: u32,
+    /// Delay before the first retry; doubled for each subsequent one
+    pub retry_delay: Duration,
+    /// Maximum files per request; `None` sends everything in one request
+    pub batch_size: Option<usize>,
+    /// Sent as `Authorization: Bearer <token>`
+    pub auth_token: Option<String>,
+    /// Extra request headers, validated when parsed from the command line
+    pub headers: Vec<(String, String)>,
+    /// Gzip the request body and send `Content-Encoding: gzip`
+    pub compress: bool,
+    /// Total per-request timeout; `None` waits indefinitely
+    pub timeout: Option<Duration>,
+    /// Timeout for establishing the connection; `None` uses no limit
+    pub connect_timeout: Option<Duration>,
+    /// Batches in flight at once; above 1 the async uploader is required
+    pub concurrent_uploads: usize,
+    /// Fail the upload when the API rejects any file
+    pub strict: bool,
+    /// Write each file's hash and batch HTTP status here after uploading
+    pub manifest: Option<PathBuf>,
+    pub format: UploadFormat,
+    /// Record delimiting for `UploadFormat::Ndjson`; ignored by the JSON layouts
+    pub framing: Framing,
+    /// Send `X-Content-Hash: <algorithm>=<hex>` computed over the exact body sent
+    pub content_hash: Option<HashAlgorithm>,
+    /// Start of the scan that produced the files; `None` uses the upload time
+    pub scan_timestamp: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            retry_delay: Duration::from_millis(500),
+            batch_size: None,
+            auth_token: None,
+            headers: Vec::new(),
+            compress: false,
+            timeout: Some(Duration::from_secs(30)),
+            connect_timeout: None,
+            concurrent_uploads: 1,
+            strict: false,
+            manifest: None,
+            format: UploadFormat::default(),
+            framing: Framing::default(),
+            content_hash: None,
+            scan_timestamp: None,
+        }
+    }
+}
+
+/// Uploads metadata using the blocking client, one batch at a time
 pub fn upload_metadata(
     api_url: &str,
     files: &[FileMeta],
+    options: &UploadOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
     if files.is_empty() {
-        println!("No files to upload");
+        status!("No files to upload");
         return Ok(());
     }
 
-    println!("📤 Preparing to upload {} files to API...", files.len());
+    status!("📤 Preparing to upload {} files to API...", files.len());
 
-    let client = Client::new();
-    let total_size: u64 = files.iter().map(|f| f.size).sum();
+    let client = build_client(options)?;
+    let requests = build_requests(files, options);
+    let batch_total = requests.len();
+    let progress_bar = upload_progress_bar();
 
-    let upload_request = UploadRequest {
-        files: files.to_vec(),
-        scan_timestamp: chrono::Utc::now().to_rfc3339(),
-        total_files: files.len(),
-        total_size,
-    };
+    let mut outcomes = Vec::with_capacity(batch_total);
+    for upload_request in &requests {
+        let result = send_with_retry(&client, api_url, upload_request, options, &progress_bar);
+        if let Err(e) = &result {
+            progress_bar.suspend(|| {
+                error!(
+                    "Batch {}/{} failed: {}",
+                    upload_request.batch_index + 1,
+                    batch_total,
+                    e
+                )
+            });
+        }
+        outcomes.push((upload_request, result));
+    }
+
+    progress_bar.finish_and_clear();
+    finish_upload(files, &outcomes, options)
+}
+
+/// Uploads metadata using the async client with up to `concurrent_uploads` batches in flight
+pub async fn upload_metadata_async(
+    api_url: &str,
+    files: &[FileMeta],
+    options: &UploadOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if files.is_empty() {
+        status!("No files to upload");
+        return Ok(());
+    }
 
-    // Create progress bar for upload
+    status!(
+        "📤 Preparing to upload {} files to API ({} concurrent)...",
+        files.len(),
+        options.concurrent_uploads
+    );
+
+    let client = build_async_client(options)?;
+    let requests: Vec<Arc<UploadRequest>> = build_requests(files, options)
+        .into_iter()
+        .map(Arc::new)
+        .collect();
+    let batch_total = requests.len();
+    let progress_bar = upload_progress_bar();
+    progress_bar.set_message(format!("Uploading {} batches...", batch_total));
+
+    let semaphore = Arc::new(Semaphore::new(options.concurrent_uploads.max(1)));
+    let api_url = Arc::new(api_url.to_string());
+    let options = Arc::new(options.clone());
+
+    let mut tasks = Vec::with_capacity(batch_total);
+    for upload_request in &requests {
+        let upload_request = Arc::clone(upload_request);
+        let client = client.clone();
+        let semaphore = Arc::clone(&semaphore);
+        let api_url = Arc::clone(&api_url);
+        let options = Arc::clone(&options);
+        let progress_bar = progress_bar.clone();
+        tasks.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await.map_err(BatchError::other)?;
+            send_with_retry_async(&client, &api_url, &upload_request, &options, &progress_bar).await
+        }));
+    }
+
+    let mut outcomes = Vec::with_capacity(batch_total);
+    for (upload_request, task) in requests.iter().zip(tasks) {
+        let result = task.await.map_err(BatchError::other).and_then(|r| r);
+        if let Err(e) = &result {
+            progress_bar.suspend(|| {
+                error!(
+                    "Batch {}/{} failed: {}",
+                    upload_request.batch_index + 1,
+                    batch_total,
+                    e
+                )
+            });
+        }
+        outcomes.push((upload_request.as_ref(), result));
+    }
+
+    progress_bar.finish_and_clear();
+    finish_upload(files, &outcomes, &options)
+}
+
+/// Splits files into request payloads that share the scan and upload timestamps;
+/// an empty list yields no requests, as there is nothing to send
+fn build_requests(files: &[FileMeta], options: &UploadOptions) -> Vec<UploadRequest> {
+    // Every batch shares both timestamps so the server can correlate them
+    let upload_timestamp = chrono::Utc::now();
+    let scan_timestamp = options
+        .scan_timestamp
+        .unwrap_or(upload_timestamp)
+        .to_rfc3339();
+    let upload_timestamp = upload_timestamp.to_rfc3339();
+    let batch_size = options.batch_size.unwrap_or(files.len()).max(1);
+    let batch_total = files.len().div_ceil(batch_size);
+    let total_size = files.iter().map(|f| f.size).sum();
+
+    files
+        .chunks(batch_size)
+        .enumerate()
+        .map(|(batch_index, batch)| UploadRequest {
+            files: batch.to_vec(),
+            scan_timestamp: scan_timestamp.clone(),
+            upload_timestamp: upload_timestamp.clone(),
+            total_files: files.len(),
+            total_size,
+            batch_files: batch.len(),
+            batch_size: batch.iter().map(|f| f.size).sum(),
+            batch_index,
+            batch_total,
+        })
+        .collect()
+}
+
+/// Spinner shown while uploading
+fn upload_progress_bar() -> ProgressBar {
     let progress_bar = ProgressBar::new(1);
     progress_bar.set_style(
         ProgressStyle::with_template("[{elapsed_precise}] {spinner:.green} {msg}")
             .unwrap()
             .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "),
     );
-    progress_bar.set_message("Uploading metadata...");
+    progress_bar
+}
+
+/// Writes the manifest if requested, then reports the combined batch outcomes
+fn finish_upload(
+    files: &[FileMeta],
+    outcomes: &[(&UploadRequest, BatchResult)],
+    options: &UploadOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(manifest_path) = &options.manifest {
+        let entries: Vec<ManifestEntry> = outcomes
+            .iter()
+            .flat_map(|(upload_request, result)| {
+                let status = match result {
+                    Ok(delivered) => Some(delivered.status),
+                    Err(e) => e.status,
+                };
+                upload_request.files.iter().map(move |file| ManifestEntry {
+                    hash: file.hash.clone(),
+                    filename: file.full_path().to_string(),
+                    batch_index: upload_request.batch_index,
+                    status: status.map(|status| status.as_u16()),
+                })
+            })
+            .collect();
+        manifest::write_manifest(manifest_path, &entries)?;
+        status!("🧾 Upload manifest saved to: {}", manifest_path.display());
+    }
 
-    let resp = client
-        .post(api_url)
-        .timeout(Duration::from_secs(30))
-        .header("Content-Type", "application/json")
-        .json(&upload_request)
-        .send();
+    let failed_batches = outcomes
+        .iter()
+        .filter(|(_, result)| result.is_err())
+        .count();
+    let responses: Vec<&UploadResponse> = outcomes
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().ok()?.response.as_ref())
+        .collect();
+    report_outcome(
+        files,
+        failed_batches,
+        outcomes.len(),
+        &responses,
+        options.strict,
+    )
+}
 
-    progress_bar.finish_and_clear();
+/// Prints the final summary, failing if any batch failed (or, when strict, any file was rejected)
+fn report_outcome(
+    files: &[FileMeta],
+    failed_batches: usize,
+    batch_total: usize,
+    responses: &[&UploadResponse],
+    strict: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if failed_batches > 0 {
+        return Err(format!(
+            "{} of {} batches failed to upload",
+            failed_batches, batch_total
+        )
+        .into());
+    }
+
+    let rejected: Vec<&RejectedFile> = responses
+        .iter()
+        .flat_map(|response| &response.rejected)
+        .collect();
+    if !responses.is_empty() {
+        let accepted: usize = responses.iter().map(|response| response.accepted).sum();
+        status!(
+            "   📥 API accepted {} files, rejected {}",
+            accepted,
+            rejected.len()
+        );
+        for file in &rejected {
+            warn!("Rejected by API: {}", file);
+        }
+    }
+    if strict && !rejected.is_empty() {
+        return Err(format!("API rejected {} files", rejected.len()).into());
+    }
+
+    let total_size: u64 = files.iter().map(|f| f.size).sum();
+    status!(
+        "✅ Successfully uploaded metadata in {} batch(es)",
+        batch_total
+    );
+    status!(
+        "   📊 Files: {}, Total size: {} bytes",
+        files.len(),
+        total_size
+    );
+    Ok(())
+}
+
+/// Builds the HTTP client once with the configured timeouts
+fn build_client(options: &UploadOptions) -> Result<Client, Box<dyn std::error::Error>> {
+    let mut builder = Client::builder().timeout(options.timeout);
+    if let Some(connect_timeout) = options.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+    Ok(builder.build()?)
+}
+
+/// Async counterpart of [`build_client`]
+fn build_async_client(
+    options: &UploadOptions,
+) -> Result<reqwest::Client, Box<dyn std::error::Error>> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(timeout) = options.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(connect_timeout) = options.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+    Ok(builder.build()?)
+}
+
+/// Headers sent with every upload request
+fn request_headers(
+    options: &UploadOptions,
+    body: &EncodedBody,
+) -> Result<HeaderMap, Box<dyn std::error::Error>> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(options.format.content_type(options.framing)),
+    );
+    if options.compress {
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    }
+    // Built once per batch, so every retry repeats the key while each batch body differs
+    headers.insert(
+        IDEMPOTENCY_KEY,
+        HeaderValue::from_str(&HashEncoding::Hex.encode(&body.digest(HashAlgorithm::Blake3)))?,
+    );
+    // Digest of the bytes on the wire, i.e. after compression
+    if let Some(algorithm) = options.content_hash {
+        headers.insert(
+            CONTENT_HASH,
+            HeaderValue::from_str(&format!(
+                "{}={}",
+                algorithm.name(),
+                HashEncoding::Hex.encode(&body.digest(algorithm))
+            ))?,
+        );
+    }
+    if let Some(token) = &options.auth_token {
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", token))?,
+        );
+    }
+    for (name, value) in &options.headers {
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(value)?,
+        );
+    }
+    Ok(headers)
+}
+
+/// Connection errors and server errors are retried, client errors never are
+fn is_retryable(status: Option<StatusCode>) -> bool {
+    status.is_none_or(|status| status.is_server_error())
+}
+
+/// Exponential backoff: the base delay doubled for each previous attempt
+fn backoff_delay(options: &UploadOptions, attempt: u32) -> Duration {
+    options.retry_delay * 2u32.saturating_pow(attempt - 1)
+}
+
+/// Sends one request, retrying connection errors and 5xx responses with backoff
+fn send_with_retry(
+    client: &Client,
+    api_url: &str,
+    upload_request: &UploadRequest,
+    options: &UploadOptions,
+    progress_bar: &ProgressBar,
+) -> BatchResult {
+    let batch_label = batch_label(upload_request);
+
+    // Serialize once so every retry sends identical bytes
+    let body = encode_body(upload_request, options).map_err(BatchError::other)?;
+    let headers = request_headers(options, &body).map_err(BatchError::other)?;
+
+    let max_attempts = options.retries + 1;
+    let mut attempt = 1;
+    let resp = loop {
+        if max_attempts > 1 {
+            progress_bar.set_message(format!(
+                "Uploading metadata{} (attempt {}/{})...",
+                batch_label, attempt, max_attempts
+            ));
+        } else {
+            progress_bar.set_message(format!("Uploading metadata{}...", batch_label));
+        }
+
+        let resp = client
+            .post(api_url)
+            .headers(headers.clone())
+            .body(body.blocking())
+            .send();
+
+        let status = resp.as_ref().ok().map(|response| response.status());
+        if !is_retryable(status) || attempt >= max_attempts {
+            break resp;
+        }
+
+        let delay = backoff_delay(options, attempt);
+        let reason = match &resp {
+            Ok(response) => format!("HTTP {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+        report_retry(progress_bar, upload_request, attempt, &reason, delay);
+        std::thread::sleep(delay);
+        attempt += 1;
+    };
+
+    let response =
+        resp.map_err(|e| BatchError::other(format!("{} (after {} attempts)", e, attempt)))?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().unwrap_or_default();
+        return Err(error_response(progress_bar, status, &text, attempt));
+    }
+
+    Ok(Delivered {
+        status,
+        response: parse_response(&response.text().unwrap_or_default()),
+    })
+}
+
+/// ` batch i/n` when the upload is split, empty for a single request
+fn batch_label(upload_request: &UploadRequest) -> String {
+    if upload_request.batch_total > 1 {
+        format!(
+            " batch {}/{}",
+            upload_request.batch_index + 1,
+            upload_request.batch_total
+        )
+    } else {
+        String::new()
+    }
+}
+
+/// Warns above the progress bar that an attempt failed and will be retried
+fn report_retry(
+    progress_bar: &ProgressBar,
+    upload_request: &UploadRequest,
+    attempt: u32,
+    reason: &str,
+    delay: Duration,
+) {
+    progress_bar.suspend(|| {
+        warn!(
+            "Upload{} attempt {} failed ({}), retrying in {:?}",
+            batch_label(upload_request),
+            attempt,
+            reason,
+            delay
+        )
+    });
+}
+
+/// Warns above the progress bar with the error response body and turns the
+/// final non-success status into the batch's error
+fn error_response(
+    progress_bar: &ProgressBar,
+    status: StatusCode,
+    text: &str,
+    attempt: u32,
+) -> BatchError {
+    progress_bar.suspend(|| {
+        warn!("API responded with error: HTTP {}", status);
+        if !text.is_empty() {
+            warn!("Response: {}", text);
+        }
+    });
+    BatchError {
+        status: Some(status),
+        message: format!("HTTP {} after {} attempts", status, attempt),
+    }
+}
+
+/// Async counterpart of [`send_with_retry`]
+async fn send_with_retry_async(
+    client: &reqwest::Client,
+    api_url: &str,
+    upload_request: &UploadRequest,
+    options: &UploadOptions,
+    progress_bar: &ProgressBar,
+) -> BatchResult {
+    let body = encode_body(upload_request, options).map_err(BatchError::other)?;
+    let headers = request_headers(options, &body).map_err(BatchError::other)?;
+
+    let max_attempts = options.retries + 1;
+    let mut attempt = 1;
+    let resp = loop {
+        let resp = client
+            .post(api_url)
+            .headers(headers.clone())
+            .body(body.streaming())
+            .send()
+            .await;
 
-    match resp {
-        Ok(response) => {
-            if response.status().is_success() {
-                println!(
-                    "✅ Successfully uploaded metadata: HTTP {}",
-                    response.status()
-                );
-                println!(
-                    "   📊 Files: {}, Total size: {} bytes",
-                    files.len(),
-                    total_size
-                );
-            } else {
-                eprintln!("⚠️  API responded with error: HTTP {}", response.status());
-                if let Ok(text) = response.text() {
-                    eprintln!("   Response: {}", text);
+        let status = resp.as_ref().ok().map(|response| response.status());
+        if !is_retryable(status) || attempt >= max_attempts {
+            break resp;
+        }
+
+        let delay = backoff_delay(options, attempt);
+        let reason = match &resp {
+            Ok(response) => format!("HTTP {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+        report_retry(progress_bar, upload_request, attempt, &reason, delay);
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    };
+
+    let response =
+        resp.map_err(|e| BatchError::other(format!("{} (after {} attempts)", e, attempt)))?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return Err(error_response(progress_bar, status, &text, attempt));
+    }
+
+    Ok(Delivered {
+        status,
+        response: parse_response(&response.text().await.unwrap_or_default()),
+    })
+}
+
+/// A request body, encoded once so every retry sends identical bytes
+enum EncodedBody {
+    /// Sent in one piece with a `Content-Length`
+    Whole(Vec<u8>),
+    /// Length-prefixed frames, each sent as it is read by a streaming body
+    Frames(Vec<Vec<u8>>),
+}
+
+impl EncodedBody {
+    /// Digest of the bytes on the wire, taken in a pass over the frames so
+    /// they never need joining into one buffer
+    fn digest(&self, algorithm: HashAlgorithm) -> Vec<u8> {
+        let mut hasher = algorithm.hasher();
+        match self {
+            EncodedBody::Whole(bytes) => hasher.update(bytes),
+            EncodedBody::Frames(frames) => {
+                for frame in frames {
+                    hasher.update(frame);
                 }
             }
         }
-        Err(e) => {
-            eprintln!("❌ Failed to upload metadata: {}", e);
-            return Err(e.into());
+        hasher.finalize()
+    }
+
+    /// Body for the blocking client; frames go out with chunked transfer encoding
+    fn blocking(&self) -> reqwest::blocking::Body {
+        match self {
+            EncodedBody::Whole(bytes) => bytes.clone().into(),
+            EncodedBody::Frames(frames) => reqwest::blocking::Body::new(FrameReader {
+                frames: frames.clone().into_iter(),
+                current: std::io::Cursor::new(Vec::new()),
+            }),
         }
     }
 
-    Ok(())
+    /// Body for the async client; each frame is one chunk of the stream
+    fn streaming(&self) -> reqwest::Body {
+        match self {
+            EncodedBody::Whole(bytes) => bytes.clone().into(),
+            EncodedBody::Frames(frames) => reqwest::Body::wrap_stream(futures_util::stream::iter(
+                frames.clone().into_iter().map(Ok::<_, std::io::Error>),
+            )),
+        }
+    }
+}
+
+/// Reads the frames of a body one after another
+struct FrameReader {
+    frames: std::vec::IntoIter<Vec<u8>>,
+    current: std::io::Cursor<Vec<u8>>,
+}
+
+impl Read for FrameReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.current.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            match self.frames.next() {
+                Some(frame) => self.current = std::io::Cursor::new(frame),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+/// Serializes the request in the configured layout, gzip-compressing it when requested
+fn encode_body(
+    upload_request: &UploadRequest,
+    options: &UploadOptions,
+) -> Result<EncodedBody, Box<dyn std::error::Error>> {
+    if options.format == UploadFormat::Ndjson && options.framing == Framing::LengthPrefixed {
+        return encode_frames(upload_request, options).map(EncodedBody::Frames);
+    }
+
+    let json = match options.format {
+        UploadFormat::Wrapped => serde_json::to_vec(upload_request)?,
+        UploadFormat::Array => serde_json::to_vec(&upload_request.files)?,
+        UploadFormat::Ndjson => {
+            let mut records = Vec::new();
+            for file in &upload_request.files {
+                serde_json::to_writer(&mut records, file)?;
+                records.push(b'\n');
+            }
+            records
+        }
+    };
+    if !options.compress {
+        return Ok(EncodedBody::Whole(json));
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&json)?;
+    Ok(EncodedBody::Whole(encoder.finish()?))
+}
+
+/// One length-prefixed frame per file. Compressed frames share one gzip stream,
+/// flushed after each record so the server can decode every frame as it arrives
+fn encode_frames(
+    upload_request: &UploadRequest,
+    options: &UploadOptions,
+) -> Result<Vec<Vec<u8>>, Box<dyn std::error::Error>> {
+    let mut frames = Vec::with_capacity(upload_request.files.len() + 1);
+    for file in &upload_request.files {
+        let record = serde_json::to_vec(file)?;
+        let length = u32::try_from(record.len())
+            .map_err(|_| format!("record for {} exceeds 4 GiB", file.filename))?;
+        let mut frame = length.to_be_bytes().to_vec();
+        frame.extend_from_slice(&record);
+        frames.push(frame);
+    }
+    if !options.compress {
+        return Ok(frames);
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    for frame in &mut frames {
+        encoder.write_all(frame)?;
+        encoder.flush()?;
+        *frame = std::mem::take(encoder.get_mut());
+    }
+    frames.push(encoder.finish()?);
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+
+    /// A request as the mock server saw it; header names are lowercase
+    struct Received {
+        headers: HashMap<String, String>,
+        body: Vec<u8>,
+    }
+
+    /// What the mock server answers a request with, after an optional delay
+    struct Reply {
+        status: u16,
+        body: String,
+        delay: Duration,
+    }
+
+    impl Reply {
+        fn status(status: u16) -> Self {
+            Self {
+                status,
+                body: String::new(),
+                delay: Duration::ZERO,
+            }
+        }
+    }
+
+    /// Everything the server threads share
+    struct State {
+        respond: Box<dyn Fn(usize) -> Reply + Send + Sync>,
+        received: Mutex<Vec<Received>>,
+        /// Requests answered or being answered, numbering each one
+        counter: AtomicUsize,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    /// Local HTTP server answering the n-th request (from 0) with `respond(n)`
+    struct MockServer {
+        url: String,
+        state: Arc<State>,
+    }
+
+    impl MockServer {
+        fn start(respond: impl Fn(usize) -> Reply + Send + Sync + 'static) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/upload", listener.local_addr().unwrap());
+            let state = Arc::new(State {
+                respond: Box::new(respond),
+                received: Mutex::new(Vec::new()),
+                counter: AtomicUsize::new(0),
+                in_flight: AtomicUsize::new(0),
+                max_in_flight: AtomicUsize::new(0),
+            });
+            let shared = Arc::clone(&state);
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let state = Arc::clone(&shared);
+                    thread::spawn(move || serve(stream, &state));
+                }
+            });
+            Self { url, state }
+        }
+
+        fn requests(&self) -> std::sync::MutexGuard<'_, Vec<Received>> {
+            self.state.received.lock().unwrap()
+        }
+
+        /// Most requests that were being answered at the same time
+        fn max_in_flight(&self) -> usize {
+            self.state.max_in_flight.load(Ordering::SeqCst)
+        }
+    }
+
+    /// Reads one request, records it, and replies with `Connection: close`
+    fn serve(mut stream: TcpStream, state: &State) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let mut headers = HashMap::new();
+        loop {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            let Some((name, value)) = line.trim_end().split_once(':') else {
+                break;
+            };
+            headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+        }
+        let body = if headers.get("transfer-encoding").map(String::as_str) == Some("chunked") {
+            read_chunked(&mut reader)
+        } else {
+            let length = headers
+                .get("content-length")
+                .map_or(0, |length| length.parse().unwrap());
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            body
+        };
+
+        let in_flight = state.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        state.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        let index = state.counter.fetch_add(1, Ordering::SeqCst);
+        state
+            .received
+            .lock()
+            .unwrap()
+            .push(Received { headers, body });
+        let reply = (state.respond)(index);
+        thread::sleep(reply.delay);
+        // The client may already have timed out and hung up
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            reply.status,
+            reply.body.len(),
+            reply.body
+        );
+        state.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Joins a chunked request body, which ends with a zero-length chunk
+    fn read_chunked(reader: &mut impl BufRead) -> Vec<u8> {
+        let mut body = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            let size = usize::from_str_radix(line.trim(), 16).unwrap();
+            let mut chunk = vec![0; size + 2];
+            reader.read_exact(&mut chunk).unwrap();
+            if size == 0 {
+                return body;
+            }
+            body.extend_from_slice(&chunk[..size]);
+        }
+    }
+
+    fn quick_retries(retries: u32) -> UploadOptions {
+        UploadOptions {
+            retries,
+            retry_delay: Duration::from_millis(1),
+            ..UploadOptions::default()
+        }
+    }
+
+    #[test]
+    fn upload_retries_server_errors_until_success() {
+        let server = MockServer::start(|n| Reply::status(if n < 2 { 502 } else { 200 }));
+        upload_metadata(&server.url, &FileMeta::test_files(2), &quick_retries(3)).unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        // Every attempt resends the same serialized batch
+        assert!(requests.iter().all(|r| r.body == requests[0].body));
+        assert_eq!(requests[0].headers["content-type"], "application/json");
+    }
+
+    #[test]
+    fn upload_gives_up_after_the_configured_retries() {
+        let server = MockServer::start(|_| Reply::status(503));
+        let error =
+            upload_metadata(&server.url, &FileMeta::test_files(2), &quick_retries(2)).unwrap_err();
+        assert!(
+            error.to_string().contains("1 of 1 batches failed"),
+            "{}",
+            error
+        );
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let server = MockServer::start(|_| Reply::status(400));
+        assert!(upload_metadata(&server.url, &FileMeta::test_files(2), &quick_retries(3)).is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    /// Parses a recorded `UploadFormat::Wrapped` body
+    fn wrapped_body(request: &Received) -> serde_json::Value {
+        serde_json::from_slice(&request.body).unwrap()
+    }
+
+    #[test]
+    fn files_are_split_into_one_post_per_batch() {
+        let server = MockServer::start(|_| Reply::status(200));
+        let options = UploadOptions {
+            batch_size: Some(2),
+            ..UploadOptions::default()
+        };
+        upload_metadata(&server.url, &FileMeta::test_files(5), &options).unwrap();
+
+        let requests = server.requests();
+        let batches: Vec<(u64, u64, usize)> = requests
+            .iter()
+            .map(wrapped_body)
+            .map(|body| {
+                (
+                    body["batch_index"].as_u64().unwrap(),
+                    body["batch_total"].as_u64().unwrap(),
+                    body["files"].as_array().unwrap().len(),
+                )
+            })
+            .collect();
+        assert_eq!(batches, [(0, 3, 2), (1, 3, 2), (2, 3, 1)]);
+        for request in requests.iter() {
+            let body = wrapped_body(request);
+            assert_eq!(
+                (body["total_files"].as_u64(), body["total_size"].as_u64()),
+                (Some(5), Some(5 * 1234))
+            );
+            let batch_files = body["files"].as_array().unwrap().len() as u64;
+            assert_eq!(body["batch_files"].as_u64(), Some(batch_files));
+            assert_eq!(body["batch_size"].as_u64(), Some(batch_files * 1234));
+        }
+        let timestamps: Vec<serde_json::Value> = requests
+            .iter()
+            .map(|request| wrapped_body(request)["scan_timestamp"].clone())
+            .collect();
+        assert!(timestamps.iter().all(|t| *t == timestamps[0]));
+    }
+
+    #[test]
+    fn empty_file_list_sends_nothing() {
+        let server = MockServer::start(|_| Reply::status(200));
+        upload_metadata(&server.url, &[], &UploadOptions::default()).unwrap();
+        assert!(server.requests().is_empty());
+        assert!(build_requests(&[], &UploadOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn one_failed_batch_fails_the_upload() {
+        let server = MockServer::start(|n| Reply::status(if n == 1 { 400 } else { 200 }));
+        let options = UploadOptions {
+            batch_size: Some(2),
+            ..quick_retries(0)
+        };
+        let error = upload_metadata(&server.url, &FileMeta::test_files(5), &options).unwrap_err();
+        assert!(
+            error.to_string().contains("1 of 3 batches failed"),
+            "{}",
+            error
+        );
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn auth_token_and_custom_headers_are_sent() {
+        let server = MockServer::start(|_| Reply::status(200));
+        let options = UploadOptions {
+            auth_token: Some("secret".to_string()),
+            headers: vec![("X-Tenant".to_string(), "acme".to_string())],
+            ..UploadOptions::default()
+        };
+        upload_metadata(&server.url, &FileMeta::test_files(1), &options).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].headers["authorization"], "Bearer secret");
+        assert_eq!(requests[0].headers["x-tenant"], "acme");
+    }
+
+    #[test]
+    fn compressed_body_is_gzip_of_the_json_payload() {
+        use flate2::read::GzDecoder;
+
+        let server = MockServer::start(|_| Reply::status(200));
+        let options = UploadOptions {
+            compress: true,
+            ..UploadOptions::default()
+        };
+        upload_metadata(&server.url, &FileMeta::test_files(3), &options).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].headers["content-encoding"], "gzip");
+        let mut json = String::new();
+        GzDecoder::new(&requests[0].body[..])
+            .read_to_string(&mut json)
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let names: Vec<&str> = body["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| file["filename"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["/photos/0.jpg", "/photos/1.jpg", "/photos/2.jpg"]);
+        assert_eq!(body["total_files"], 3);
+    }
+
+    #[test]
+    fn slow_responses_fail_with_a_timeout() {
+        let server = MockServer::start(|_| Reply {
+            delay: Duration::from_secs(2),
+            ..Reply::status(200)
+        });
+        let options = UploadOptions {
+            timeout: Some(Duration::from_millis(200)),
+            ..quick_retries(0)
+        };
+        let started = std::time::Instant::now();
+        assert!(upload_metadata(&server.url, &FileMeta::test_files(1), &options).is_err());
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn concurrent_uploads_keep_n_batches_in_flight() {
+        let server = MockServer::start(|_| Reply {
+            delay: Duration::from_millis(200),
+            ..Reply::status(200)
+        });
+        let options = UploadOptions {
+            batch_size: Some(1),
+            concurrent_uploads: 3,
+            ..UploadOptions::default()
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(upload_metadata_async(
+                &server.url,
+                &FileMeta::test_files(6),
+                &options,
+            ))
+            .unwrap();
+
+        let mut batch_indexes: Vec<u64> = server
+            .requests()
+            .iter()
+            .map(|request| wrapped_body(request)["batch_index"].as_u64().unwrap())
+            .collect();
+        batch_indexes.sort();
+        assert_eq!(batch_indexes, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(server.max_in_flight(), 3);
+    }
+
+    #[test]
+    fn rejected_files_fail_a_strict_upload() {
+        let server = MockServer::start(|_| {
+            Reply {
+            body: r#"{"accepted": 1, "rejected": ["/photos/1.jpg", {"path": "/photos/2.jpg", "reason": "too large"}]}"#.to_string(),
+            ..Reply::status(200)
+        }
+        });
+        upload_metadata(
+            &server.url,
+            &FileMeta::test_files(3),
+            &UploadOptions::default(),
+        )
+        .unwrap();
+
+        let strict = UploadOptions {
+            strict: true,
+            ..UploadOptions::default()
+        };
+        let error = upload_metadata(&server.url, &FileMeta::test_files(3), &strict).unwrap_err();
+        assert_eq!(error.to_string(), "API rejected 2 files");
+    }
+
+    #[test]
+    fn rejection_lists_parse_bare_and_detailed_paths() {
+        let response = parse_response(
+            r#"{"accepted": 1, "rejected": ["/a.jpg", {"filename": "/b.jpg", "reason": "quota"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(response.accepted, 1);
+        let rejected: Vec<String> = response.rejected.iter().map(ToString::to_string).collect();
+        assert_eq!(rejected, ["/a.jpg", "/b.jpg (quota)"]);
+    }
+
+    #[test]
+    fn non_json_success_bodies_are_accepted() {
+        assert!(parse_response("OK").is_none());
+        let server = MockServer::start(|_| Reply {
+            body: "OK".to_string(),
+            ..Reply::status(200)
+        });
+        let strict = UploadOptions {
+            strict: true,
+            ..UploadOptions::default()
+        };
+        upload_metadata(&server.url, &FileMeta::test_files(1), &strict).unwrap();
+    }
+
+    fn read_manifest(path: &Path) -> Vec<ManifestEntry> {
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn manifest_records_every_hash_with_its_batch_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("uploaded.json");
+        let server = MockServer::start(|_| Reply::status(200));
+        let files = FileMeta::test_files(5);
+        let options = UploadOptions {
+            batch_size: Some(2),
+            manifest: Some(manifest_path.clone()),
+            ..UploadOptions::default()
+        };
+        upload_metadata(&server.url, &files, &options).unwrap();
+
+        let entries = read_manifest(&manifest_path);
+        let hashes: Vec<&str> = entries.iter().map(|entry| entry.hash.as_str()).collect();
+        let expected: Vec<&str> = files.iter().map(|file| file.hash.as_str()).collect();
+        assert_eq!(hashes, expected);
+        assert!(entries.iter().all(|entry| entry.status == Some(200)));
+        let batches: Vec<usize> = entries.iter().map(|entry| entry.batch_index).collect();
+        assert_eq!(batches, [0, 0, 1, 1, 2]);
+    }
+
+    #[test]
+    fn manifest_keeps_the_status_of_failed_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("uploaded.json");
+        let server = MockServer::start(|n| Reply::status(if n == 0 { 200 } else { 422 }));
+        let options = UploadOptions {
+            batch_size: Some(1),
+            manifest: Some(manifest_path.clone()),
+            ..quick_retries(0)
+        };
+        assert!(upload_metadata(&server.url, &FileMeta::test_files(2), &options).is_err());
+
+        let statuses: Vec<Option<u16>> = read_manifest(&manifest_path)
+            .iter()
+            .map(|entry| entry.status)
+            .collect();
+        assert_eq!(statuses, [Some(200), Some(422)]);
+    }
+
+    #[test]
+    fn array_format_sends_a_bare_array_of_the_files() {
+        let server = MockServer::start(|_| Reply::status(200));
+        let files = FileMeta::test_files(3);
+        let options = UploadOptions {
+            format: UploadFormat::Array,
+            ..UploadOptions::default()
+        };
+        upload_metadata(&server.url, &files, &options).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body, serde_json::to_value(&files).unwrap());
+        assert_eq!(requests[0].headers["content-type"], "application/json");
+    }
+
+    #[test]
+    fn ndjson_format_sends_one_file_per_line() {
+        let server = MockServer::start(|_| Reply::status(200));
+        let files = FileMeta::test_files(3);
+        let options = UploadOptions {
+            format: UploadFormat::Ndjson,
+            ..UploadOptions::default()
+        };
+        upload_metadata(&server.url, &files, &options).unwrap();
+
+        let requests = server.requests();
+        let records: Vec<serde_json::Value> = std::str::from_utf8(&requests[0].body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let expected: Vec<serde_json::Value> = files
+            .iter()
+            .map(|file| serde_json::to_value(file).unwrap())
+            .collect();
+        assert_eq!(records, expected);
+        assert_eq!(requests[0].headers["content-type"], "application/x-ndjson");
+    }
+
+    #[test]
+    fn content_hash_header_matches_the_bytes_on_the_wire() {
+        use sha2::Digest;
+
+        let server = MockServer::start(|_| Reply::status(200));
+        let options = UploadOptions {
+            compress: true,
+            content_hash: Some(HashAlgorithm::Sha256),
+            ..UploadOptions::default()
+        };
+        upload_metadata(&server.url, &FileMeta::test_files(3), &options).unwrap();
+        let options = UploadOptions {
+            content_hash: Some(HashAlgorithm::Blake3),
+            ..UploadOptions::default()
+        };
+        upload_metadata(&server.url, &FileMeta::test_files(3), &options).unwrap();
+
+        let requests = server.requests();
+        // The compressed body is hashed as sent, not the JSON inside it
+        assert_eq!(requests[0].headers["content-encoding"], "gzip");
+        assert_eq!(
+            requests[0].headers["x-content-hash"],
+            format!("sha256={:x}", sha2::Sha256::digest(&requests[0].body))
+        );
+        assert_eq!(
+            requests[1].headers["x-content-hash"],
+            format!("blake3={}", blake3::hash(&requests[1].body).to_hex())
+        );
+    }
+
+    #[test]
+    fn scan_timestamp_is_when_the_scan_started() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.jpg"), [1; 2048]).unwrap();
+        let mut scan_options = crate::scanner::ScanOptions::default();
+        scan_options.process.progress = crate::progress::ProgressMode::None;
+        let report =
+            crate::scanner::scan_folder(&[dir.path().display().to_string()], &scan_options, None);
+        thread::sleep(Duration::from_millis(20));
+
+        let server = MockServer::start(|_| Reply::status(200));
+        let options = UploadOptions {
+            scan_timestamp: Some(report.stats.started_at),
+            ..UploadOptions::default()
+        };
+        upload_metadata(&server.url, &report.files, &options).unwrap();
+
+        let body = wrapped_body(&server.requests()[0]);
+        let timestamp = |field: &str| {
+            chrono::DateTime::parse_from_rfc3339(body[field].as_str().unwrap()).unwrap()
+        };
+        assert_eq!(timestamp("scan_timestamp"), report.stats.started_at);
+        assert!(timestamp("scan_timestamp") < timestamp("upload_timestamp"));
+    }
+
+    /// Splits a length-prefixed body back into its records
+    fn decode_frames(mut body: &[u8]) -> Vec<FileMeta> {
+        let mut decoded = Vec::new();
+        while !body.is_empty() {
+            let (header, rest) = body.split_at(4);
+            let length = u32::from_be_bytes(header.try_into().unwrap()) as usize;
+            let (record, rest) = rest.split_at(length);
+            decoded.push(serde_json::from_slice(record).unwrap());
+            body = rest;
+        }
+        decoded
+    }
+
+    #[test]
+    fn length_prefixed_frames_decode_back_into_the_files() {
+        let server = MockServer::start(|_| Reply::status(200));
+        let files = FileMeta::test_files(4);
+        let options = UploadOptions {
+            format: UploadFormat::Ndjson,
+            framing: Framing::LengthPrefixed,
+            ..UploadOptions::default()
+        };
+        upload_metadata(&server.url, &files, &options).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].headers["content-type"],
+            "application/octet-stream"
+        );
+        // Streamed, so the length of the whole body is never announced
+        assert_eq!(requests[0].headers["transfer-encoding"], "chunked");
+        assert!(!requests[0].headers.contains_key("content-length"));
+        let decoded = decode_frames(&requests[0].body);
+        let names: Vec<&str> = decoded.iter().map(|f| f.filename.as_str()).collect();
+        let expected: Vec<&str> = files.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(names, expected);
+        assert!(decoded.iter().zip(&files).all(|(a, b)| a.hash == b.hash));
+    }
+
+    #[test]
+    fn streamed_compressed_frames_match_their_content_hash() {
+        use flate2::read::GzDecoder;
+        use sha2::Digest;
+
+        let server = MockServer::start(|_| Reply::status(200));
+        let files = FileMeta::test_files(3);
+        let options = UploadOptions {
+            format: UploadFormat::Ndjson,
+            framing: Framing::LengthPrefixed,
+            compress: true,
+            content_hash: Some(HashAlgorithm::Sha256),
+            concurrent_uploads: 2,
+            ..UploadOptions::default()
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(upload_metadata_async(&server.url, &files, &options))
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].headers["transfer-encoding"], "chunked");
+        // Digested in a pass before sending, yet equal to the streamed bytes
+        assert_eq!(
+            requests[0].headers["x-content-hash"],
+            format!("sha256={:x}", sha2::Sha256::digest(&requests[0].body))
+        );
+        let mut framed = Vec::new();
+        GzDecoder::new(&requests[0].body[..])
+            .read_to_end(&mut framed)
+            .unwrap();
+        let names: Vec<String> = decode_frames(&framed)
+            .into_iter()
+            .map(|file| file.filename)
+            .collect();
+        assert_eq!(names, ["/photos/0.jpg", "/photos/1.jpg", "/photos/2.jpg"]);
+    }
+
+    #[test]
+    fn idempotency_key_repeats_across_retries_and_differs_between_batches() {
+        // The first batch fails twice before succeeding; the second succeeds at once
+        let server = MockServer::start(|n| Reply::status(if n < 2 { 503 } else { 200 }));
+        let options = UploadOptions {
+            batch_size: Some(2),
+            ..quick_retries(3)
+        };
+        upload_metadata(&server.url, &FileMeta::test_files(4), &options).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        let keys: Vec<(&str, u64)> = requests
+            .iter()
+            .map(|request| {
+                (
+                    request.headers["idempotency-key"].as_str(),
+                    wrapped_body(request)["batch_index"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        let (first, second): (Vec<_>, Vec<_>) = keys.iter().partition(|(_, batch)| *batch == 0);
+        assert_eq!(first.len(), 3);
+        assert!(first.iter().all(|(key, _)| *key == first[0].0));
+        assert_eq!(second.len(), 1);
+        assert_ne!(first[0].0, second[0].0);
+    }
 }
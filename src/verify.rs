@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::hash::HashAlgorithm;
+use crate::scanner::{self, HashStrategy, ScanError};
+use crate::uploader::FileMeta;
+use crate::{status, warning};
+
+/// A file whose current hash differs from the recorded one
+pub struct Mismatch {
+    pub path: PathBuf,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Outcome of checking current files against a previous scan
+pub struct VerifyReport {
+    pub verified: usize,
+    pub mismatched: Vec<Mismatch>,
+    pub missing: Vec<PathBuf>,
+    pub new: Vec<PathBuf>,
+    /// Files that exist but could not be rehashed
+    pub errors: Vec<ScanError>,
+}
+
+/// Rehashes every recorded file that still exists using the algorithm it was
+/// recorded with, and lists discovered files the manifest does not know about.
+/// Relative manifest paths are resolved against `root`.
+pub fn verify_files(
+    previous: &[FileMeta],
+    discovered: &[PathBuf],
+    root: &Path,
+    hash_strategy: HashStrategy,
+) -> VerifyReport {
+    let recorded: Vec<(PathBuf, &FileMeta)> = previous
+        .iter()
+        .map(|file| {
+            let path = file.os_path();
+            let path = if path.is_relative() {
+                root.join(path)
+            } else {
+                path
+            };
+            (path, file)
+        })
+        .collect();
+
+    let outcomes: Vec<(PathBuf, Result<Option<Mismatch>, String>)> = recorded
+        .par_iter()
+        .filter(|(path, _)| path.exists())
+        .map(|(path, file)| (path.clone(), rehash(path, file, hash_strategy)))
+        .collect();
+
+    let mut report = VerifyReport {
+        verified: 0,
+        mismatched: Vec::new(),
+        missing: recorded
+            .iter()
+            .filter(|(path, _)| !path.exists())
+            .map(|(path, _)| path.clone())
+            .collect(),
+        new: Vec::new(),
+        errors: Vec::new(),
+    };
+    for (path, outcome) in outcomes {
+        match outcome {
+            Ok(None) => report.verified += 1,
+            Ok(Some(mismatch)) => report.mismatched.push(mismatch),
+            Err(e) => report.errors.push((path, e)),
+        }
+    }
+
+    let known: HashSet<&Path> = recorded.iter().map(|(path, _)| path.as_path()).collect();
+    report.new = discovered
+        .iter()
+        .filter(|path| !known.contains(path.as_path()))
+        .cloned()
+        .collect();
+
+    report.mismatched.sort_by(|a, b| a.path.cmp(&b.path));
+    report.missing.sort();
+    report.errors.sort_by(|a, b| a.0.cmp(&b.0));
+    report
+}
+
+/// Hashes one file the way it was originally hashed and compares the result
+fn rehash(
+    path: &Path,
+    file: &FileMeta,
+    hash_strategy: HashStrategy,
+) -> Result<Option<Mismatch>, String> {
+    // Manifests written before `hash_algo` existed were always BLAKE3
+    let algorithm = if file.hash_algo.is_empty() {
+        HashAlgorithm::default()
+    } else {
+        HashAlgorithm::from_name(&file.hash_algo)
+            .ok_or_else(|| format!("unknown hash algorithm: {}", file.hash_algo))?
+    };
+    let file_size = path.metadata().map_err(|e| e.to_string())?.len();
+    let strategy = HashStrategy {
+        algorithm,
+        ..hash_strategy
+    };
+    let mut actual = scanner::hash_file(path, file_size, strategy).map_err(|e| e.to_string())?;
+    // Files that `--rehash-on-size-match` told apart from a sampled match carry a full hash
+    if actual != file.hash && strategy.samples(file_size) {
+        let full = HashStrategy {
+            full_hash: true,
+            ..strategy
+        };
+        let full_hash = scanner::hash_file(path, file_size, full).map_err(|e| e.to_string())?;
+        if full_hash == file.hash {
+            actual = full_hash;
+        }
+    }
+
+    Ok((actual != file.hash).then(|| Mismatch {
+        path: path.to_path_buf(),
+        expected: file.hash.clone(),
+        actual,
+    }))
+}
+
+/// Prints the verification summary followed by each problem found
+pub fn print_report(report: &VerifyReport) {
+    let mut output = format!(
+        "🔍 Verified {} files: {} mismatched, {} missing, {} new\n",
+        report.verified,
+        report.mismatched.len(),
+        report.missing.len(),
+        report.new.len()
+    );
+    for mismatch in &report.mismatched {
+        output.push_str(&format!(
+            "   ❌ Mismatch: {} (expected {}, got {})\n",
+            mismatch.path.display(),
+            mismatch.expected,
+            mismatch.actual
+        ));
+    }
+    for path in &report.missing {
+        output.push_str(&format!("   ❓ Missing: {}\n", path.display()));
+    }
+    for path in &report.new {
+        output.push_str(&format!("   🆕 New: {}\n", path.display()));
+    }
+    print!("{}", status::decorate(&output));
+    for (path, error) in &report.errors {
+        warning!("   ⚠️  {}: {}", path.display(), error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::ScanOptions;
+
+    #[test]
+    fn altered_missing_and_new_files_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for name in ["kept.bin", "altered.bin", "deleted.bin"] {
+            std::fs::write(root.join(name), name.repeat(100)).unwrap();
+        }
+        let folders = [root.display().to_string()];
+        let baseline = scanner::scan_folder(&folders, &ScanOptions::default(), None).files;
+
+        std::fs::write(root.join("altered.bin"), "changed".repeat(100)).unwrap();
+        std::fs::remove_file(root.join("deleted.bin")).unwrap();
+        std::fs::write(root.join("added.bin"), "added".repeat(100)).unwrap();
+        let (discovered, _) = scanner::discover_files(&folders, &ScanOptions::default());
+
+        let report = verify_files(&baseline, &discovered, root, HashStrategy::default());
+        assert_eq!(report.verified, 1);
+        let mismatched: Vec<&Path> = report.mismatched.iter().map(|m| m.path.as_path()).collect();
+        assert_eq!(mismatched, [root.join("altered.bin")]);
+        assert_eq!(report.missing, [root.join("deleted.bin")]);
+        assert_eq!(report.new, [root.join("added.bin")]);
+        assert!(report.errors.is_empty());
+    }
+
+    #[test]
+    fn files_are_rehashed_with_their_recorded_algorithm() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.bin");
+        std::fs::write(&path, b"abc").unwrap();
+        let recorded = FileMeta {
+            hash_algo: "sha256".to_string(),
+            ..FileMeta::for_test(&path.display().to_string())
+                .with_size(3)
+                .with_hash(&HashAlgorithm::Sha256.hash_bytes(b"abc"))
+        };
+
+        let report = verify_files(&[recorded], &[path], dir.path(), HashStrategy::default());
+        assert_eq!(report.verified, 1);
+        assert!(report.mismatched.is_empty());
+    }
+}
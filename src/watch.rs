@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use log::{debug, warn};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::progress::ProgressMode;
+use crate::scanner::{self, DuplicateMode, ScanOptions};
+use crate::uploader::FileMeta;
+
+/// Files added, changed, or removed by one burst of filesystem events
+pub struct WatchChanges {
+    /// When the rescan began
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    /// New or rehashed entries as they now appear in the results
+    pub updated: Vec<FileMeta>,
+    /// Paths dropped from the results
+    pub removed: Vec<String>,
+}
+
+impl WatchChanges {
+    pub fn is_empty(&self) -> bool {
+        self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// The current results of a watched scan, keyed by full path
+///
+/// Duplicates follow the scan's [`DuplicateMode`]: with `skip` a new copy of a
+/// known file is ignored, with `group` its path is attached to the original.
+/// A skipped copy is not promoted when its original is later deleted.
+pub struct WatchState {
+    files: BTreeMap<String, FileMeta>,
+    /// Path of the entry each hash is listed under, for duplicate detection
+    by_hash: HashMap<String, String>,
+    options: ScanOptions,
+    /// Files written by this process, whose changes must not trigger a rescan
+    ignored: Vec<PathBuf>,
+}
+
+impl WatchState {
+    /// Starts from the files of the initial scan
+    pub fn new(files: Vec<FileMeta>, options: &ScanOptions) -> Self {
+        let mut options = options.clone();
+        // Rescans cover a handful of files; a progress bar would flash for each one
+        options.process.progress = ProgressMode::None;
+        options.process.limit = None;
+        options.process.max_files = None;
+
+        let mut state = Self {
+            files: BTreeMap::new(),
+            by_hash: HashMap::new(),
+            options,
+            ignored: Vec::new(),
+        };
+        for file in files {
+            state
+                .by_hash
+                .insert(file.hash.clone(), file.filename.clone());
+            state.files.insert(file.filename.clone(), file);
+        }
+        state
+    }
+
+    /// Ignores events for `path`, e.g. the output file when it lies inside a watched root
+    pub fn ignore(&mut self, path: &Path) {
+        if let Ok(path) = std::path::absolute(path) {
+            self.ignored.push(path);
+        }
+    }
+
+    /// Current results sorted by filename
+    pub fn files(&self) -> Vec<FileMeta> {
+        self.files.values().cloned().collect()
+    }
+
+    /// Rehashes paths that exist, drops those that are gone, and returns what changed
+    pub fn apply(&mut self, paths: &BTreeSet<PathBuf>) -> WatchChanges {
+        let mut changes = WatchChanges {
+            started_at: chrono::Utc::now(),
+            updated: Vec::new(),
+            removed: Vec::new(),
+        };
+        let mut to_scan = Vec::new();
+        for path in paths {
+            if self.is_ignored(path) {
+                continue;
+            }
+            if path.is_file() {
+                to_scan.push(path.clone());
+            } else if path.is_dir() {
+                // A directory moved or copied in arrives as a single event
+                let (found, _) =
+                    scanner::discover_files(&[path.display().to_string()], &self.options);
+                to_scan.extend(found);
+            } else {
+                changes.removed.extend(self.remove(path));
+            }
+        }
+        if to_scan.is_empty() {
+            return changes;
+        }
+
+        // Known files are dropped first so a rehash that no longer passes the
+        // filters (e.g. truncated below --min-size) counts as a removal
+        let mut replaced: BTreeSet<String> = BTreeSet::new();
+        for path in &to_scan {
+            replaced.extend(self.remove(path));
+        }
+
+        let report = scanner::scan_files(&to_scan, &self.options, None);
+        for (path, error) in &report.errors {
+            warn!("Could not rescan {}: {}", path.display(), error);
+        }
+        for file in report.into_files() {
+            replaced.remove(&file.filename);
+            if let Some(entry) = self.insert(file) {
+                changes.updated.push(entry);
+            }
+        }
+        changes.removed.extend(replaced);
+        changes
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        std::path::absolute(path).is_ok_and(|path| self.ignored.contains(&path))
+    }
+
+    /// Adds a freshly hashed file and returns the entry it now appears under,
+    /// or `None` when it is a skipped duplicate
+    fn insert(&mut self, file: FileMeta) -> Option<FileMeta> {
+        let mode = self.options.process.duplicates;
+        if mode != DuplicateMode::KeepAll {
+            if let Some(original) = self
+                .by_hash
+                .get(&file.hash)
+                .and_then(|path| self.files.get_mut(path))
+            {
+                if mode == DuplicateMode::Skip {
+                    debug!("Skipping duplicate {}", file.filename);
+                    return None;
+                }
+                original.duplicate_paths.push(file.filename);
+                return Some(original.clone());
+            }
+        }
+        self.by_hash
+            .entry(file.hash.clone())
+            .or_insert_with(|| file.filename.clone());
+        self.files.insert(file.filename.clone(), file.clone());
+        Some(file)
+    }
+
+    /// Removes the file at `path`, or every file beneath it for a directory,
+    /// and returns the removed paths
+    fn remove(&mut self, path: &Path) -> Vec<String> {
+        let removed: Vec<String> = self
+            .files
+            .keys()
+            .filter(|key| Path::new(key).starts_with(path))
+            .cloned()
+            .collect();
+        for key in &removed {
+            if let Some(file) = self.files.remove(key) {
+                if self.by_hash.get(&file.hash) == Some(key) {
+                    self.by_hash.remove(&file.hash);
+                }
+            }
+        }
+        for file in self.files.values_mut() {
+            file.duplicate_paths
+                .retain(|duplicate| !Path::new(duplicate).starts_with(path));
+        }
+        removed
+    }
+}
+
+/// Watches the roots recursively and calls `on_change` once each burst of
+/// events has been quiet for `debounce`; only returns if the watcher fails
+pub fn watch(
+    roots: &[String],
+    state: &mut WatchState,
+    debounce: Duration,
+    mut on_change: impl FnMut(&WatchState, &WatchChanges),
+) -> notify::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for root in roots {
+        watcher.watch(Path::new(root), RecursiveMode::Recursive)?;
+    }
+
+    while let Ok(event) = rx.recv() {
+        let mut paths = BTreeSet::new();
+        collect_paths(event, &mut paths);
+        while let Ok(event) = rx.recv_timeout(debounce) {
+            collect_paths(event, &mut paths);
+        }
+        debug!("Rescanning {} changed paths", paths.len());
+
+        let changes = state.apply(&paths);
+        if !changes.is_empty() {
+            on_change(state, &changes);
+        }
+    }
+    Ok(())
+}
+
+fn collect_paths(event: notify::Result<Event>, paths: &mut BTreeSet<PathBuf>) {
+    match event {
+        // Reads (including our own hashing) change nothing
+        Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+        Ok(event) => paths.extend(event.paths),
+        Err(e) => warn!("Watch error: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn initial_state(root: &Path) -> WatchState {
+        let options = ScanOptions::default();
+        let report = scanner::scan_folder(&[root.display().to_string()], &options, None);
+        WatchState::new(report.into_files(), &options)
+    }
+
+    fn names(files: &[FileMeta]) -> Vec<&str> {
+        files
+            .iter()
+            .map(|file| file.filename.rsplit('/').next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn applied_events_add_rehash_and_remove_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.jpg"), [1; 2048]).unwrap();
+        fs::write(dir.path().join("b.jpg"), [2; 2048]).unwrap();
+        let mut state = initial_state(dir.path());
+
+        fs::write(dir.path().join("c.jpg"), [3; 2048]).unwrap();
+        fs::write(dir.path().join("a.jpg"), [4; 2048]).unwrap();
+        fs::remove_file(dir.path().join("b.jpg")).unwrap();
+        let changes = state.apply(&BTreeSet::from([
+            dir.path().join("a.jpg"),
+            dir.path().join("b.jpg"),
+            dir.path().join("c.jpg"),
+        ]));
+
+        assert_eq!(names(&changes.updated), ["a.jpg", "c.jpg"]);
+        assert_eq!(
+            changes.updated[0].hash,
+            blake3::hash(&[4; 2048]).to_hex().to_string()
+        );
+        assert_eq!(
+            changes.removed,
+            [dir.path().join("b.jpg").display().to_string()]
+        );
+        assert_eq!(names(&state.files()), ["a.jpg", "c.jpg"]);
+    }
+
+    #[test]
+    fn creating_a_file_in_a_watched_dir_adds_it_to_the_results() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.jpg"), [1; 2048]).unwrap();
+        let mut state = initial_state(dir.path());
+        let roots = vec![dir.path().display().to_string()];
+
+        let (tx, rx) = mpsc::channel();
+        // Runs until the test process exits
+        std::thread::spawn(move || {
+            watch(
+                &roots,
+                &mut state,
+                Duration::from_millis(100),
+                |state, _| {
+                    let _ = tx.send(state.files());
+                },
+            )
+        });
+        // Give the watcher time to register before the file lands
+        std::thread::sleep(Duration::from_millis(300));
+        fs::write(dir.path().join("new.jpg"), [2; 2048]).unwrap();
+
+        let files = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(names(&files), ["a.jpg", "new.jpg"]);
+    }
+}
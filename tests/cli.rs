@@ -0,0 +1,617 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::thread::JoinHandle;
+
+use assert_cmd::Command;
+
+/// Writes `len` bytes of `fill` to `relative` under `root`, creating parents
+fn write_file(root: &Path, relative: &str, len: usize, fill: u8) {
+    let path = root.join(relative);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, vec![fill; len]).unwrap();
+}
+
+/// Accepts a single HTTP request on localhost, answers 200, and yields its body
+fn capture_one_post() -> (String, JoinHandle<Vec<u8>>) {
+    answer_one_post(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+}
+
+/// Accepts a single HTTP request on localhost, writes `reply`, and yields its body
+fn answer_one_post(reply: &'static [u8]) -> (String, JoinHandle<Vec<u8>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/upload", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut length = 0;
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            line.clear();
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        stream.write_all(reply).unwrap();
+        body
+    });
+    (url, handle)
+}
+
+fn biebie() -> Command {
+    let mut command = Command::cargo_bin("biebie-cli").unwrap();
+    command.env_remove("NO_COLOR").env_remove("RUST_LOG");
+    command
+}
+
+#[test]
+fn json_progress_ends_with_processed_equal_to_total() {
+    let dir = tempfile::tempdir().unwrap();
+    for i in 0..250 {
+        write_file(dir.path(), &format!("d{}/f{}.bin", i % 5, i), 1100, i as u8);
+    }
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--progress", "json", "--quiet", "--output", "ndjson"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // stderr is a pipe here, not a terminal
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stderr)
+        .unwrap()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    assert!(events.len() >= 3, "expected interval and final events");
+    let last = events.last().unwrap();
+    assert_eq!(last["processed"], 250);
+    assert_eq!(last["total"], 250);
+    assert_eq!(last["bytes_processed"], 250 * 1100);
+}
+
+#[test]
+fn skip_uploaded_leaves_manifest_hashes_out_of_the_post_body() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("photos");
+    write_file(&root, "uploaded.jpg", 2048, 1);
+    // Same content under another name counts as uploaded too
+    write_file(&root, "copy-of-uploaded.jpg", 2048, 1);
+    write_file(&root, "new.jpg", 2048, 2);
+    let manifest = dir.path().join("manifest.json");
+    let uploaded_hash = biebie::hash::HashAlgorithm::Blake3.hash_bytes(&[1; 2048]);
+    fs::write(
+        &manifest,
+        serde_json::json!([{
+            "hash": uploaded_hash,
+            "filename": "/elsewhere/uploaded.jpg",
+            "batch_index": 0,
+            "status": 200,
+        }])
+        .to_string(),
+    )
+    .unwrap();
+
+    let (url, request) = capture_one_post();
+    biebie()
+        .arg(&root)
+        .args(["--duplicates", "keep-all", "--quiet", "--output", "json"])
+        .arg("--api")
+        .arg(&url)
+        .arg("--skip-uploaded")
+        .arg(&manifest)
+        .assert()
+        .success();
+
+    let body: serde_json::Value = serde_json::from_slice(&request.join().unwrap()).unwrap();
+    let names: Vec<&str> = body["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["filename"].as_str().unwrap())
+        .collect();
+    assert_eq!(names.len(), 1);
+    assert!(names[0].ends_with("new.jpg"), "{:?}", names);
+}
+
+#[test]
+fn verify_exits_non_zero_when_a_file_changed() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("archive");
+    write_file(&root, "a.bin", 2048, 1);
+    write_file(&root, "b.bin", 2048, 2);
+    let manifest = dir.path().join("baseline.json");
+    biebie()
+        .arg(&root)
+        .args(["--quiet", "--output", "json", "--output-file"])
+        .arg(&manifest)
+        .assert()
+        .success();
+
+    biebie()
+        .arg(&root)
+        .arg("--verify")
+        .arg(&manifest)
+        .assert()
+        .success();
+
+    write_file(&root, "b.bin", 2048, 3);
+    let output = biebie()
+        .arg(&root)
+        .arg("--verify")
+        .arg(&manifest)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("1 mismatched"), "{}", stdout);
+    assert!(stdout.contains("b.bin"), "{}", stdout);
+}
+
+#[test]
+fn files_from_stdin_hashes_every_listed_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut list = String::new();
+    for (i, name) in ["a.jpg", "sub/b.jpg", "other/c.jpg"].iter().enumerate() {
+        write_file(dir.path(), name, 2048, i as u8);
+        list.push_str(&format!("{}\n", dir.path().join(name).display()));
+    }
+    let missing = dir.path().join("missing.jpg");
+    list.push_str(&format!("{}\n", missing.display()));
+
+    let output = biebie()
+        .args(["--files-from", "-", "--quiet", "--output", "json"])
+        .write_stdin(list)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let files: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let mut hashes: Vec<(String, String)> = files
+        .iter()
+        .map(|file| {
+            (
+                file["filename"].as_str().unwrap().to_string(),
+                file["hash"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    hashes.sort();
+    let mut expected: Vec<(String, String)> = ["a.jpg", "other/c.jpg", "sub/b.jpg"]
+        .iter()
+        .zip([0u8, 2, 1])
+        .map(|(name, fill)| {
+            (
+                dir.path().join(name).display().to_string(),
+                biebie::hash::HashAlgorithm::Blake3.hash_bytes(&[fill; 2048]),
+            )
+        })
+        .collect();
+    expected.sort();
+    assert_eq!(hashes, expected);
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&missing.display().to_string()),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn quiet_json_stdout_is_only_the_payload() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.jpg", 2048, 1);
+    write_file(dir.path(), "b.jpg", 2048, 2);
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--quiet", "--output", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.trim_end().lines().count(), 1, "{}", stdout);
+    let files: Vec<serde_json::Value> = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(files.len(), 2);
+}
+
+#[test]
+fn status_goes_to_stderr_and_stdout_is_exactly_the_payload() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.jpg", 2048, 1);
+    write_file(dir.path(), "b.jpg", 2048, 2);
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--output", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let files: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(files.len(), 2);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Scanning"), "{}", stderr);
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--output", "csv"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let mut reader = csv::Reader::from_reader(output.stdout.as_slice());
+    assert_eq!(&reader.headers().unwrap()[0], "filename");
+    let names: Vec<String> = reader
+        .records()
+        .map(|record| record.unwrap()[0].to_string())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            dir.path().join("a.jpg").display().to_string(),
+            dir.path().join("b.jpg").display().to_string(),
+        ]
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Scanning"), "{}", stderr);
+}
+
+#[test]
+fn scan_completes_with_trace_logging() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "nested/a.jpg", 2048, 1);
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["-vvv", "--output", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let files: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(files.len(), 1);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("DEBUG"), "{}", stderr);
+}
+
+#[test]
+fn existing_output_file_is_kept_unless_forced() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("photos");
+    write_file(&root, "a.jpg", 2048, 1);
+    let output_file = dir.path().join("scan.json");
+    fs::write(&output_file, "yesterday").unwrap();
+
+    let output = biebie()
+        .arg(&root)
+        .args(["--output", "json", "--output-file"])
+        .arg(&output_file)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("--force"), "{}", stderr);
+    // Refused before scanning
+    assert!(!stderr.contains("Scanning"), "{}", stderr);
+    assert_eq!(fs::read_to_string(&output_file).unwrap(), "yesterday");
+
+    biebie()
+        .arg(&root)
+        .args(["--output", "json", "--force", "--output-file"])
+        .arg(&output_file)
+        .assert()
+        .success();
+    let files: Vec<serde_json::Value> =
+        serde_json::from_str(&fs::read_to_string(&output_file).unwrap()).unwrap();
+    assert_eq!(files.len(), 1);
+}
+
+#[test]
+fn appending_two_csv_scans_writes_the_header_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let output_file = dir.path().join("inventory.csv");
+    for (run, fill) in [("first", 1), ("second", 2)] {
+        let root = dir.path().join(run);
+        write_file(&root, "a.jpg", 2048, fill);
+        write_file(&root, "b.jpg", 4096, fill);
+        biebie()
+            .arg(&root)
+            .args(["--output", "csv", "--append", "--output-file"])
+            .arg(&output_file)
+            .assert()
+            .success();
+    }
+
+    let written = fs::read_to_string(&output_file).unwrap();
+    let lines: Vec<&str> = written.lines().collect();
+    assert_eq!(lines.len(), 5, "{}", written);
+    assert!(lines[0].starts_with("filename,"));
+    assert_eq!(
+        lines
+            .iter()
+            .filter(|line| line.starts_with("filename,"))
+            .count(),
+        1
+    );
+    let rows: Vec<&str> = lines[1..]
+        .iter()
+        .map(|line| line.split(',').next().unwrap())
+        .collect();
+    assert_eq!(
+        rows,
+        ["first/a.jpg", "first/b.jpg", "second/a.jpg", "second/b.jpg"].map(|name| dir
+            .path()
+            .join(name)
+            .display()
+            .to_string())
+    );
+}
+
+#[test]
+fn no_dedup_keeps_every_identical_file() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.jpg", 2048, 7);
+    write_file(dir.path(), "b.jpg", 2048, 7);
+
+    let scanned = |extra: &[&str]| -> Vec<serde_json::Value> {
+        let output = biebie()
+            .arg(dir.path())
+            .args(["--quiet", "--output", "json"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        serde_json::from_slice(&output.stdout).unwrap()
+    };
+    assert_eq!(scanned(&["--no-dedup"]).len(), 2);
+    assert_eq!(scanned(&[]).len(), 1);
+}
+
+#[test]
+fn upload_failure_prints_a_json_error_object() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.jpg", 2048, 1);
+    // Bound then dropped, so nothing is listening on the port
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--quiet", "--output", "json", "--upload-retries", "0"])
+        .arg("--api")
+        .arg(format!("http://127.0.0.1:{}/upload", port))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let error: serde_json::Value = serde_json::from_str(stdout.lines().last().unwrap()).unwrap();
+    assert_eq!(error["context"], "upload");
+    assert!(error["error"]
+        .as_str()
+        .unwrap()
+        .contains("Failed to upload"));
+}
+
+#[test]
+fn compact_json_has_no_newlines_between_elements() {
+    let dir = tempfile::tempdir().unwrap();
+    for i in 0..3 {
+        write_file(dir.path(), &format!("{}.jpg", i), 2048, i);
+    }
+
+    let stdout = |flag: &str| -> String {
+        let output = biebie()
+            .arg(dir.path())
+            .args(["--quiet", "--output", "json", flag])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let compact = stdout("--compact");
+    assert!(!compact.trim_end().contains('\n'), "{}", compact);
+    let pretty = stdout("--pretty");
+    assert!(pretty.lines().count() > 3, "{}", pretty);
+
+    let compact: serde_json::Value = serde_json::from_str(&compact).unwrap();
+    let pretty: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+    assert_eq!(compact.as_array().unwrap().len(), 3);
+    assert_eq!(compact, pretty);
+}
+
+#[test]
+fn console_without_color_or_emoji_has_no_escapes_or_emoji() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.jpg", 2048, 1);
+    write_file(dir.path(), "clip.mp4", 4096, 2);
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--output", "console", "--color", "never", "--no-emoji"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    for stream in [&output.stdout, &output.stderr] {
+        let text = String::from_utf8_lossy(stream);
+        // Durations may print as `µs`, so check for emoji rather than pure ASCII
+        assert_eq!(biebie::status::strip_emoji(&text), text);
+        assert!(!stream.contains(&0x1b), "{}", text);
+    }
+    assert!(String::from_utf8_lossy(&output.stdout).contains("a.jpg"));
+
+    let colored = biebie()
+        .arg(dir.path())
+        .args(["--output", "console", "--color", "always"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(colored.stdout).unwrap();
+    assert!(stdout.contains("\u{1b}["), "{}", stdout);
+}
+
+#[test]
+fn sampled_duplicate_warning_honours_no_emoji() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.bin", 16 * 1024, 7);
+    write_file(dir.path(), "b.bin", 16 * 1024, 7);
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--find-duplicates", "--no-emoji", "--no-dedup"])
+        .args(["--large-threshold", "4KB", "--sample-threshold", "8KB"])
+        .args(["--sample-size", "1KB"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("may be false positives"), "{}", stderr);
+    assert_eq!(biebie::status::strip_emoji(&stderr), stderr);
+}
+
+#[test]
+fn manifest_hash_is_part_of_json_and_ndjson_output() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.jpg", 2048, 1);
+    write_file(dir.path(), "b.jpg", 2048, 2);
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--manifest-hash", "--quiet", "--output", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let manifest_hash = json["manifest_hash"].as_str().unwrap();
+    assert_eq!(manifest_hash.len(), 64);
+    assert_eq!(json["files"].as_array().unwrap().len(), 2);
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--manifest-hash", "--quiet", "--output", "ndjson"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let records: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 3);
+    assert!(records[..2].iter().all(|record| record["hash"].is_string()));
+    assert_eq!(
+        records[2],
+        serde_json::json!({ "manifest_hash": manifest_hash })
+    );
+}
+
+#[test]
+fn ndjson_of_an_empty_scan_prints_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--quiet", "--output", "ndjson"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty(), "{:?}", output.stdout);
+}
+
+#[test]
+fn fingerprinted_output_reads_back_with_since_and_verify() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("photos");
+    write_file(&root, "a.jpg", 2048, 1);
+    write_file(&root, "b.jpg", 2048, 2);
+
+    for format in ["json", "ndjson"] {
+        let previous = dir.path().join(format!("previous.{}", format));
+        biebie()
+            .arg(&root)
+            .args([
+                "--manifest-hash",
+                "--quiet",
+                "--output",
+                format,
+                "--output-file",
+            ])
+            .arg(&previous)
+            .assert()
+            .success();
+
+        biebie()
+            .arg(&root)
+            .arg("--verify")
+            .arg(&previous)
+            .assert()
+            .success();
+
+        let output = biebie()
+            .arg(&root)
+            .args(["--quiet", "--output", "json", "--since"])
+            .arg(&previous)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let changed: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(changed, serde_json::json!([]), "{}", format);
+    }
+}
+
+#[test]
+fn stats_only_prints_totals_without_file_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.jpg", 2048, 1);
+    write_file(dir.path(), "b.jpg", 2048, 1);
+    write_file(dir.path(), "clip.mp4", 4096, 2);
+
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--stats-only", "--quiet", "--output", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(stats.get("files").is_none(), "{}", stats);
+    assert_eq!(stats["files_found"], 3);
+    assert_eq!(stats["unique_files"], 2);
+    assert_eq!(stats["total_bytes"], 2048 + 4096);
+    assert_eq!(stats["by_filetype"]["video"]["count"], 1);
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("a.jpg"), "{}", stdout);
+}
+
+#[test]
+fn concurrent_upload_logs_the_error_response_body() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.jpg", 2048, 1);
+
+    let (url, request) = answer_one_post(
+        b"HTTP/1.1 400 Bad Request\r\nContent-Length: 14\r\nConnection: close\r\n\r\nquota exceeded",
+    );
+    let output = biebie()
+        .arg(dir.path())
+        .args(["--quiet", "--output", "json", "--concurrent-uploads", "2"])
+        .arg("--api")
+        .arg(&url)
+        .output()
+        .unwrap();
+    request.join().unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("HTTP 400"), "{}", stderr);
+    assert!(stderr.contains("quota exceeded"), "{}", stderr);
+}
@@ -0,0 +1,110 @@
+use std::fs;
+use std::path::Path;
+
+use biebie::hash::HashAlgorithm;
+use biebie::progress::ProgressMode;
+use biebie::{scan_folder, FileFilter, HashStrategy, ProcessOptions, ScanOptions, WalkOptions};
+use sha2::{Digest, Sha256};
+
+/// Writes `contents` to `relative` under `root`, creating parents
+fn write_file(root: &Path, relative: &str, contents: &[u8]) {
+    let path = root.join(relative);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+/// Default options without the progress bar
+fn quiet_options() -> ScanOptions {
+    let mut options = ScanOptions::default();
+    options.process.progress = ProgressMode::None;
+    options
+}
+
+#[test]
+fn scan_folder_reports_every_file_in_a_temp_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.jpg", b"first");
+    write_file(dir.path(), "nested/b.mp4", b"second file");
+
+    let root = dir.path().to_str().unwrap().to_string();
+    let report = scan_folder(&[root], &quiet_options(), None);
+
+    assert!(report.errors.is_empty());
+    let mut files: Vec<_> = report
+        .files
+        .iter()
+        .map(|file| (file.full_path().to_string(), file.size, file.hash.clone()))
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        vec![
+            (
+                dir.path().join("a.jpg").display().to_string(),
+                5,
+                blake3::hash(b"first").to_hex().to_string()
+            ),
+            (
+                dir.path().join("nested/b.mp4").display().to_string(),
+                11,
+                blake3::hash(b"second file").to_hex().to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn scan_options_built_directly_drive_the_scan() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "keep.jpg", b"large enough");
+    write_file(dir.path(), "tiny.jpg", b"x");
+    write_file(dir.path(), "other.png", b"wrong extension");
+    write_file(dir.path(), "skip/excluded.jpg", b"excluded by glob");
+
+    let excludes = biebie::scanner::build_exclude_set(&["skip/".to_string()]).unwrap();
+    let options = ScanOptions {
+        filter: FileFilter::new(&["jpg".to_string()], excludes, 5, None),
+        hash: HashStrategy {
+            algorithm: HashAlgorithm::Sha256,
+            ..HashStrategy::default()
+        },
+        walk: WalkOptions {
+            follow_symlinks: true,
+            ..WalkOptions::default()
+        },
+        process: ProcessOptions {
+            progress: ProgressMode::None,
+            threads: Some(2),
+            ..ProcessOptions::default()
+        },
+    };
+    let root = dir.path().to_str().unwrap().to_string();
+    let report = scan_folder(&[root], &options, None);
+
+    assert_eq!(report.files.len(), 1);
+    let file = &report.files[0];
+    assert!(file.full_path().ends_with("keep.jpg"));
+    assert_eq!(file.hash_algo, "sha256");
+    let expected = Sha256::digest(b"large enough");
+    assert_eq!(file.hash, format!("{:x}", expected));
+    assert_eq!(report.stats.threads, 2);
+}
+
+#[test]
+fn report_stats_count_unique_and_duplicate_files() {
+    let dir = tempfile::tempdir().unwrap();
+    write_file(dir.path(), "a.jpg", b"same");
+    write_file(dir.path(), "b/a-copy.jpg", b"same");
+    write_file(dir.path(), "c/a-copy.jpg", b"same");
+    write_file(dir.path(), "d.jpg", b"different");
+
+    let root = dir.path().to_str().unwrap().to_string();
+    let report = scan_folder(&[root], &quiet_options(), None);
+
+    assert_eq!(report.stats.unique_files, report.files.len());
+    assert_eq!(report.stats.unique_files, 2);
+    assert_eq!(report.stats.files_found, 4);
+    assert_eq!(report.stats.duplicate_files, 2);
+    assert_eq!(report.stats.duplicate_bytes, 8);
+    assert_eq!(report.into_files().len(), 2);
+}
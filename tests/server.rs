@@ -0,0 +1,99 @@
+#![cfg(feature = "server")]
+
+use std::fs;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use biebie::progress::ProgressMode;
+use biebie::ScanOptions;
+
+/// Starts `biebie::server::serve` for `root` on a free local port and returns its base URL
+fn start_server(root: &std::path::Path) -> String {
+    // Bound then dropped so the server can take the port
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let mut options = ScanOptions::default();
+    options.process.progress = ProgressMode::None;
+    let serve_addr = addr.clone();
+    let roots = [root.display().to_string()];
+    std::thread::spawn(move || biebie::server::serve(&serve_addr, &roots, options));
+
+    let url = format!("http://{}", addr);
+    for _ in 0..100 {
+        if std::net::TcpStream::connect(&addr).is_ok() {
+            return url;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    panic!("server did not start on {}", addr);
+}
+
+#[test]
+fn scan_endpoint_returns_the_file_list_as_json() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.jpg"), [1; 2048]).unwrap();
+    fs::create_dir(dir.path().join("nested")).unwrap();
+    fs::write(dir.path().join("nested/b.jpg"), [2; 4096]).unwrap();
+    let url = start_server(dir.path());
+
+    let client = reqwest::blocking::Client::new();
+    let response = client
+        .get(format!("{}/scan", url))
+        .query(&[("path", dir.path().to_str().unwrap())])
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let report: serde_json::Value = response.json().unwrap();
+    let mut files: Vec<(String, u64)> = report["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| {
+            (
+                file["filename"].as_str().unwrap().to_string(),
+                file["size"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        [
+            (dir.path().join("a.jpg").display().to_string(), 2048),
+            (dir.path().join("nested/b.jpg").display().to_string(), 4096),
+        ]
+    );
+
+    let missing = client
+        .get(format!("{}/scan", url))
+        .query(&[("path", dir.path().join("missing").to_str().unwrap())])
+        .send()
+        .unwrap();
+    assert_eq!(missing.status(), 404);
+}
+
+#[test]
+fn scan_endpoint_refuses_paths_outside_the_served_folder() {
+    let dir = tempfile::tempdir().unwrap();
+    let served = dir.path().join("served");
+    fs::create_dir_all(served.join("inner")).unwrap();
+    fs::create_dir(dir.path().join("private")).unwrap();
+    let url = start_server(&served);
+
+    let client = reqwest::blocking::Client::new();
+    let status = |path: &std::path::Path| {
+        client
+            .get(format!("{}/scan", url))
+            .query(&[("path", path.to_str().unwrap())])
+            .send()
+            .unwrap()
+            .status()
+    };
+    assert_eq!(status(&served.join("inner")), 200);
+    assert_eq!(status(&dir.path().join("private")), 403);
+    // `..` is resolved before the check
+    assert_eq!(status(&served.join("../private")), 403);
+}